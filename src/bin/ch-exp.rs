@@ -32,6 +32,10 @@ struct Args {
 
     #[arg(long, value_parser = bin_utils::parse_comma_string_list)]
     planner_type: Option<std::vec::Vec<String>>,
+
+    // Write the computed join index into the DB's __join_keys__ so later runs can skip the join
+    #[arg(long)]
+    persist_join_index: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -99,10 +103,14 @@ fn main() {
     let mut records = vec![];
     let query = parser.parse(QUERY, &Default::default()).unwrap();
     let mut exec_stats = ExecStats::new();
-    query
-        .table
-        .table
-        .eval_join(&ExecParams::default(), &mut exec_stats, None);
+    query.table.table.eval_join(
+        &ExecParams {
+            persist_join_index: args.persist_join_index,
+            ..Default::default()
+        },
+        &mut exec_stats,
+        None,
+    );
 
     for trial in 0..args.num_trials {
         let mut outputs = vec![];
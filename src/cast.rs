@@ -0,0 +1,179 @@
+use super::expr::{DBCol, DataType};
+use super::utils;
+use chrono::{TimeZone, Utc};
+use roaring::RoaringBitmap;
+use std::convert::TryFrom;
+use std::fmt;
+
+// Conversions of cols between data types, for `CAST(x AS type)` and `x::type`.
+
+// A value which cannot be converted to the type it is cast to.
+#[derive(Debug, Clone)]
+pub struct CastError {
+    // None if no value of the type can be converted
+    val: Option<String>,
+    from: DataType,
+    to: DataType,
+}
+
+impl fmt::Display for CastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.val {
+            Some(val) => write!(
+                f,
+                "Cannot cast {:?} value {} to {:?}",
+                self.from, val, self.to
+            ),
+            None => write!(f, "Cannot cast {:?} to {:?}", self.from, self.to),
+        }
+    }
+}
+
+// Converts `col` to `data_type`. Numbers are converted with rounding and checked for overflow,
+// strings are parsed, and anything can be turned into a string. Positions in `nulls` are not
+// converted, since their values are meaningless.
+pub fn cast(
+    col: &DBCol,
+    nulls: Option<&RoaringBitmap>,
+    data_type: DataType,
+) -> Result<DBCol, CastError> {
+    // Converts each value with `convert`, which gives None if the value cannot be converted.
+    fn convert_vals<T: fmt::Debug, U: Clone>(
+        vals: &[T],
+        nulls: Option<&RoaringBitmap>,
+        (from, to): (DataType, DataType),
+        null_val: U,
+        convert: impl Fn(&T) -> Option<U>,
+    ) -> Result<Vec<U>, CastError> {
+        vals.iter()
+            .enumerate()
+            .map(|(i, val)| {
+                if nulls.is_some_and(|nulls| nulls.contains(i as u32)) {
+                    Ok(null_val.clone())
+                } else {
+                    convert(val).ok_or_else(|| CastError {
+                        val: Some(format!("{:?}", val)),
+                        from,
+                        to,
+                    })
+                }
+            })
+            .collect()
+    }
+
+    macro_rules! convert {
+        ($vals:expr, $null_val:expr, $convert:expr) => {
+            convert_vals(
+                $vals,
+                nulls,
+                (col.data_type(), data_type),
+                $null_val,
+                $convert,
+            )
+        };
+    }
+
+    if col.data_type() == data_type {
+        return Ok(col.clone());
+    }
+    let result = match (col, data_type) {
+        (DBCol::Str(vals), DataType::Int) => {
+            DBCol::Int(convert!(vals, 0, |v: &String| v.trim().parse().ok())?)
+        }
+        (DBCol::Str(vals), DataType::Long) => {
+            DBCol::Long(convert!(vals, 0, |v: &String| v.trim().parse().ok())?)
+        }
+        (DBCol::Str(vals), DataType::Float) => {
+            DBCol::Float(convert!(vals, 0., |v: &String| v.trim().parse().ok())?)
+        }
+        (DBCol::Str(vals), DataType::Double) => {
+            DBCol::Double(convert!(vals, 0., |v: &String| v.trim().parse().ok())?)
+        }
+        (DBCol::Str(vals), DataType::Bool) => DBCol::Bool(convert!(vals, false, |v: &String| {
+            match &v.trim().to_lowercase()[..] {
+                "t" | "true" | "y" | "yes" | "on" | "1" => Some(true),
+                "f" | "false" | "n" | "no" | "off" | "0" => Some(false),
+                _ => None,
+            }
+        })?),
+        (DBCol::Str(vals), DataType::DateTime) => {
+            DBCol::DateTime(convert!(vals, Utc.timestamp(0, 0), |v: &String| {
+                utils::try_parse_datetime(v.trim())
+            })?)
+        }
+        (DBCol::Str(vals), DataType::Duration) => {
+            DBCol::Duration(convert!(vals, chrono::Duration::zero(), |v: &String| {
+                utils::try_parse_duration(v.trim())
+            })?)
+        }
+        (col, DataType::Str) => DBCol::Str(match col {
+            DBCol::Int(vals) => vals.iter().map(|v| v.to_string()).collect(),
+            DBCol::Long(vals) => vals.iter().map(|v| v.to_string()).collect(),
+            DBCol::Float(vals) => vals.iter().map(|v| v.to_string()).collect(),
+            DBCol::Double(vals) => vals.iter().map(|v| v.to_string()).collect(),
+            DBCol::Bool(vals) => vals.iter().map(|v| v.to_string()).collect(),
+            DBCol::DateTime(vals) => vals
+                .iter()
+                .map(|v| v.format("%Y-%m-%d %H:%M:%S").to_string())
+                .collect(),
+            DBCol::Duration(vals) => vals.iter().map(|v| v.to_string()).collect(),
+            DBCol::Months(vals) => vals.iter().map(|v| utils::format_months(*v)).collect(),
+            DBCol::Str(_) => unreachable!(),
+        }),
+        (DBCol::Bool(vals), DataType::Int) => DBCol::Int(vals.iter().map(|v| *v as i32).collect()),
+        (DBCol::Bool(vals), DataType::Long) => {
+            DBCol::Long(vals.iter().map(|v| *v as i64).collect())
+        }
+        (DBCol::Int(vals), DataType::Bool) => DBCol::Bool(vals.iter().map(|v| *v != 0).collect()),
+        (DBCol::Long(vals), DataType::Bool) => DBCol::Bool(vals.iter().map(|v| *v != 0).collect()),
+        (DBCol::Int(vals), DataType::Long) => DBCol::Long(vals.iter().map(|v| *v as i64).collect()),
+        (DBCol::Long(vals), DataType::Int) => {
+            DBCol::Int(convert!(vals, 0, |v: &i64| i32::try_from(*v).ok())?)
+        }
+        (DBCol::Float(_) | DBCol::Double(_), DataType::Int) => {
+            let vals: Vec<f64> = col.double_iter().collect();
+            DBCol::Int(convert!(&vals, 0, |v: &f64| {
+                let v = v.round();
+                Some(v as i32).filter(|_| v >= i32::MIN as f64 && v <= i32::MAX as f64)
+            })?)
+        }
+        (DBCol::Float(_) | DBCol::Double(_), DataType::Long) => {
+            let vals: Vec<f64> = col.double_iter().collect();
+            DBCol::Long(convert!(&vals, 0, |v: &f64| {
+                let v = v.round();
+                Some(v as i64).filter(|_| v >= i64::MIN as f64 && v < i64::MAX as f64)
+            })?)
+        }
+        (DBCol::Int(_) | DBCol::Long(_) | DBCol::Double(_), DataType::Float) => {
+            DBCol::Float(col.double_iter().map(|v| v as f32).collect())
+        }
+        (DBCol::Int(_) | DBCol::Long(_) | DBCol::Float(_), DataType::Double) => {
+            DBCol::Double(col.double_iter().collect())
+        }
+        (col, data_type) => {
+            return Err(CastError {
+                val: None,
+                from: col.data_type(),
+                to: data_type,
+            });
+        }
+    };
+    Ok(result)
+}
+
+// Checks whether values of type `from` can be converted to `to` at all, so that casts between
+// unsupported types are rejected when they are parsed rather than when they are run.
+pub fn check_cast(from: DataType, to: DataType) -> Result<(), CastError> {
+    let empty = match from {
+        DataType::Int => DBCol::Int(vec![]),
+        DataType::Long => DBCol::Long(vec![]),
+        DataType::Float => DBCol::Float(vec![]),
+        DataType::Double => DBCol::Double(vec![]),
+        DataType::Str => DBCol::Str(vec![]),
+        DataType::Bool => DBCol::Bool(vec![]),
+        DataType::DateTime => DBCol::DateTime(vec![]),
+        DataType::Duration => DBCol::Duration(vec![]),
+        DataType::Months => DBCol::Months(vec![]),
+    };
+    cast(&empty, None, to).map(|_| ())
+}
@@ -7,3 +7,19 @@ pub const BLOCK_SIZE: usize = 512;
 
 pub const READ_BUF_SIZE: usize = 8 * 4096;
 //pub const READ_BUF_SIZE: usize = 32;
+
+// IN lists with at least this many constant items are looked up through a hash set instead of a
+// linear scan.
+pub const IN_LIST_HASH_THRESHOLD: usize = 8;
+
+// The selectivity the join order optimizer assumes for predicates it has no statistics for.
+pub const DEFAULT_SELECTIVITY: f64 = 1. / 3.;
+
+// The cost the planners assume for evaluating a semi-join atom (`IN (subquery)` or `EXISTS`) when
+// there is none given for it, relative to the default cost of 1 for other atoms.
+pub const SEMI_JOIN_COST: f64 = 10.;
+
+// The size of the bloom filters built for joins, and the number of bits set for each key. Ten bits
+// and seven hashes per key give about 1% false positives.
+pub const BLOOM_FILTER_BITS_PER_KEY: usize = 10;
+pub const BLOOM_FILTER_NUM_HASHES: usize = 7;
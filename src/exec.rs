@@ -1,7 +1,8 @@
+use super::cast::CastError;
 use super::db::DB;
 use super::expr::{DBCol, DBResult};
-use super::parser::Query;
-use super::table::Table;
+use super::parser::{PredNode, Query};
+use super::table::{Id, Table};
 use log::debug;
 use roaring::RoaringBitmap;
 use serde::{Deserialize, Serialize};
@@ -14,12 +15,32 @@ use std::path::PathBuf;
 use std::rc::Rc;
 use std::time::Instant;
 
+// The groups of a GROUP BY: the key of each group, in the order in which the groups first appear,
+// along with the positions of the rows in each group. A key has a single-value col for each GROUP
+// BY item, or an empty col where the item is NULL.
+#[derive(Clone, Debug, Default)]
+pub struct Groups {
+    pub keys: Vec<Vec<DBCol>>,
+    pub rows: Vec<Vec<usize>>,
+}
+
+impl Groups {
+    // Empty if the query has no GROUP BY
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.rows.iter().map(|rows| rows.len()).sum()
+    }
+}
+
 // `index` stores the set of indices which are considered valid in the current context
 pub struct RunContext {
     // This should be in reference to the `ref_table` found in `TableNode`.
     // If this is None, we want the entire range of values.
     pub index: Option<RoaringBitmap>,
-    pub groups: Vec<Vec<DBCol>>,
+    pub groups: Groups,
     // XXX Passing a `ref_table` table will make the expressions evaluate according to this table. This
     // should only be used in special debugging circumstances.
     pub ref_table: Option<Rc<dyn Table>>,
@@ -53,7 +74,11 @@ pub struct ExecParams {
     pub special_case_one_multi_table_clause: bool, // If we have a case-2 query, and only one of the clauses is multi-table, we can special case it to make it just perform conjoin/disjoin after propagating all the other clauses
     pub dont_use_centroids: bool, // This is equivalent special casing every multi-table clause
     pub include_debug_info: bool,
-    pub check_plan_only: bool, // used to check ordering of bdc plans
+    pub check_plan_only: bool,    // used to check ordering of bdc plans
+    pub persist_join_index: bool, // write hash-joined indexes into __join_keys__ for later runs
+    pub bloom_filter_joins: bool, // drop rows which cannot join through a bloom filter of the other, filtered side
+    pub sort_merge_joins: bool, // join equalities by sorting and merging both sides instead of hashing
+    pub limit_chunk_size: Option<usize>, // for queries with a LIMIT, filter chunks of this many rows until enough rows pass
 }
 
 impl Default for ExecParams {
@@ -66,6 +91,10 @@ impl Default for ExecParams {
             dont_use_centroids: false,
             include_debug_info: false,
             check_plan_only: false,
+            persist_join_index: false,
+            bloom_filter_joins: false,
+            sort_merge_joins: false,
+            limit_chunk_size: None,
         }
     }
 }
@@ -79,8 +108,22 @@ pub struct ExecStats {
     pub plan_time_ms: u128,
     pub num_bufs_read: u128,
     pub num_joined_records: u128,
+    pub num_join_key_records: u128, // join keys which were read, one per row of the side each key refers to
+    pub num_bloom_filtered_records: u128, // rows dropped by the bloom filters of joins before joining
+    pub num_scanned_records: u128, // rows of the table which were filtered, fewer than all of them if a LIMIT was met early
     pub num_final_records: u128,
     pub num_synthesized_records: u128, // includes all intermediate records which are created that may not be as a result in the conjoin/disjoin functions
+    #[serde(skip)]
+    pub error: Option<ExecError>, // the first error met while evaluating, which the query fails with
+    #[serde(skip)]
+    pub semi_join_results: HashMap<Id, Rc<(usize, Vec<DBResult>)>>, // the number of rows and the keys of each semi-join's subquery, by the id of the semi-join
+}
+
+// An error which makes a query fail while it is run, as opposed to when it is parsed
+#[derive(Debug, Clone)]
+pub enum ExecError {
+    // A value which cannot be converted to the type it is cast to
+    CastError(CastError),
 }
 
 impl ExecStats {
@@ -93,8 +136,13 @@ impl ExecStats {
             plan_time_ms: 0,
             num_bufs_read: 0,
             num_joined_records: 0,
+            num_join_key_records: 0,
+            num_bloom_filtered_records: 0,
+            num_scanned_records: 0,
             num_final_records: 0,
             num_synthesized_records: 0,
+            error: None,
+            semi_join_results: HashMap::new(),
         }
     }
 }
@@ -133,6 +181,8 @@ impl<'a> Executor<'a> {
         exec_stats: &mut ExecStats,
     ) -> Vec<DBResult> {
         let total_time_beg = Instant::now();
+        exec_stats.error = None;
+        exec_stats.semi_join_results.clear();
 
         debug!("PRINTING QUERY\n{}", query);
 
@@ -143,22 +193,50 @@ impl<'a> Executor<'a> {
 
         let mut run_context = RunContext {
             index: None,
-            groups: vec![],
+            groups: Default::default(),
             ref_table: None,
             exec_params: exec_params.clone(),
         };
 
         debug!("EVALUATING JOIN");
-        query
-            .table
-            .table
-            .eval_join(exec_params, exec_stats, query.filter.as_ref());
+        let pushed_tables =
+            query
+                .table
+                .table
+                .eval_join(exec_params, exec_stats, query.filter.as_ref());
+        // The atoms pushed below the joins were already applied while joining.
+        let has_filter = query.filter.is_some();
+        query.filter = query
+            .filter
+            .take()
+            .and_then(|pred| pred.without_pushdown_atoms(&pushed_tables));
         let data_num = query.table.table.len();
+        // Only the first rows passing the filter are needed for a LIMIT, so they are searched for in
+        // chunks of the table.
+        let row_limit = exec_params.limit_chunk_size.and(query.row_limit());
         debug!("EVALUATING FILTER");
         let now = Instant::now();
-        run_context.index = query.filter.as_mut().and_then(|pred| {
-            Some(pred.eval(&(0..data_num as u32).collect(), exec_params, exec_stats))
-        });
+        exec_stats.num_scanned_records = data_num as u128;
+        run_context.index = match (query.filter.as_mut(), row_limit) {
+            (Some(pred), Some(row_limit)) => Some(eval_in_chunks(
+                pred,
+                data_num,
+                row_limit,
+                exec_params,
+                exec_stats,
+            )),
+            (Some(pred), None) => {
+                Some(pred.eval(&(0..data_num as u32).collect(), exec_params, exec_stats))
+            }
+            (None, Some(row_limit)) => {
+                let num = cmp::min(row_limit, data_num);
+                exec_stats.num_scanned_records = num as u128;
+                Some((0..num as u32).collect())
+            }
+            // The whole filter was pushed down, so every joined row passes it.
+            (None, None) if has_filter => Some((0..data_num as u32).collect()),
+            (None, None) => None,
+        };
         exec_stats.pred_only_time_ms = now.elapsed().as_millis();
 
         if let Some(index) = &run_context.index {
@@ -172,7 +250,7 @@ impl<'a> Executor<'a> {
         run_context.groups = query.group_by.eval(&run_context, exec_stats);
         debug!(
             "EVALED GROUP BYS:\n{}",
-            run_context.groups[..cmp::min(10, run_context.groups.len())]
+            run_context.groups.keys[..cmp::min(10, run_context.groups.keys.len())]
                 .iter()
                 .map(|groups| format!(
                     "{}({})",
@@ -188,11 +266,36 @@ impl<'a> Executor<'a> {
         );
         debug!("EVALUATING PROJECTION");
         let results = query.projection.eval(&run_context, exec_stats);
+        let results = match &query.having {
+            Some(having) => {
+                debug!("EVALUATING HAVING");
+                having.eval(results, &run_context, exec_stats)
+            }
+            None => results,
+        };
+        let results = query.projection.eval_distinct(results);
+        debug!("EVALUATING ORDER BY");
+        let results = query.order_by.eval(results, &run_context, exec_stats);
         exec_stats.total_time_ms = total_time_beg.elapsed().as_millis();
         exec_stats.num_final_records = results[0].len() as u128;
         results
     }
 
+    // Runs `query` like `run`, but fails with the first error met while evaluating it, e.g., a value
+    // which cannot be converted by a CAST. `run` leaves such an error in `exec_stats.error`.
+    pub fn try_run(
+        &mut self,
+        query: Query,
+        exec_params: &ExecParams,
+        exec_stats: &mut ExecStats,
+    ) -> Result<Vec<DBResult>, ExecError> {
+        let results = self.run(query, exec_params, exec_stats);
+        match exec_stats.error.take() {
+            Some(err) => Err(err),
+            None => Ok(results),
+        }
+    }
+
     pub fn run_without_eval_join(
         &mut self,
         mut query: Query,
@@ -200,6 +303,8 @@ impl<'a> Executor<'a> {
         exec_stats: &mut ExecStats,
     ) -> Vec<DBResult> {
         let total_time_beg = Instant::now();
+        exec_stats.error = None;
+        exec_stats.semi_join_results.clear();
 
         query
             .filter
@@ -208,7 +313,7 @@ impl<'a> Executor<'a> {
 
         let mut run_context = RunContext {
             index: None,
-            groups: vec![],
+            groups: Default::default(),
             ref_table: None,
             exec_params: exec_params.clone(),
         };
@@ -233,7 +338,7 @@ impl<'a> Executor<'a> {
         run_context.groups = query.group_by.eval(&run_context, exec_stats);
         debug!(
             "EVALED GROUP BYS:\n{}",
-            run_context.groups[..cmp::min(10, run_context.groups.len())]
+            run_context.groups.keys[..cmp::min(10, run_context.groups.keys.len())]
                 .iter()
                 .map(|groups| format!(
                     "{}({})",
@@ -249,8 +354,45 @@ impl<'a> Executor<'a> {
         );
         debug!("EVALUATING PROJECTION");
         let results = query.projection.eval(&run_context, exec_stats);
+        let results = match &query.having {
+            Some(having) => {
+                debug!("EVALUATING HAVING");
+                having.eval(results, &run_context, exec_stats)
+            }
+            None => results,
+        };
+        let results = query.projection.eval_distinct(results);
+        debug!("EVALUATING ORDER BY");
+        let results = query.order_by.eval(results, &run_context, exec_stats);
         exec_stats.total_time_ms = total_time_beg.elapsed().as_millis();
         exec_stats.num_final_records = results[0].len() as u128;
         results
     }
 }
+
+// Evaluates `pred` on consecutive chunks of `exec_params.limit_chunk_size` rows until `row_limit`
+// rows pass it, and returns the first `row_limit` of them. The atoms are planned anew for each
+// chunk.
+fn eval_in_chunks(
+    pred: &PredNode,
+    data_num: usize,
+    row_limit: usize,
+    exec_params: &ExecParams,
+    exec_stats: &mut ExecStats,
+) -> RoaringBitmap {
+    let chunk_size = exec_params.limit_chunk_size.unwrap();
+    assert!(chunk_size > 0, "limit_chunk_size must be positive");
+    let mut index = RoaringBitmap::new();
+    let mut start = 0;
+    while start < data_num && (index.len() as usize) < row_limit {
+        let end = cmp::min(start + chunk_size, data_num);
+        index |= pred.eval(
+            &(start as u32..end as u32).collect(),
+            exec_params,
+            exec_stats,
+        );
+        start = end;
+    }
+    exec_stats.num_scanned_records = start as u128;
+    index.into_iter().take(row_limit).collect()
+}
@@ -1,14 +1,19 @@
-use super::exec::{ExecStats, RunContext};
-use super::funcs::FUNC_MAP;
-use super::parser::ParseContext;
+use super::cast::{self, CastError};
+use super::config::IN_LIST_HASH_THRESHOLD;
+use super::exec::{ExecError, ExecStats, Groups, RunContext};
+use super::funcs::{self, FUNC_MAP};
+use super::join;
+use super::parser::{ParseContext, ParseError, Parser};
+use super::semi_join::SemiJoin;
 use super::table::{FileCol, Table};
 use super::utils;
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use log::debug;
 use regex::Regex;
+use roaring::RoaringBitmap;
 use sqlparser::ast;
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::rc::Rc;
@@ -43,6 +48,16 @@ macro_rules! do_plus_op {
             DBCol::Int(vec![])
         } else if let (DBCol::DateTime(xvals), DBCol::Duration(yvals))= ($xcol, $ycol) {
             DBCol::DateTime(xvals.iter().zip(yvals.iter()).map(|(x, y)| *x + *y).collect())
+        } else if let (DBCol::Duration(xvals), DBCol::DateTime(yvals))= ($xcol, $ycol) {
+            DBCol::DateTime(xvals.iter().zip(yvals.iter()).map(|(x, y)| *y + *x).collect())
+        } else if let (DBCol::DateTime(xvals), DBCol::Months(yvals))= ($xcol, $ycol) {
+            DBCol::DateTime(xvals.iter().zip(yvals.iter()).map(|(x, y)| utils::add_months(*x, *y)).collect())
+        } else if let (DBCol::Months(xvals), DBCol::DateTime(yvals))= ($xcol, $ycol) {
+            DBCol::DateTime(xvals.iter().zip(yvals.iter()).map(|(x, y)| utils::add_months(*y, *x)).collect())
+        } else if let (DBCol::Duration(xvals), DBCol::Duration(yvals))= ($xcol, $ycol) {
+            DBCol::Duration(xvals.iter().zip(yvals.iter()).map(|(x, y)| *x + *y).collect())
+        } else if let (DBCol::Months(xvals), DBCol::Months(yvals))= ($xcol, $ycol) {
+            DBCol::Months(xvals.iter().zip(yvals.iter()).map(|(x, y)| x + y).collect())
         } else if let (DBCol::DateTime(xvals), DBCol::Str(yvals)) = ($xcol, $ycol) {
             let duration = utils::parse_duration(&yvals[0]);
             DBCol::DateTime(xvals.iter().map(|x| *x + duration).collect())
@@ -60,6 +75,12 @@ macro_rules! do_minus_op {
             DBCol::Duration(xvals.iter().zip(yvals.iter()).map(|(x, y)| *x - *y).collect())
         } else if let (DBCol::DateTime(xvals), DBCol::Duration(yvals))= ($xcol, $ycol) {
             DBCol::DateTime(xvals.iter().zip(yvals.iter()).map(|(x, y)| *x - *y).collect())
+        } else if let (DBCol::DateTime(xvals), DBCol::Months(yvals))= ($xcol, $ycol) {
+            DBCol::DateTime(xvals.iter().zip(yvals.iter()).map(|(x, y)| utils::add_months(*x, -*y)).collect())
+        } else if let (DBCol::Duration(xvals), DBCol::Duration(yvals))= ($xcol, $ycol) {
+            DBCol::Duration(xvals.iter().zip(yvals.iter()).map(|(x, y)| *x - *y).collect())
+        } else if let (DBCol::Months(xvals), DBCol::Months(yvals))= ($xcol, $ycol) {
+            DBCol::Months(xvals.iter().zip(yvals.iter()).map(|(x, y)| x - y).collect())
         } else if let (DBCol::DateTime(xvals), DBCol::Str(yvals)) = ($xcol, $ycol) {
             let duration = utils::parse_duration(&yvals[0]);
             DBCol::DateTime(xvals.iter().map(|x| *x - duration).collect())
@@ -104,6 +125,8 @@ macro_rules! do_bool_op {
             DBCol::Bool(xvals.iter().map(|x| x $op &dt).collect())
         } else if let (DBCol::Duration(xvals), DBCol::Duration(yvals)) = ($xcol, $ycol) {
             DBCol::Bool(xvals.iter().zip(yvals.iter()).map(|(x, y)| x $op y).collect())
+        } else if let (DBCol::Months(xvals), DBCol::Months(yvals)) = ($xcol, $ycol) {
+            DBCol::Bool(xvals.iter().zip(yvals.iter()).map(|(x, y)| x $op y).collect())
         } else if let (DBCol::Duration(xvals), DBCol::Str(yvals)) = ($xcol, $ycol) {
             let duration = utils::parse_duration(&yvals[0]);
             DBCol::Bool(xvals.iter().map(|x| x $op &duration).collect())
@@ -117,7 +140,7 @@ macro_rules! do_bool_op {
     };
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DataType {
     Str,
     Int,
@@ -127,11 +150,12 @@ pub enum DataType {
     Bool,
     DateTime,
     Duration,
+    Months,
 }
 
 // A vector of db values. All values are expressed as DBcols. Singleton values are vectors of size
 // 1.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub enum DBCol {
     Int(Vec<i32>),
     Long(Vec<i64>),
@@ -141,10 +165,36 @@ pub enum DBCol {
     Str(Vec<String>),
     DateTime(Vec<DateTime<Utc>>),
     Duration(Vec<Duration>),
+    // Intervals of whole months, e.g., `interval '1' year`. Months have no fixed length, so these
+    // are added to dates by the calendar.
+    Months(Vec<i32>),
+}
+
+// Cols are keys of the groups of a `DBResult`, so floats are compared by their bits, as they are
+// hashed (see `join::float_bits`). This way a NaN key finds itself, and -0 is the same key as 0.
+impl PartialEq for DBCol {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (DBCol::Int(vals), DBCol::Int(other)) => vals == other,
+            (DBCol::Long(vals), DBCol::Long(other)) => vals == other,
+            (DBCol::Bool(vals), DBCol::Bool(other)) => vals == other,
+            (DBCol::Float(vals), DBCol::Float(other)) => vals
+                .iter()
+                .map(|v| join::float_bits(*v))
+                .eq(other.iter().map(|v| join::float_bits(*v))),
+            (DBCol::Double(vals), DBCol::Double(other)) => vals
+                .iter()
+                .map(|v| join::double_bits(*v))
+                .eq(other.iter().map(|v| join::double_bits(*v))),
+            (DBCol::Str(vals), DBCol::Str(other)) => vals == other,
+            (DBCol::DateTime(vals), DBCol::DateTime(other)) => vals == other,
+            (DBCol::Duration(vals), DBCol::Duration(other)) => vals == other,
+            (DBCol::Months(vals), DBCol::Months(other)) => vals == other,
+            _ => false,
+        }
+    }
 }
 
-// XXX This will implement equals for floats, but this is always a tricky concept; if you use
-// floats for hash map keys, you should reevaluate your life
 impl Eq for DBCol {}
 
 impl Hash for DBCol {
@@ -152,12 +202,12 @@ impl Hash for DBCol {
         match self {
             DBCol::Float(vals) => {
                 for f in vals {
-                    f.to_bits().hash(state);
+                    join::float_bits(*f).hash(state);
                 }
             }
             DBCol::Double(vals) => {
                 for d in vals {
-                    d.to_bits().hash(state);
+                    join::double_bits(*d).hash(state);
                 }
             }
             DBCol::Int(vals) => {
@@ -178,6 +228,9 @@ impl Hash for DBCol {
             DBCol::Duration(vals) => {
                 vals.hash(state);
             }
+            DBCol::Months(vals) => {
+                vals.hash(state);
+            }
         }
     }
 }
@@ -194,6 +247,7 @@ impl DBCol {
             Str(vals) => vals.len(),
             DateTime(vals) => vals.len(),
             Duration(vals) => vals.len(),
+            Months(vals) => vals.len(),
         }
     }
 
@@ -208,6 +262,7 @@ impl DBCol {
             Str(_) => DataType::Str,
             DateTime(_) => DataType::DateTime,
             Duration(_) => DataType::Duration,
+            Months(_) => DataType::Months,
         }
     }
 
@@ -249,6 +304,21 @@ impl DBCol {
         }
     }
 
+    // `len` values of type `data_type` which stand in for NULLs.
+    pub fn placeholders(data_type: DataType, len: usize) -> DBCol {
+        match data_type {
+            DataType::Int => DBCol::Int(vec![0; len]),
+            DataType::Long => DBCol::Long(vec![0; len]),
+            DataType::Float => DBCol::Float(vec![0.; len]),
+            DataType::Double => DBCol::Double(vec![0.; len]),
+            DataType::Str => DBCol::Str(vec![String::new(); len]),
+            DataType::Bool => DBCol::Bool(vec![false; len]),
+            DataType::DateTime => DBCol::DateTime(vec![Utc.timestamp(0, 0); len]),
+            DataType::Duration => DBCol::Duration(vec![Duration::zero(); len]),
+            DataType::Months => DBCol::Months(vec![0; len]),
+        }
+    }
+
     // `num` is the size we want the resulting length to be.
     pub fn repeat(&mut self, num: usize) {
         match self {
@@ -284,6 +354,10 @@ impl DBCol {
                 assert_eq!(vals.len(), 1);
                 vals.append(&mut vec![vals[0]; num - 1]);
             }
+            DBCol::Months(vals) => {
+                assert_eq!(vals.len(), 1);
+                vals.append(&mut vec![vals[0]; num - 1]);
+            }
         }
     }
 
@@ -313,6 +387,99 @@ impl DBCol {
             DBCol::Duration(vals) => {
                 vals.clear();
             }
+            DBCol::Months(vals) => {
+                vals.clear();
+            }
+        }
+    }
+
+    // Returns a new col holding the values at the given positions, in order.
+    pub fn gather(&self, idxs: &[usize]) -> DBCol {
+        match self {
+            DBCol::Int(vals) => DBCol::Int(idxs.iter().map(|i| vals[*i]).collect()),
+            DBCol::Long(vals) => DBCol::Long(idxs.iter().map(|i| vals[*i]).collect()),
+            DBCol::Float(vals) => DBCol::Float(idxs.iter().map(|i| vals[*i]).collect()),
+            DBCol::Double(vals) => DBCol::Double(idxs.iter().map(|i| vals[*i]).collect()),
+            DBCol::Str(vals) => DBCol::Str(idxs.iter().map(|i| vals[*i].clone()).collect()),
+            DBCol::Bool(vals) => DBCol::Bool(idxs.iter().map(|i| vals[*i]).collect()),
+            DBCol::DateTime(vals) => DBCol::DateTime(idxs.iter().map(|i| vals[*i]).collect()),
+            DBCol::Duration(vals) => DBCol::Duration(idxs.iter().map(|i| vals[*i]).collect()),
+            DBCol::Months(vals) => DBCol::Months(idxs.iter().map(|i| vals[*i]).collect()),
+        }
+    }
+
+    // Returns the position of the first occurrence of each value, in order. All NULLs count as one
+    // value.
+    pub fn distinct_positions(&self, nulls: Option<&RoaringBitmap>) -> Vec<usize> {
+        fn first_positions<T: Hash + Eq>(
+            vals: impl Iterator<Item = T>,
+            nulls: Option<&RoaringBitmap>,
+        ) -> Vec<usize> {
+            let mut seen = HashSet::new();
+            let mut seen_null = false;
+            let mut positions = vec![];
+            for (i, val) in vals.enumerate() {
+                let is_first = if nulls.is_some_and(|nulls| nulls.contains(i as u32)) {
+                    !std::mem::replace(&mut seen_null, true)
+                } else {
+                    seen.insert(val)
+                };
+                if is_first {
+                    positions.push(i);
+                }
+            }
+            positions
+        }
+
+        match self {
+            DBCol::Int(vals) => first_positions(vals.iter(), nulls),
+            DBCol::Long(vals) => first_positions(vals.iter(), nulls),
+            DBCol::Float(vals) => first_positions(vals.iter().map(|v| join::float_bits(*v)), nulls),
+            DBCol::Double(vals) => {
+                first_positions(vals.iter().map(|v| join::double_bits(*v)), nulls)
+            }
+            DBCol::Str(vals) => first_positions(vals.iter(), nulls),
+            DBCol::Bool(vals) => first_positions(vals.iter(), nulls),
+            DBCol::DateTime(vals) => first_positions(vals.iter(), nulls),
+            DBCol::Duration(vals) => first_positions(vals.iter(), nulls),
+            DBCol::Months(vals) => first_positions(vals.iter(), nulls),
+        }
+    }
+
+    // Appends the values of `other`, which has to be the same type of col.
+    pub fn append(&mut self, other: DBCol) {
+        match (self, other) {
+            (DBCol::Int(vals), DBCol::Int(mut other)) => vals.append(&mut other),
+            (DBCol::Long(vals), DBCol::Long(mut other)) => vals.append(&mut other),
+            (DBCol::Float(vals), DBCol::Float(mut other)) => vals.append(&mut other),
+            (DBCol::Double(vals), DBCol::Double(mut other)) => vals.append(&mut other),
+            (DBCol::Str(vals), DBCol::Str(mut other)) => vals.append(&mut other),
+            (DBCol::Bool(vals), DBCol::Bool(mut other)) => vals.append(&mut other),
+            (DBCol::DateTime(vals), DBCol::DateTime(mut other)) => vals.append(&mut other),
+            (DBCol::Duration(vals), DBCol::Duration(mut other)) => vals.append(&mut other),
+            (DBCol::Months(vals), DBCol::Months(mut other)) => vals.append(&mut other),
+            (col, other) => {
+                panic!(
+                    "Cannot append {:?} values to a {:?} col",
+                    other.data_type(),
+                    col.data_type()
+                );
+            }
+        }
+    }
+
+    // Overwrites the given positions of a number col with 1. NULL slots hold arbitrary values
+    // (usually 0), so divisors are patched this way before dividing.
+    fn set_ones(&mut self, positions: &RoaringBitmap) {
+        for pos in positions {
+            let pos = pos as usize;
+            match self {
+                DBCol::Int(vals) => vals[pos] = 1,
+                DBCol::Long(vals) => vals[pos] = 1,
+                DBCol::Float(vals) => vals[pos] = 1.,
+                DBCol::Double(vals) => vals[pos] = 1.,
+                _ => {}
+            }
         }
     }
 
@@ -330,6 +497,7 @@ impl DBCol {
                 DBCol::Bool(vals) => Some(Box::new(vals[0])),
                 DBCol::DateTime(vals) => Some(Box::new(vals[0])),
                 DBCol::Duration(vals) => Some(Box::new(vals[0])),
+                DBCol::Months(vals) => Some(Box::new(vals[0])),
             }
         }
     }
@@ -370,6 +538,10 @@ impl DBCol {
                 .iter()
                 .map(|x| Box::new(*x) as Box<dyn fmt::Debug>)
                 .collect(),
+            DBCol::Months(vals) => vals[..num]
+                .iter()
+                .map(|x| Box::new(*x) as Box<dyn fmt::Debug>)
+                .collect(),
         }
     }
 }
@@ -379,9 +551,12 @@ impl DBCol {
 // grouping in place (e.g., no group by), then everything is stored in the default key (an empty
 // Vec).
 // There should always be at least one key in DBResult (e.g., the default key).
-#[derive(Debug)]
+// `nulls` marks which positions of each group's col are NULL. The values stored at those positions
+// are meaningless. Groups without an entry in `nulls` have no NULLs.
+#[derive(Debug, Clone)]
 pub struct DBResult {
     pub cols: HashMap<Vec<DBCol>, DBCol>,
+    pub nulls: HashMap<Vec<DBCol>, RoaringBitmap>,
     // The table that this result was evaluated against
     pub ref_table: Option<Rc<dyn Table>>,
 }
@@ -390,68 +565,78 @@ impl PartialEq for DBResult {
     // TODO Check the ref_table as well
     fn eq(&self, other: &Self) -> bool {
         self.cols == other.cols
+            && self
+                .cols
+                .keys()
+                .all(|group| self.null_mask(group) == other.null_mask(group))
     }
 }
 
 impl DBResult {
+    // Returns the NULL positions for the group, or None if it has no NULLs.
+    pub fn null_mask(&self, group: &[DBCol]) -> Option<&RoaringBitmap> {
+        self.nulls.get(group).filter(|mask| !mask.is_empty())
+    }
+
+    pub fn is_null(&self, group: &[DBCol], pos: usize) -> bool {
+        self.null_mask(group)
+            .is_some_and(|mask| mask.contains(pos as u32))
+    }
+
     pub fn is_ungrouped(&self) -> bool {
         self.cols.len() == 1 && self.cols.iter().next().unwrap().0.is_empty()
     }
 
-    pub fn group(&mut self, groups: &Vec<Vec<DBCol>>) {
-        assert_eq!(1, self.cols.len());
-        let main_col_entry = self.cols.iter().next().unwrap();
-        assert!(main_col_entry.0.is_empty());
+    // Splits an ungrouped result into `groups`, keeping the order of the rows within each group.
+    pub fn group(&mut self, groups: &Groups) {
+        assert!(self.is_ungrouped());
+        let col = &self.cols[&vec![]];
         assert_eq!(
-            main_col_entry.1.len(),
-            groups.len(),
+            col.len(),
+            groups.num_rows(),
             "\n{:?}\n**\n{:?}",
-            main_col_entry.1,
+            col,
             groups
         );
 
-        macro_rules! entry_default_push {
-            ($entry:expr, $val:expr, $type:path) => {
-                let mut entry = $entry.or_insert($type(vec![]));
-                if let $type(ref mut entry_vals) = &mut entry {
-                    entry_vals.push($val);
-                } else {
-                    panic!("blah");
-                }
-            };
-        }
-
+        let null_mask = self.null_mask(&[]);
+        let mut grouped_nulls = HashMap::new();
         let mut grouped_cols = HashMap::new();
-        for i in 0..main_col_entry.1.len() {
-            let entry = grouped_cols.entry(groups[i].clone());
-            match main_col_entry.1 {
-                DBCol::Int(vals) => {
-                    entry_default_push!(entry, vals[i], DBCol::Int);
-                }
-                DBCol::Long(vals) => {
-                    entry_default_push!(entry, vals[i], DBCol::Long);
-                }
-                DBCol::Float(vals) => {
-                    entry_default_push!(entry, vals[i], DBCol::Float);
-                }
-                DBCol::Double(vals) => {
-                    entry_default_push!(entry, vals[i], DBCol::Double);
-                }
-                DBCol::Str(vals) => {
-                    entry_default_push!(entry, vals[i].clone(), DBCol::Str);
-                }
-                DBCol::Bool(vals) => {
-                    entry_default_push!(entry, vals[i], DBCol::Bool);
-                }
-                DBCol::DateTime(vals) => {
-                    entry_default_push!(entry, vals[i], DBCol::DateTime);
-                }
-                DBCol::Duration(vals) => {
-                    entry_default_push!(entry, vals[i], DBCol::Duration);
+        for (key, rows) in groups.keys.iter().zip(&groups.rows) {
+            if let Some(null_mask) = null_mask {
+                let group_nulls: RoaringBitmap = rows
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, row)| null_mask.contains(**row as u32))
+                    .map(|(i, _)| i as u32)
+                    .collect();
+                if !group_nulls.is_empty() {
+                    grouped_nulls.insert(key.clone(), group_nulls);
                 }
             }
+            grouped_cols.insert(key.clone(), col.gather(rows));
         }
         self.cols = grouped_cols;
+        self.nulls = grouped_nulls;
+    }
+
+    // Returns the groups sorted by their keys, with NULL keys last, so that they are always visited in
+    // the same order.
+    pub fn groups(&self) -> Vec<&Vec<DBCol>> {
+        let mut groups: Vec<&Vec<DBCol>> = self.cols.keys().collect();
+        groups.sort_by(|a, b| {
+            a.iter()
+                .zip(b.iter())
+                .map(|(a, b)| match (a.len(), b.len()) {
+                    (0, 0) => cmp::Ordering::Equal,
+                    (0, _) => cmp::Ordering::Greater,
+                    (_, 0) => cmp::Ordering::Less,
+                    _ => join::order_at((a, 0), (b, 0)),
+                })
+                .find(|ord| *ord != cmp::Ordering::Equal)
+                .unwrap_or(cmp::Ordering::Equal)
+        });
+        groups
     }
 
     pub fn len(&self) -> usize {
@@ -463,6 +648,246 @@ impl DBResult {
 //    }
 //}
 
+// Repeats singleton cols so that both sides of an element-wise op have the same length.
+fn broadcast_cols(col: &mut DBCol, other_col: &mut DBCol) {
+    if col.len() < other_col.len() && col.len() == 1 {
+        col.repeat(other_col.len());
+    } else if col.len() > other_col.len() && other_col.len() == 1 {
+        other_col.repeat(col.len());
+    } else if col.len() == 0 || other_col.len() == 0 {
+        // Handled in the do_*_op macros.
+    } else if col.len() != other_col.len() {
+        panic!(
+            "col and other_col are not same length\ncol: {:?}\n**\nother_col: {:?}",
+            col, other_col
+        );
+    }
+}
+
+// Three-valued AND, where None stands for NULL.
+pub fn and3(x: Option<bool>, y: Option<bool>) -> Option<bool> {
+    match (x, y) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+// Three-valued OR, where None stands for NULL.
+pub fn or3(x: Option<bool>, y: Option<bool>) -> Option<bool> {
+    match (x, y) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+// Returns the NULL mask of a col of length `len` once it is broadcast to `out_len`.
+pub fn broadcast_null_mask(
+    mask: Option<&RoaringBitmap>,
+    len: usize,
+    out_len: usize,
+) -> Option<RoaringBitmap> {
+    match mask {
+        Some(mask) if len == 1 && out_len > 1 && mask.contains(0) => {
+            Some((0..out_len as u32).collect())
+        }
+        Some(mask) if !mask.is_empty() => Some(mask.clone()),
+        _ => None,
+    }
+}
+
+// An element-wise op is NULL wherever any of its inputs is NULL.
+fn union_null_masks(masks: Vec<Option<RoaringBitmap>>) -> Option<RoaringBitmap> {
+    masks
+        .into_iter()
+        .flatten()
+        .fold(None, |acc, mask| match acc {
+            Some(acc) => Some(acc | mask),
+            None => Some(mask),
+        })
+}
+
+// Checks each value against the list of items. Short lists are scanned linearly, while long lists
+// are put into a hash set first.
+fn in_list_lookup<T, I>(vals: I, items: Vec<T>) -> Vec<bool>
+where
+    T: Eq + Hash,
+    I: Iterator<Item = T>,
+{
+    if items.len() < IN_LIST_HASH_THRESHOLD {
+        vals.map(|val| items.contains(&val)).collect()
+    } else {
+        let items: HashSet<T> = items.into_iter().collect();
+        vals.map(|val| items.contains(&val)).collect()
+    }
+}
+
+// Matches `col` against a list of constant (singleton) items, coercing the items to the type of
+// `col` the same way `do_bool_op` does. A string which is no date or duration matches nothing.
+fn in_list_consts(col: &DBCol, items: &[DBCol]) -> Vec<bool> {
+    macro_rules! collect_items {
+        ($pat:pat => $val:expr) => {
+            items
+                .iter()
+                .map(|item| match item {
+                    $pat => Some($val),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+        };
+    }
+
+    let matches = match col {
+        DBCol::Str(vals) => collect_items!(DBCol::Str(ivals) => ivals[0].as_str())
+            .map(|items| in_list_lookup(vals.iter().map(|v| v.as_str()), items)),
+        DBCol::Bool(vals) => collect_items!(DBCol::Bool(ivals) => ivals[0])
+            .map(|items| in_list_lookup(vals.iter().cloned(), items)),
+        DBCol::Months(vals) => collect_items!(DBCol::Months(ivals) => ivals[0])
+            .map(|items| in_list_lookup(vals.iter().cloned(), items)),
+        DBCol::DateTime(vals) => items
+            .iter()
+            .map(|item| match item {
+                DBCol::DateTime(ivals) => Some(Some(ivals[0])),
+                DBCol::Str(ivals) => Some(utils::try_parse_datetime(&ivals[0])),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|items| {
+                in_list_lookup(vals.iter().cloned(), items.into_iter().flatten().collect())
+            }),
+        DBCol::Duration(vals) => items
+            .iter()
+            .map(|item| match item {
+                DBCol::Duration(ivals) => Some(Some(ivals[0])),
+                DBCol::Str(ivals) => Some(utils::try_parse_duration(&ivals[0])),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|items| {
+                in_list_lookup(vals.iter().cloned(), items.into_iter().flatten().collect())
+            }),
+        _ if col.is_integral_type() && items.iter().all(|item| item.is_integral_type()) => {
+            Some(in_list_lookup(
+                col.iter_as_long(),
+                items
+                    .iter()
+                    .map(|item| item.iter_as_long().next().unwrap())
+                    .collect(),
+            ))
+        }
+        _ if col.is_number_type() && items.iter().all(|item| item.is_number_type()) => {
+            // Compare doubles by their bits; adding 0.0 folds -0.0 into 0.0 first.
+            Some(in_list_lookup(
+                col.double_iter().map(|v| (v + 0.).to_bits()),
+                items
+                    .iter()
+                    .map(|item| (item.double_iter().next().unwrap() + 0.).to_bits())
+                    .collect(),
+            ))
+        }
+        _ => None,
+    };
+
+    matches.unwrap_or_else(|| {
+        panic!(
+            "in list between weird types example ({:?}, {:?})",
+            col.data_type(),
+            items
+                .iter()
+                .map(|item| item.data_type())
+                .collect::<Vec<_>>()
+        )
+    })
+}
+
+// Checks that the IN list `item` can be compared with an expression of type `data_type`, as
+// `in_list_consts` coerces the items. A string literal compared with dates or durations has to be
+// one.
+fn check_in_list_item(data_type: DataType, item: &Expr) -> Result<(), ExprCreateError> {
+    let item_type = match item.data_type() {
+        Some(item_type) => item_type,
+        None => return Ok(()),
+    };
+    if let Expr::Value(DBCol::Str(vals)) = item {
+        let parses = match data_type {
+            DataType::DateTime => utils::try_parse_datetime(&vals[0]).is_some(),
+            DataType::Duration => utils::try_parse_duration(&vals[0]).is_some(),
+            _ => true,
+        };
+        if !parses {
+            return Err(ExprCreateError::InvalidLiteral(item.to_string()));
+        }
+    }
+    let coerced =
+        matches!(data_type, DataType::DateTime | DataType::Duration) && item_type == DataType::Str;
+    if coerced || join::key_type(data_type, item_type).is_some() {
+        Ok(())
+    } else {
+        Err(ExprCreateError::InListTypeError(
+            item.to_string(),
+            vec![data_type, item_type],
+        ))
+    }
+}
+
+// Parses the value of an interval literal, e.g., `interval '3' day` or `interval '1-2' year to
+// month`. Years and months become months, which have no fixed length, and the other fields a
+// duration. Gives None if the value does not fit the fields.
+fn parse_interval(
+    value: &str,
+    leading_field: &ast::DateTimeField,
+    last_field: Option<&ast::DateTimeField>,
+) -> Option<DBCol> {
+    use ast::DateTimeField::*;
+    let value = value.trim();
+    match (leading_field, last_field) {
+        (Year, None) => Some(DBCol::Months(vec![value.parse::<i32>().ok()? * 12])),
+        (Month, None) => Some(DBCol::Months(vec![value.parse().ok()?])),
+        (Year, Some(Month)) => {
+            let (sign, value) = match value.strip_prefix('-') {
+                Some(value) => (-1, value),
+                None => (1, value),
+            };
+            let (years, months) = value.split_once('-')?;
+            let months = years.parse::<i32>().ok()? * 12 + months.parse::<i32>().ok()?;
+            Some(DBCol::Months(vec![sign * months]))
+        }
+        (Year, _) | (Month, _) => None,
+        // The day, if it is one of the fields, is separated by a space, and the others by colons,
+        // e.g., `'1 02:30' day to minute`.
+        (leading_field, last_field) => {
+            let fields = [Day, Hour, Minute, Second];
+            let pos = |field| fields.iter().position(|f| f == field);
+            let (first, last) = (
+                pos(leading_field)?,
+                pos(last_field.unwrap_or(leading_field))?,
+            );
+            let mut nums = vec![];
+            let mut tokens = value.split_whitespace();
+            if first == 0 {
+                nums.push(tokens.next()?.parse::<i64>().ok()?);
+            }
+            if let Some(token) = tokens.next() {
+                for num in token.split(':') {
+                    nums.push(num.parse::<i64>().ok()?);
+                }
+            }
+            if tokens.next().is_some() || nums.is_empty() || first + nums.len() > last + 1 {
+                return None;
+            }
+            // The number of seconds in each field
+            let units = [24 * 60 * 60, 60 * 60, 60, 1];
+            let seconds = nums
+                .iter()
+                .zip(&units[first..])
+                .map(|(num, unit)| num * unit)
+                .sum();
+            Some(DBCol::Duration(vec![Duration::seconds(seconds)]))
+        }
+    }
+}
+
 // Here, `ref_table` refers to the table with which the expression is evaluated iin  respect to. For
 // The reason `ref_tables` might have multiple i.e.,
 // example, the table may be different between expressions in the predicate and the join condition.
@@ -494,12 +919,35 @@ pub enum Expr {
     Function {
         name: String,
         args: Vec<Box<Expr>>,
+        // `count(DISTINCT x)`, where duplicates are dropped from each group before aggregating
+        distinct: bool,
     },
+    // `CASE WHEN conditions[0] THEN results[0] ... ELSE else_ END`
     Case {
-        cond: Box<Expr>,
-        then: Box<Expr>,
+        conditions: Vec<Expr>,
+        results: Vec<Expr>,
         else_: Option<Box<Expr>>,
     },
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+        // Whether the list has a NULL item, which is left out of `list`. Then rows which match no
+        // item are NULL rather than FALSE.
+        has_null: bool,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    // `x IN (subquery)` or `EXISTS (subquery)`
+    SemiJoin(Rc<SemiJoin>),
+    Cast {
+        expr: Box<Expr>,
+        data_type: DataType,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -575,6 +1023,42 @@ pub enum ExprCreateError {
     ColDoesNotExist,
     ExprHasAndOr,
     UnimplementedFunc(String),
+    // A subquery which cannot be evaluated as a semi-join, e.g., one which aggregates or refers to
+    // the outer query other than by equalities
+    UnsupportedSubquery(String),
+    // The FROM clause of a subquery could not be parsed
+    SubqueryError(Box<ParseError>),
+    // A constant which cannot be converted to the type it is cast to, or a cast between types
+    // which cannot be converted at all
+    CastError(CastError),
+    // A typed literal whose value cannot be parsed, e.g., `date '2020-13-01'`
+    InvalidLiteral(String),
+    // DISTINCT in a call which is not an aggregate of one arg
+    UnsupportedDistinct(String),
+    // A cast to a type which has no counterpart among the col types, e.g., TIME
+    UnsupportedCast(ast::DataType),
+    // An IN list item which cannot be compared with the expression, with the types of both
+    InListTypeError(String, Vec<DataType>),
+    // A CASE whose results are of types which have no common type, with the types of the results
+    CaseTypeError(String, Vec<DataType>),
+}
+
+impl ExprCreateError {
+    // Whether the expression cannot be evaluated at all, rather than only not under the context it
+    // was parsed with. Such errors are reported instead of leaving the expression out.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(
+            self,
+            ExprCreateError::UnsupportedSubquery(_)
+                | ExprCreateError::SubqueryError(_)
+                | ExprCreateError::CastError(_)
+                | ExprCreateError::InvalidLiteral(_)
+                | ExprCreateError::UnsupportedDistinct(_)
+                | ExprCreateError::UnsupportedCast(_)
+                | ExprCreateError::InListTypeError(..)
+                | ExprCreateError::CaseTypeError(..)
+        )
+    }
 }
 
 impl Expr {
@@ -629,31 +1113,23 @@ impl Expr {
                         .and_then(|col| Some((col, table)))
                 }
 
-                let result = context
-                    .aliases
-                    .get(&idents[0])
-                    .and_then(|table| try_find_col(table, &idents[1], &idents[0]))
-                    .or_else(|| {
-                        if context.ref_table.contains_subtable(&idents[0]) {
-                            try_find_col(&context.ref_table, &idents[1], &idents[0])
-                        } else {
-                            for table in &context.other_tables {
-                                let col = table.find_col(&idents[1], Some(&idents[0]));
-                                if let Some(col) = col {
-                                    return Some((col, table));
-                                }
-                            }
-                            None
-                        }
-                    })
-                    .and_then(|(col, table)| {
-                        Some(Expr::ColRef {
-                            col,
-                            ref_table: table.clone(),
-                        })
+                // Aliased tables are copies named by their alias, so aliases are found as
+                // subtables like any other table.
+                let found = if context.ref_table.contains_subtable(&idents[0]) {
+                    try_find_col(&context.ref_table, &idents[1], &idents[0])
+                } else {
+                    context
+                        .other_tables
+                        .iter()
+                        .filter(|table| table.contains_subtable(&idents[0]))
+                        .find_map(|table| try_find_col(table, &idents[1], &idents[0]))
+                };
+                found
+                    .map(|(col, table)| Expr::ColRef {
+                        col,
+                        ref_table: table.clone(),
                     })
-                    .ok_or(ExprCreateError::ColDoesNotExist);
-                result
+                    .ok_or(ExprCreateError::ColDoesNotExist)
             }
             ast::Expr::IsNull(subexpr) => Ok(Expr::IsNull(Box::new(Expr::new(subexpr, context)?))),
             ast::Expr::IsNotNull(subexpr) => {
@@ -668,6 +1144,19 @@ impl Expr {
                     Ok(Expr::Value(DBCol::Str(vec![string.to_string()])))
                 }
                 ast::Value::Boolean(boolean) => Ok(Expr::Value(DBCol::Bool(vec![*boolean]))),
+                ast::Value::Date(string) | ast::Value::Timestamp(string) => {
+                    utils::try_parse_datetime(string)
+                        .map(|datetime| Expr::Value(DBCol::DateTime(vec![datetime])))
+                        .ok_or_else(|| ExprCreateError::InvalidLiteral(val.to_string()))
+                }
+                ast::Value::Interval {
+                    value,
+                    leading_field,
+                    last_field,
+                    ..
+                } => parse_interval(value, leading_field, last_field.as_ref())
+                    .map(Expr::Value)
+                    .ok_or_else(|| ExprCreateError::InvalidLiteral(val.to_string())),
                 _ => {
                     panic!("Unknown type for val {:?}", val);
                 }
@@ -678,8 +1167,16 @@ impl Expr {
             ),
             ast::Expr::UnaryOp { .. } => make_unary_ops!(ast_expr, context, Plus, Minus, Not),
             ast::Expr::Nested(expr) => Expr::new(expr, context),
-            ast::Expr::Function(ast::Function { name, args, .. }) => {
+            ast::Expr::Function(ast::Function {
+                name,
+                args,
+                distinct,
+                ..
+            }) => {
                 let name = name.to_string();
+                if *distinct && (!funcs::AGGREGATES.contains(&&name[..]) || args.len() != 1) {
+                    return Err(ExprCreateError::UnsupportedDistinct(ast_expr.to_string()));
+                }
                 //if name == "timezone" || name == "date_trunc" {
                 //    return Err(ExprCreateError::UnimplementedFunc(name.to_string()));
                 //}
@@ -702,37 +1199,167 @@ impl Expr {
                     Ok(Expr::Function {
                         name,
                         args: vec![parsed_args.swap_remove(0)],
+                        distinct: false,
                     })
                 } else {
                     Ok(Expr::Function {
                         name,
                         args: parsed_args,
+                        distinct: *distinct,
                     })
                 }
             }
             ast::Expr::Case {
+                operand,
                 conditions,
                 results,
                 else_result,
-                ..
             } => {
-                assert!(conditions.len() == 1, "more than 1 case condition");
-                assert!(results.len() == 1, "more than 1 case result");
-                let cond = Expr::new(&conditions[0], context)?;
-                let then = Expr::new(&results[0], context)?;
+                // `CASE x WHEN v THEN ...` is evaluated as `CASE WHEN x = v THEN ...`.
+                let conditions = conditions
+                    .iter()
+                    .map(|cond| match operand {
+                        Some(operand) => Expr::new(
+                            &ast::Expr::BinaryOp {
+                                left: operand.clone(),
+                                op: ast::BinaryOperator::Eq,
+                                right: Box::new(cond.clone()),
+                            },
+                            context,
+                        ),
+                        None => Expr::new(cond, context),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let results = results
+                    .iter()
+                    .map(|result| Expr::new(result, context))
+                    .collect::<Result<Vec<_>, _>>()?;
                 let else_ = else_result
                     .as_ref()
-                    .and_then(|e| Some(Expr::new(e, context)))
+                    .map(|e| Expr::new(e, context))
                     .transpose()?;
+                // The results are widened to a common type, as join keys are.
+                let data_types = results
+                    .iter()
+                    .chain(else_.as_ref())
+                    .filter_map(|result| result.data_type())
+                    .collect::<Vec<_>>();
+                if let Some((first, rest)) = data_types.split_first() {
+                    let common_type = rest.iter().try_fold(*first, |common_type, data_type| {
+                        join::key_type(common_type, *data_type)
+                    });
+                    if common_type.is_none() {
+                        return Err(ExprCreateError::CaseTypeError(
+                            ast_expr.to_string(),
+                            data_types,
+                        ));
+                    }
+                }
                 Ok(Expr::Case {
-                    cond: Box::new(cond),
-                    then: Box::new(then),
-                    else_: else_.and_then(|e| Some(Box::new(e))),
+                    conditions,
+                    results,
+                    else_: else_.map(Box::new),
                 })
             }
-            // TODO Implement in list/between in future
-            ast::Expr::InList { .. } | ast::Expr::Between { .. } => {
-                Ok(Expr::Value(DBCol::Bool(vec![true])))
+            ast::Expr::Cast { expr, data_type } => {
+                let expr = Expr::new(expr, context)?;
+                let cast = |expr: Expr, to: DataType| match expr {
+                    // Constants are converted right away.
+                    Expr::Value(val) => Ok(Expr::Value(
+                        cast::cast(&val, None, to).map_err(ExprCreateError::CastError)?,
+                    )),
+                    expr => {
+                        if let Some(from) = expr.data_type() {
+                            cast::check_cast(from, to).map_err(ExprCreateError::CastError)?;
+                        }
+                        Ok(Expr::Cast {
+                            expr: Box::new(expr),
+                            data_type: to,
+                        })
+                    }
+                };
+                match data_type {
+                    ast::DataType::SmallInt | ast::DataType::Int => cast(expr, DataType::Int),
+                    ast::DataType::BigInt => cast(expr, DataType::Long),
+                    ast::DataType::Real | ast::DataType::Float(None) => cast(expr, DataType::Float),
+                    ast::DataType::Float(Some(precision)) if *precision <= 24 => {
+                        cast(expr, DataType::Float)
+                    }
+                    ast::DataType::Float(_)
+                    | ast::DataType::Double
+                    | ast::DataType::Decimal(..) => cast(expr, DataType::Double),
+                    ast::DataType::Char(_) | ast::DataType::Varchar(_) | ast::DataType::Text => {
+                        cast(expr, DataType::Str)
+                    }
+                    ast::DataType::Boolean => cast(expr, DataType::Bool),
+                    ast::DataType::Timestamp => cast(expr, DataType::DateTime),
+                    // A date is a timestamp at midnight.
+                    ast::DataType::Date => Ok(Expr::Function {
+                        name: "date_trunc".to_string(),
+                        args: vec![
+                            Box::new(Expr::Value(DBCol::Str(vec!["day".to_string()]))),
+                            Box::new(cast(expr, DataType::DateTime)?),
+                        ],
+                        distinct: false,
+                    }),
+                    ast::DataType::Interval => cast(expr, DataType::Duration),
+                    data_type => Err(ExprCreateError::UnsupportedCast(data_type.clone())),
+                }
+            }
+            ast::Expr::InList {
+                expr,
+                list: ast_list,
+                negated,
+            } => {
+                let expr = Expr::new(expr, context)?;
+                let is_null =
+                    |item: &&ast::Expr| matches!(item, ast::Expr::Value(ast::Value::Null));
+                let list = ast_list
+                    .iter()
+                    .filter(|item| !is_null(item))
+                    .map(|item| Expr::new(item, context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if let Some(data_type) = expr.data_type() {
+                    for item in &list {
+                        check_in_list_item(data_type, item)?;
+                    }
+                }
+                Ok(Expr::InList {
+                    expr: Box::new(expr),
+                    list,
+                    negated: *negated,
+                    has_null: ast_list.iter().any(|item| is_null(&item)),
+                })
+            }
+            ast::Expr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => Ok(Expr::Between {
+                expr: Box::new(Expr::new(expr, context)?),
+                low: Box::new(Expr::new(low, context)?),
+                high: Box::new(Expr::new(high, context)?),
+                negated: *negated,
+            }),
+            ast::Expr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => {
+                let expr = Expr::new(expr, context)?;
+                let semi_join = Parser::new(context.db).parse_semi_join(
+                    Some(expr),
+                    subquery,
+                    *negated,
+                    context,
+                )?;
+                Ok(Expr::SemiJoin(Rc::new(semi_join)))
+            }
+            ast::Expr::Exists(subquery) => {
+                let semi_join =
+                    Parser::new(context.db).parse_semi_join(None, subquery, false, context)?;
+                Ok(Expr::SemiJoin(Rc::new(semi_join)))
             }
             _ => {
                 panic!("Expression not supported {:?}", ast_expr);
@@ -751,22 +1378,21 @@ impl Expr {
         let mut left_result = left.eval(run_context, exec_stats);
         let mut right_result = right.eval(run_context, exec_stats);
 
+        let mut nulls = HashMap::new();
         for (group, col) in left_result.cols.iter_mut() {
             let other_col = right_result
                 .cols
                 .get_mut(group)
                 .expect("other col doesn't have some group that col has");
-            if col.len() < other_col.len() && col.len() == 1 {
-                col.repeat(other_col.len());
-            } else if col.len() > other_col.len() && other_col.len() == 1 {
-                other_col.repeat(col.len());
-            } else if col.len() == 0 || other_col.len() == 0 {
-                // Handled in the do_*_op macros.
-            } else if col.len() != other_col.len() {
-                panic!(
-                    "col and other_col are not same length\ncol: {:?}\n**\nother_col: {:?}",
-                    col, other_col
-                );
+            let len = cmp::max(col.len(), other_col.len());
+            let null_mask = union_null_masks(vec![
+                broadcast_null_mask(left_result.nulls.get(group), col.len(), len),
+                broadcast_null_mask(right_result.nulls.get(group), other_col.len(), len),
+            ]);
+            broadcast_cols(col, other_col);
+            if let (Some(mask), BinaryOperator::Divide | BinaryOperator::Modulus) = (&null_mask, op)
+            {
+                other_col.set_ones(mask);
             }
 
             *col = match op {
@@ -797,8 +1423,12 @@ impl Expr {
                         panic!("one of the types is not a string for not like");
                     }
                 }
+            };
+            if let Some(mask) = null_mask {
+                nulls.insert(group.clone(), mask);
             }
         }
+        left_result.nulls = nulls;
         left_result
     }
 
@@ -837,83 +1467,448 @@ impl Expr {
         result
     }
 
-    // TODO Implement cases - right now it just returns the then result.
-    // TODO Redo this logic so you evaluate the case col first, then build bitmaps out of yes/no,
-    // and then fetch then/else based on those values
-    #[allow(unreachable_code, unused_variables)]
+    // Constant list items (the common case) are evaluated once and matched against the column
+    // through `in_list_lookup`. Items which reference columns are compared row by row. As in SQL,
+    // rows which match no item are NULL rather than FALSE if some item is NULL for them.
+    fn eval_in_list(
+        &self,
+        expr: &Expr,
+        list: &[Expr],
+        negated: bool,
+        has_null: bool,
+        run_context: &RunContext,
+        exec_stats: &mut ExecStats,
+    ) -> DBResult {
+        let mut result = expr.eval(run_context, exec_stats);
+
+        let (consts, non_consts): (Vec<&Expr>, Vec<&Expr>) =
+            list.iter().partition(|item| item.get_all_cols().is_empty());
+        let const_context = RunContext {
+            index: None,
+            groups: Default::default(),
+            ref_table: None,
+            exec_params: run_context.exec_params.clone(),
+        };
+        let mut has_null = has_null;
+        let consts: Vec<DBCol> = consts
+            .iter()
+            .filter_map(|item| {
+                let item_result = item.eval(&const_context, exec_stats);
+                assert!(item_result.is_ungrouped());
+                if item_result.nulls.values().any(|nulls| !nulls.is_empty()) {
+                    has_null = true;
+                    return None;
+                }
+                item_result.cols.into_iter().next().map(|(_, col)| col)
+            })
+            .collect();
+        let non_consts: Vec<DBResult> = non_consts
+            .iter()
+            .map(|item| item.eval(run_context, exec_stats))
+            .collect();
+
+        for (group, col) in result.cols.iter_mut() {
+            let mut matches = if consts.is_empty() {
+                vec![false; col.len()]
+            } else {
+                in_list_consts(col, &consts)
+            };
+
+            // Rows compared with a NULL item
+            let mut unknown = RoaringBitmap::new();
+            for item_result in &non_consts {
+                let mut item_col = item_result
+                    .cols
+                    .get(group)
+                    .expect("in list item doesn't have some group that col has")
+                    .clone();
+                let item_nulls = item_result.nulls.get(group);
+                let item_len = item_col.len();
+                let mut col = col.clone();
+                broadcast_cols(&mut col, &mut item_col);
+                if matches.len() == 1 {
+                    matches = vec![matches[0]; col.len()];
+                }
+                if let DBCol::Bool(eq_vals) = do_bool_op!(&col, &item_col, ==) {
+                    for (idx, (m, eq)) in matches.iter_mut().zip(eq_vals).enumerate() {
+                        let item_idx = if item_len == 1 { 0 } else { idx as u32 };
+                        if matches!(item_nulls, Some(nulls) if nulls.contains(item_idx)) {
+                            unknown.insert(idx as u32);
+                        } else {
+                            *m |= eq;
+                        }
+                    }
+                }
+            }
+            if has_null {
+                unknown.insert_range(0..matches.len() as u32);
+            }
+            for (idx, m) in matches.iter().enumerate() {
+                if *m {
+                    unknown.remove(idx as u32);
+                }
+            }
+
+            if negated {
+                matches.iter_mut().for_each(|m| *m = !*m);
+            }
+            *col = DBCol::Bool(matches);
+            if !unknown.is_empty() {
+                *result.nulls.entry(group.clone()).or_default() |= unknown;
+            }
+        }
+        result
+    }
+
+    // The column is read once and compared against both bounds, rather than being split into two
+    // comparison atoms.
+    fn eval_between(
+        &self,
+        expr: &Expr,
+        low: &Expr,
+        high: &Expr,
+        negated: bool,
+        run_context: &RunContext,
+        exec_stats: &mut ExecStats,
+    ) -> DBResult {
+        let mut result = expr.eval(run_context, exec_stats);
+        let mut low_result = low.eval(run_context, exec_stats);
+        let mut high_result = high.eval(run_context, exec_stats);
+
+        let mut nulls = HashMap::new();
+        for (group, col) in result.cols.iter_mut() {
+            let low_col = low_result
+                .cols
+                .get_mut(group)
+                .expect("low col doesn't have some group that col has");
+            let high_col = high_result
+                .cols
+                .get_mut(group)
+                .expect("high col doesn't have some group that col has");
+            let len = cmp::max(col.len(), cmp::max(low_col.len(), high_col.len()));
+            let col_nulls = broadcast_null_mask(result.nulls.get(group), col.len(), len);
+            let low_nulls = union_null_masks(vec![
+                col_nulls.clone(),
+                broadcast_null_mask(low_result.nulls.get(group), low_col.len(), len),
+            ])
+            .unwrap_or_default();
+            let high_nulls = union_null_masks(vec![
+                col_nulls,
+                broadcast_null_mask(high_result.nulls.get(group), high_col.len(), len),
+            ])
+            .unwrap_or_default();
+            broadcast_cols(col, low_col);
+            broadcast_cols(col, high_col);
+            broadcast_cols(low_col, high_col);
+
+            let above_low = do_bool_op!(&col, &low_col, >=);
+            let below_high = do_bool_op!(&col, &high_col, <=);
+            let mut null_mask = RoaringBitmap::new();
+            *col = match (above_low, below_high) {
+                (DBCol::Bool(above_low), DBCol::Bool(below_high)) => DBCol::Bool(
+                    above_low
+                        .into_iter()
+                        .zip(below_high)
+                        .enumerate()
+                        .map(|(i, (x, y))| {
+                            let i = i as u32;
+                            let x = Some(x).filter(|_| !low_nulls.contains(i));
+                            let y = Some(y).filter(|_| !high_nulls.contains(i));
+                            match and3(x, y) {
+                                Some(val) => val != negated,
+                                None => {
+                                    null_mask.insert(i);
+                                    false
+                                }
+                            }
+                        })
+                        .collect(),
+                ),
+                _ => unreachable!(),
+            };
+            if !null_mask.is_empty() {
+                nulls.insert(group.clone(), null_mask);
+            }
+        }
+        result.nulls = nulls;
+        result
+    }
+
+    // Each WHEN is evaluated only on the rows which no earlier WHEN took, and each THEN (or the
+    // ELSE) only on the rows its WHEN took, so that a result is never computed for rows which do not
+    // use it. The branches are evaluated ungrouped and their values put back in row order before
+    // grouping. Rows which no WHEN takes are NULL without an ELSE.
     fn eval_case(
         &self,
-        cond: &Box<Expr>,
-        then: &Box<Expr>,
+        conditions: &[Expr],
+        results: &[Expr],
         else_: &Option<Box<Expr>>,
         run_context: &RunContext,
         exec_stats: &mut ExecStats,
     ) -> DBResult {
-        return then.eval(run_context, exec_stats);
+        if !run_context.groups.is_empty() {
+            return self.eval_case_grouped(conditions, results, else_, run_context, exec_stats);
+        }
+        let branch_context = |index: Option<RoaringBitmap>| RunContext {
+            index,
+            groups: Default::default(),
+            ref_table: run_context.ref_table.clone(),
+            exec_params: run_context.exec_params.clone(),
+        };
 
-        macro_rules! do_case_then_else {
-            ($cond_vals:expr, $then_vals:expr, $else_vals:expr) => {
-                $cond_vals
+        // The rows not yet taken by a branch, where None stands for every row, as with `index`.
+        let mut remaining = run_context.index.clone();
+        let mut len = run_context.index.as_ref().map(|index| index.len() as usize);
+        let mut branches: Vec<(&Expr, Option<RoaringBitmap>)> = vec![];
+        for (cond, result) in conditions.iter().zip(results) {
+            if remaining.as_ref().is_some_and(|rows| rows.is_empty()) {
+                break;
+            }
+            let cond_result = cond.eval(&branch_context(remaining.clone()), exec_stats);
+            let vals = match cond_result.cols.get(&vec![]) {
+                Some(DBCol::Bool(vals)) => vals,
+                _ => {
+                    panic!("CASE condition ({}) is not a boolean", cond);
+                }
+            };
+            let nulls = cond_result.null_mask(&[]);
+            let holds = |i: usize| vals[i] && !nulls.is_some_and(|nulls| nulls.contains(i as u32));
+
+            let taken = if vals.len() == 1 {
+                // A constant condition takes all of the remaining rows or none of them.
+                if holds(0) {
+                    remaining.replace(RoaringBitmap::new())
+                } else {
+                    Some(RoaringBitmap::new())
+                }
+            } else {
+                let rows = remaining.get_or_insert_with(|| {
+                    len = Some(vals.len());
+                    (0..vals.len() as u32).collect()
+                });
+                let taken: RoaringBitmap = rows
                     .iter()
                     .enumerate()
-                    .map(|(i, cond_val)| {
-                        if *cond_val {
-                            $then_vals[i].clone()
-                        } else {
-                            $else_vals[i].clone()
-                        }
-                    })
-                    .collect()
+                    .filter(|(i, _)| holds(*i))
+                    .map(|(_, row)| row)
+                    .collect();
+                *rows -= &taken;
+                Some(taken)
             };
+            branches.push((result, taken));
+        }
+        if let Some(else_) = else_ {
+            branches.push((else_, remaining.replace(RoaringBitmap::new())));
         }
+        branches.retain(|(_, rows)| !rows.as_ref().is_some_and(|rows| rows.is_empty()));
+        let null_rows = remaining.filter(|rows| !rows.is_empty());
 
-        // TODO - FINISH HERE
-        unreachable!();
-        let mut cond_result = cond.eval(run_context, exec_stats);
-        let then_result = then.eval(run_context, exec_stats);
-        assert!(else_.is_some());
-        let else_result = else_.as_ref().unwrap().eval(run_context, exec_stats);
-        for (group, col) in cond_result.cols.iter_mut() {
-            let cond_vals;
-            if let DBCol::Bool(cond) = col {
-                cond_vals = cond
-            } else {
-                panic!("cond is not of bool type");
-            }
-            let then_col = &then_result.cols[group];
-            let else_col = &else_result.cols[group];
-            *col = if then_col.is_number_type() && else_col.is_number_type() {
-                match (then_col, else_col) {
-                    (DBCol::Int(then_vals), DBCol::Int(else_vals)) => {
-                        DBCol::Int(do_case_then_else!(cond_vals, then_vals, else_vals))
-                    }
-                    (DBCol::Long(then_vals), DBCol::Long(else_vals)) => {
-                        DBCol::Long(do_case_then_else!(cond_vals, then_vals, else_vals))
-                    }
-                    (DBCol::Float(then_vals), DBCol::Float(else_vals)) => {
-                        DBCol::Float(do_case_then_else!(cond_vals, then_vals, else_vals))
-                    }
-                    _ => {
-                        if then_col.is_integral_type() && else_col.is_integral_type() {
-                            let then_vals: Vec<i64> = then_col.iter_as_long().collect();
-                            let else_vals: Vec<i64> = else_col.iter_as_long().collect();
-                            DBCol::Long(do_case_then_else!(cond_vals, then_vals, else_vals))
-                        } else {
-                            let then_vals: Vec<f64> = then_col.double_iter().collect();
-                            let else_vals: Vec<f64> = else_col.double_iter().collect();
-                            DBCol::Double(do_case_then_else!(cond_vals, then_vals, else_vals))
+        // A branch which takes every row is evaluated as is.
+        if branches.len() == 1 && null_rows.is_none() {
+            return branches[0].0.eval(run_context, exec_stats);
+        }
+        // If no row takes a branch, every row is NULL. The first result is evaluated on no rows, just
+        // for the type of the result. Without a condition which depends on the row, the CASE is a
+        // constant.
+        if branches.is_empty() {
+            let result = results[0].eval(&branch_context(Some(RoaringBitmap::new())), exec_stats);
+            let len = len.unwrap_or(1);
+            let col = DBCol::placeholders(result.cols[&vec![]].data_type(), len);
+            return DBResult {
+                cols: vec![(vec![], col)].into_iter().collect(),
+                nulls: vec![(vec![], (0..len as u32).collect())]
+                    .into_iter()
+                    .collect(),
+                ref_table: run_context.ref_table.clone(),
+            };
+        }
+
+        // Every branch took some but not all of the rows, so the number of rows is known by now.
+        let len = len.unwrap();
+        let pos = |row: u32| match &run_context.index {
+            Some(index) => index.rank(row) as usize - 1,
+            None => row as usize,
+        };
+        let branch_results = branches
+            .iter()
+            .map(|(result, rows)| {
+                let rows = rows.as_ref().unwrap();
+                (
+                    rows,
+                    result.eval(&branch_context(Some(rows.clone())), exec_stats),
+                )
+            })
+            .collect::<Vec<_>>();
+        let data_types = branch_results
+            .iter()
+            .map(|(_, result)| result.cols[&vec![]].data_type())
+            .collect::<Vec<_>>();
+        let common_type = data_types[1..]
+            .iter()
+            .try_fold(data_types[0], |common_type, data_type| {
+                join::key_type(common_type, *data_type)
+            })
+            .unwrap_or_else(|| {
+                panic!(
+                    "The results of ({}) have incompatible types {:?}",
+                    self, data_types
+                )
+            });
+
+        // The values of all branches are appended in turn, and then gathered into row order.
+        let mut all_vals: Option<DBCol> = None;
+        let mut idxs = vec![0; len];
+        let mut null_mask = null_rows
+            .iter()
+            .flatten()
+            .map(|row| pos(row) as u32)
+            .collect::<RoaringBitmap>();
+        let ref_table = branch_results[0].1.ref_table.clone();
+        for (rows, mut result) in branch_results {
+            let offset = all_vals.as_ref().map_or(0, |vals| vals.len());
+            let mut col = result.cols.remove(&vec![]).unwrap();
+            let nulls = broadcast_null_mask(result.null_mask(&[]), col.len(), rows.len() as usize);
+            if col.data_type() != common_type {
+                col = join::cast_keys(&col, common_type);
+            }
+            if col.len() == 1 && rows.len() > 1 {
+                col.repeat(rows.len() as usize);
+            }
+            for (i, row) in rows.iter().enumerate() {
+                idxs[pos(row)] = offset + i;
+                if nulls.as_ref().is_some_and(|nulls| nulls.contains(i as u32)) {
+                    null_mask.insert(pos(row) as u32);
+                }
+            }
+            match &mut all_vals {
+                Some(all_vals) => all_vals.append(col),
+                None => all_vals = Some(col),
+            }
+        }
+
+        let mut nulls = HashMap::new();
+        if !null_mask.is_empty() {
+            nulls.insert(vec![], null_mask);
+        }
+        DBResult {
+            cols: vec![(vec![], all_vals.unwrap().gather(&idxs))]
+                .into_iter()
+                .collect(),
+            nulls,
+            ref_table,
+        }
+    }
+
+    // Evaluates CASE under GROUP BY, where the conditions and results may aggregate. Every branch is
+    // then evaluated on all the rows of each group, and the value of the branch each row takes is
+    // picked out. Values of length 1, e.g., aggregates, apply to every row of their group.
+    fn eval_case_grouped(
+        &self,
+        conditions: &[Expr],
+        results: &[Expr],
+        else_: &Option<Box<Expr>>,
+        run_context: &RunContext,
+        exec_stats: &mut ExecStats,
+    ) -> DBResult {
+        let cond_results = conditions
+            .iter()
+            .map(|cond| cond.eval(run_context, exec_stats))
+            .collect::<Vec<_>>();
+        // The ELSE result, if any, is the last branch.
+        let branch_results = results
+            .iter()
+            .chain(else_.as_deref())
+            .map(|result| result.eval(run_context, exec_stats))
+            .collect::<Vec<_>>();
+
+        let mut cols = HashMap::new();
+        let mut nulls = HashMap::new();
+        for group in branch_results[0].cols.keys() {
+            let data_types = branch_results
+                .iter()
+                .map(|result| result.cols[group].data_type())
+                .collect::<Vec<_>>();
+            let common_type = data_types[1..]
+                .iter()
+                .try_fold(data_types[0], |common_type, data_type| {
+                    join::key_type(common_type, *data_type)
+                })
+                .unwrap_or_else(|| {
+                    panic!(
+                        "The results of ({}) have incompatible types {:?}",
+                        self, data_types
+                    )
+                });
+            let len = cond_results
+                .iter()
+                .chain(&branch_results)
+                .map(|result| result.cols[group].len())
+                .max()
+                .unwrap();
+            let at = |col_len: usize, i: usize| if col_len == 1 { 0 } else { i };
+
+            // The values of all branches are appended in turn, and then gathered into row order.
+            let mut all_vals: Option<DBCol> = None;
+            let mut offsets = vec![];
+            for result in &branch_results {
+                let mut col = result.cols[group].clone();
+                if col.data_type() != common_type {
+                    col = join::cast_keys(&col, common_type);
+                }
+                offsets.push((all_vals.as_ref().map_or(0, |vals| vals.len()), col.len()));
+                match &mut all_vals {
+                    Some(all_vals) => all_vals.append(col),
+                    None => all_vals = Some(col),
+                }
+            }
+
+            let mut null_mask = RoaringBitmap::new();
+            let mut idxs = vec![0; len];
+            for (i, idx) in idxs.iter_mut().enumerate() {
+                let branch = cond_results
+                    .iter()
+                    .position(|result| {
+                        let vals = match &result.cols[group] {
+                            DBCol::Bool(vals) => vals,
+                            _ => {
+                                panic!("CASE condition of ({}) is not a boolean", self);
+                            }
+                        };
+                        let j = at(vals.len(), i);
+                        vals[j]
+                            && !result
+                                .null_mask(group)
+                                .is_some_and(|nulls| nulls.contains(j as u32))
+                    })
+                    .or_else(|| else_.as_ref().map(|_| conditions.len()));
+                match branch {
+                    Some(branch) => {
+                        let (offset, branch_len) = offsets[branch];
+                        let j = at(branch_len, i);
+                        *idx = offset + j;
+                        if branch_results[branch]
+                            .null_mask(group)
+                            .is_some_and(|nulls| nulls.contains(j as u32))
+                        {
+                            null_mask.insert(i as u32);
                         }
                     }
+                    None => {
+                        null_mask.insert(i as u32);
+                    }
                 }
-            } else if let (DBCol::Str(then_vals), DBCol::Str(else_vals)) = (then_col, else_col) {
-                DBCol::Str(do_case_then_else!(cond_vals, then_vals, else_vals))
-            } else if let (DBCol::Bool(then_vals), DBCol::Bool(else_vals)) = (then_col, else_col) {
-                DBCol::Bool(do_case_then_else!(cond_vals, then_vals, else_vals))
-            } else {
-                panic!("then and else are incompatible");
             }
+
+            cols.insert(group.clone(), all_vals.unwrap().gather(&idxs));
+            if !null_mask.is_empty() {
+                nulls.insert(group.clone(), null_mask);
+            }
+        }
+        DBResult {
+            cols,
+            nulls,
+            ref_table: branch_results[0].ref_table.clone(),
         }
-        cond_result
     }
 
     // XXX We  might want this if we reorder the table join order
@@ -942,21 +1937,22 @@ impl Expr {
                     .iter()
                     .next()
                     .expect(&format!("Table has no cols {}", ref_table.name())[..]);
-                col.read(run_context, exec_stats, ref_table)
-            }
-            // FIXME - For now, let's not worry about nulls
-            Expr::IsNull(subexpr) => {
-                let mut result = subexpr.eval(run_context, exec_stats);
-                for (_, col) in result.cols.iter_mut() {
-                    *col = DBCol::Bool(vec![false; col.len()]);
-                }
+                // A wildcard stands for whole rows, which are never NULL (e.g., for count(*)).
+                let mut result = col.read(run_context, exec_stats, ref_table);
+                result.nulls.clear();
                 result
             }
-            // FIXME - For now, let's not worry about nulls
-            Expr::IsNotNull(subexpr) => {
+            Expr::IsNull(subexpr) | Expr::IsNotNull(subexpr) => {
+                let is_null = matches!(self, Expr::IsNull(_));
                 let mut result = subexpr.eval(run_context, exec_stats);
-                for (_, col) in result.cols.iter_mut() {
-                    *col = DBCol::Bool(vec![true; col.len()]);
+                let nulls = std::mem::take(&mut result.nulls);
+                for (group, col) in result.cols.iter_mut() {
+                    let mask = nulls.get(group);
+                    *col = DBCol::Bool(
+                        (0..col.len() as u32)
+                            .map(|i| mask.is_some_and(|mask| mask.contains(i)) == is_null)
+                            .collect(),
+                    );
                 }
                 result
             }
@@ -969,25 +1965,33 @@ impl Expr {
                 if run_context.groups.is_empty() {
                     DBResult {
                         cols: vec![(vec![], val.clone())].into_iter().collect(),
+                        nulls: HashMap::new(),
                         ref_table: None,
                     }
                 } else {
                     DBResult {
                         cols: run_context
                             .groups
+                            .keys
                             .iter()
                             .map(|g| (g.clone(), val.clone()))
                             .collect(),
+                        nulls: HashMap::new(),
                         ref_table: None,
                     }
                 }
             }
-            Expr::Function { name, args } => {
+            Expr::Function {
+                name,
+                args,
+                distinct,
+            } => {
                 // XXX We make an assumption that aggregate functions only appear in the projection
                 // (i.e., no with clause for now)
                 let arg_results = args
                     .iter()
                     .map(|e| e.eval(run_context, exec_stats))
+                    .map(|arg| if *distinct { funcs::distinct(arg) } else { arg })
                     .collect();
                 //let arg_results = args
                 //    .iter()
@@ -1004,12 +2008,119 @@ impl Expr {
                 }
                 FUNC_MAP[&name[..]](arg_results)
             }
-            Expr::Case { cond, then, else_ } => {
-                self.eval_case(cond, then, else_, run_context, exec_stats)
+            Expr::Case {
+                conditions,
+                results,
+                else_,
+            } => self.eval_case(conditions, results, else_, run_context, exec_stats),
+            Expr::InList {
+                expr,
+                list,
+                negated,
+                has_null,
+            } => self.eval_in_list(expr, list, *negated, *has_null, run_context, exec_stats),
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => self.eval_between(expr, low, high, *negated, run_context, exec_stats),
+            Expr::SemiJoin(semi_join) => semi_join.eval(run_context, exec_stats),
+            Expr::Cast { expr, data_type } => {
+                let mut result = expr.eval(run_context, exec_stats);
+                for (group, col) in result.cols.iter_mut() {
+                    *col = match cast::cast(col, result.nulls.get(group), *data_type) {
+                        Ok(col) => col,
+                        Err(err) => {
+                            // The query fails with the error, so the values are left as nulls
+                            // only to keep evaluating with the right type.
+                            exec_stats.error.get_or_insert(ExecError::CastError(err));
+                            let all_rows = (0..col.len() as u32).collect();
+                            cast::cast(col, Some(&all_rows), *data_type).unwrap()
+                        }
+                    };
+                }
+                result
             }
         }
     }
 
+    // Whether this expression aggregates rows, i.e., calls an aggregate function.
+    pub fn has_aggregate(&self) -> bool {
+        match self {
+            Expr::ColRef { .. } | Expr::Wildcard { .. } | Expr::Value(_) | Expr::SemiJoin(_) => {
+                false
+            }
+            Expr::IsNull(subexpr)
+            | Expr::IsNotNull(subexpr)
+            | Expr::Nested(subexpr)
+            | Expr::UnaryOp { expr: subexpr, .. }
+            | Expr::Cast { expr: subexpr, .. } => subexpr.has_aggregate(),
+            Expr::BinaryOp { left, right, .. } => left.has_aggregate() || right.has_aggregate(),
+            Expr::Function { name, args, .. } => {
+                funcs::AGGREGATES.contains(&&name[..]) || args.iter().any(|arg| arg.has_aggregate())
+            }
+            Expr::Case {
+                conditions,
+                results,
+                else_,
+            } => conditions
+                .iter()
+                .chain(results)
+                .chain(else_.as_deref())
+                .any(|expr| expr.has_aggregate()),
+            Expr::InList { expr, list, .. } => {
+                expr.has_aggregate() || list.iter().any(|item| item.has_aggregate())
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => expr.has_aggregate() || low.has_aggregate() || high.has_aggregate(),
+        }
+    }
+
+    // The type of the values this expression evaluates to, if it can be told without evaluating it.
+    // Arithmetic between numbers widens as `do_number_op!` does.
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Expr::ColRef { col, .. } => Some(col.data_type()),
+            Expr::Value(val) => Some(val.data_type()),
+            Expr::Cast { data_type, .. } => Some(*data_type),
+            Expr::Nested(subexpr) => subexpr.data_type(),
+            Expr::IsNull(_)
+            | Expr::IsNotNull(_)
+            | Expr::InList { .. }
+            | Expr::Between { .. }
+            | Expr::SemiJoin(_)
+            | Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                ..
+            } => Some(DataType::Bool),
+            Expr::UnaryOp { expr, .. } => expr.data_type(),
+            Expr::BinaryOp { left, right, op } => match op {
+                BinaryOperator::Plus
+                | BinaryOperator::Minus
+                | BinaryOperator::Multiply
+                | BinaryOperator::Divide
+                | BinaryOperator::Modulus => {
+                    let (left, right) = (left.data_type()?, right.data_type()?);
+                    let is_number = |data_type| {
+                        matches!(
+                            data_type,
+                            DataType::Int | DataType::Long | DataType::Float | DataType::Double
+                        )
+                    };
+                    if is_number(left) && is_number(right) {
+                        join::key_type(left, right)
+                    } else {
+                        None
+                    }
+                }
+                _ => Some(DataType::Bool),
+            },
+            Expr::Wildcard { .. } | Expr::Function { .. } | Expr::Case { .. } => None,
+        }
+    }
+
     // Get all columns referenced in this expression.
     pub fn get_all_cols(&self) -> Vec<Rc<FileCol>> {
         match self {
@@ -1017,7 +2128,8 @@ impl Expr {
             Expr::IsNull(subexpr)
             | Expr::IsNotNull(subexpr)
             | Expr::Nested(subexpr)
-            | Expr::UnaryOp { expr: subexpr, .. } => subexpr.get_all_cols(),
+            | Expr::UnaryOp { expr: subexpr, .. }
+            | Expr::Cast { expr: subexpr, .. } => subexpr.get_all_cols(),
             Expr::Wildcard { .. } => {
                 panic!("No");
             }
@@ -1035,14 +2147,38 @@ impl Expr {
                 }
                 ret
             }
-            Expr::Case { cond, then, else_ } => {
-                let mut ret = cond.get_all_cols();
-                ret.append(&mut then.get_all_cols());
-                if else_.is_some() {
-                    ret.append(&mut else_.as_ref().unwrap().get_all_cols());
+            Expr::Case {
+                conditions,
+                results,
+                else_,
+            } => {
+                let mut ret = vec![];
+                for expr in conditions.iter().chain(results).chain(else_.as_deref()) {
+                    ret.append(&mut expr.get_all_cols());
+                }
+                ret
+            }
+            Expr::InList { expr, list, .. } => {
+                let mut ret = expr.get_all_cols();
+                for item in list {
+                    ret.append(&mut item.get_all_cols());
                 }
                 ret
             }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                let mut ret = expr.get_all_cols();
+                ret.append(&mut low.get_all_cols());
+                ret.append(&mut high.get_all_cols());
+                ret
+            }
+            // The subquery's own columns are not part of the outer query.
+            Expr::SemiJoin(semi_join) => semi_join
+                .outer_keys()
+                .iter()
+                .flat_map(|key| key.get_all_cols())
+                .collect(),
         }
     }
 }
@@ -1087,17 +2223,53 @@ impl fmt::Display for Expr {
                 DBCol::Str(vals) => write!(f, "'{}'", vals[0]),
                 _ => write!(f, "{:?}", col.any().unwrap()),
             },
-            Expr::Function { name, args } => {
+            Expr::Function {
+                name,
+                args,
+                distinct,
+            } => {
                 let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
-                write!(f, "{}({})", name, args.join(", "))
+                let distinct = if *distinct { "DISTINCT " } else { "" };
+                write!(f, "{}({}{})", name, distinct, args.join(", "))
             }
-            Expr::Case { cond, then, else_ } => {
-                let else_ = else_
-                    .as_ref()
-                    .and_then(|e| Some(format!(" ELSE {}", e)))
-                    .unwrap_or("".to_string());
-                write!(f, "CASE WHEN {} THEN {}{} END", cond, then, else_)
+            Expr::Case {
+                conditions,
+                results,
+                else_,
+            } => {
+                write!(f, "CASE")?;
+                for (cond, result) in conditions.iter().zip(results) {
+                    write!(f, " WHEN {} THEN {}", cond, result)?;
+                }
+                if let Some(else_) = else_ {
+                    write!(f, " ELSE {}", else_)?;
+                }
+                write!(f, " END")
+            }
+            Expr::InList {
+                expr,
+                list,
+                negated,
+                has_null,
+            } => {
+                let mut list: Vec<String> = list.iter().map(|item| item.to_string()).collect();
+                if *has_null {
+                    list.push("NULL".to_string());
+                }
+                let op = if *negated { "NOT IN" } else { "IN" };
+                write!(f, "{} {} ({})", expr, op, list.join(", "))
+            }
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let op = if *negated { "NOT BETWEEN" } else { "BETWEEN" };
+                write!(f, "{} {} {} AND {}", expr, op, low, high)
             }
+            Expr::SemiJoin(semi_join) => write!(f, "{}", semi_join),
+            Expr::Cast { expr, data_type } => write!(f, "CAST({} AS {:?})", expr, data_type),
         }
     }
 }
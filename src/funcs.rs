@@ -1,6 +1,8 @@
-use super::expr::{DBCol, DBResult};
+use super::expr::{self, BinaryOperator, DBCol, DBResult};
+use super::join::{self, ColKeys};
 use chrono::{Datelike, Timelike, Utc};
 use num;
+use roaring::RoaringBitmap;
 use spin_sleep;
 use std::cmp::Ordering;
 use std::collections::HashMap;
@@ -16,6 +18,9 @@ lazy_static! {
             m.insert("avg", avg as fn(Vec<DBResult>) ->DBResult);
             m.insert("count", count as fn(Vec<DBResult>) ->DBResult);
             m.insert("coalesce", coalesce as fn(Vec<DBResult>) ->DBResult);
+            m.insert("nullif", nullif as fn(Vec<DBResult>) ->DBResult);
+            m.insert("greatest", greatest as fn(Vec<DBResult>) ->DBResult);
+            m.insert("least", least as fn(Vec<DBResult>) ->DBResult);
             m.insert("trunc", trunc as fn(Vec<DBResult>) ->DBResult);
             m.insert("round", round as fn(Vec<DBResult>) ->DBResult);
             m.insert("abs", abs as fn(Vec<DBResult>) ->DBResult);
@@ -32,6 +37,9 @@ lazy_static! {
         };
 }
 
+// The functions in `FUNC_MAP` which aggregate the rows of each group into one value
+pub const AGGREGATES: [&str; 5] = ["sum", "max", "min", "avg", "count"];
+
 //pub static function_map: HashSet<String> = vec!["sum"].iter().map(|f| f.to_string()).collect();
 
 //pub static function_map: HashMap<String, &dyn Fn(Vec<DBResult>) -> DBResult> =
@@ -44,14 +52,59 @@ lazy_static! {
 //    assert!(groupings.windows(2).all(|w| w[0] == w[1]));
 //}
 
+// Drops the duplicate values of each group of `arg`, for aggregates over DISTINCT values. One NULL is
+// kept for groups which have them, so that a group of only NULLs still aggregates to NULL.
+pub fn distinct(mut arg: DBResult) -> DBResult {
+    let mut nulls = HashMap::new();
+    for (group, col) in arg.cols.iter_mut() {
+        let group_nulls = arg.nulls.get(group).filter(|mask| !mask.is_empty());
+        let positions = col.distinct_positions(group_nulls);
+        if let Some(group_nulls) = group_nulls {
+            let null_mask: RoaringBitmap = positions
+                .iter()
+                .enumerate()
+                .filter(|(_, pos)| group_nulls.contains(**pos as u32))
+                .map(|(i, _)| i as u32)
+                .collect();
+            nulls.insert(group.clone(), null_mask);
+        }
+        *col = col.gather(&positions);
+    }
+    arg.nulls = nulls;
+    arg
+}
+
+// Runs the aggregate `agg` on the non-NULL values of each group of `arg`. A group which has rows
+// but only NULLs aggregates to NULL.
+fn aggregate(mut arg: DBResult, agg: fn(&DBCol) -> DBCol) -> DBResult {
+    let mut nulls = HashMap::new();
+    for (group, col) in arg.cols.iter_mut() {
+        let null_mask = arg.nulls.get(group).filter(|mask| !mask.is_empty());
+        *col = match null_mask {
+            Some(mask) if mask.len() as usize == col.len() => {
+                nulls.insert(group.clone(), (0..1).collect::<RoaringBitmap>());
+                col.gather(&[0])
+            }
+            Some(mask) => {
+                let idxs: Vec<usize> = (0..col.len())
+                    .filter(|i| !mask.contains(*i as u32))
+                    .collect();
+                agg(&col.gather(&idxs))
+            }
+            None => agg(col),
+        };
+    }
+    arg.nulls = nulls;
+    arg
+}
+
 fn sum(mut args: Vec<DBResult>) -> DBResult {
     assert!(args.len() == 1);
-    let mut arg = args.pop().unwrap();
-    for (_, col) in arg.cols.iter_mut() {
+    aggregate(args.pop().unwrap(), |col| {
         if col.len() == 0 {
-            *col = DBCol::Int(vec![]);
+            DBCol::Int(vec![])
         } else {
-            *col = match col {
+            match col {
                 DBCol::Int(vals) => DBCol::Int(vec![vals.iter().sum()]),
                 DBCol::Long(vals) => DBCol::Long(vec![vals.iter().sum()]),
                 DBCol::Float(vals) => DBCol::Float(vec![vals.iter().sum()]),
@@ -59,20 +112,18 @@ fn sum(mut args: Vec<DBResult>) -> DBResult {
                 _ => {
                     panic!("called sum on non-numerical type");
                 }
-            };
+            }
         }
-    }
-    arg
+    })
 }
 
 fn max(mut args: Vec<DBResult>) -> DBResult {
     assert!(args.len() == 1);
-    let mut arg = args.pop().unwrap();
-    for (_, col) in arg.cols.iter_mut() {
+    aggregate(args.pop().unwrap(), |col| {
         if col.len() == 0 {
-            *col = DBCol::Int(vec![]);
+            DBCol::Int(vec![])
         } else {
-            *col = match col {
+            match col {
                 DBCol::Int(vals) => DBCol::Int(vec![*vals.iter().max().unwrap()]),
                 DBCol::Long(vals) => DBCol::Long(vec![*vals.iter().max().unwrap()]),
                 DBCol::Float(vals) => DBCol::Float(vec![*vals
@@ -87,20 +138,18 @@ fn max(mut args: Vec<DBResult>) -> DBResult {
                 _ => {
                     panic!("called max on non-numerical type");
                 }
-            };
+            }
         }
-    }
-    arg
+    })
 }
 
 fn min(mut args: Vec<DBResult>) -> DBResult {
     assert!(args.len() == 1);
-    let mut arg = args.pop().unwrap();
-    for (_, col) in arg.cols.iter_mut() {
+    aggregate(args.pop().unwrap(), |col| {
         if col.len() == 0 {
-            *col = DBCol::Int(vec![]);
+            DBCol::Int(vec![])
         } else {
-            *col = match col {
+            match col {
                 DBCol::Int(vals) => DBCol::Int(vec![*vals.iter().min().unwrap()]),
                 DBCol::Long(vals) => DBCol::Long(vec![*vals.iter().min().unwrap()]),
                 DBCol::Float(vals) => DBCol::Float(vec![*vals
@@ -115,20 +164,18 @@ fn min(mut args: Vec<DBResult>) -> DBResult {
                 _ => {
                     panic!("called min on non-numerical type (e.g., {:?})", col.any());
                 }
-            };
+            }
         }
-    }
-    arg
+    })
 }
 
 fn avg(mut args: Vec<DBResult>) -> DBResult {
     assert!(args.len() == 1);
-    let mut arg = args.pop().unwrap();
-    for (_, col) in arg.cols.iter_mut() {
+    aggregate(args.pop().unwrap(), |col| {
         if col.len() == 0 {
-            *col = DBCol::Int(vec![0]);
+            DBCol::Int(vec![0])
         } else {
-            *col = match col {
+            match col {
                 DBCol::Int(vals) => {
                     let sum: i32 = vals.iter().sum();
                     DBCol::Int(vec![sum / (vals.len() as i32)])
@@ -148,29 +195,158 @@ fn avg(mut args: Vec<DBResult>) -> DBResult {
                 _ => {
                     panic!("called sum on non-numerical type");
                 }
-            };
+            }
         }
-    }
-    arg
+    })
 }
 
 fn count(mut args: Vec<DBResult>) -> DBResult {
     assert!(args.len() == 1);
     let mut arg = args.pop().unwrap();
-    for (_, col) in arg.cols.iter_mut() {
-        *col = DBCol::Int(vec![col.len() as i32]);
+    for (group, col) in arg.cols.iter_mut() {
+        let num_nulls = arg.nulls.get(group).map_or(0, |mask| mask.len() as usize);
+        *col = DBCol::Int(vec![(col.len() - num_nulls) as i32]);
     }
+    arg.nulls.clear();
     arg
 }
 
-// FIXME This version of coalesce does nothing but return a copy of the first column.
-fn coalesce(mut args: Vec<DBResult>) -> DBResult {
-    assert!(args.len() >= 1);
-    let mut arg = args.swap_remove(0);
-    for (_, col) in arg.cols.iter_mut() {
-        *col = col.clone();
+// Runs a function which, row by row, takes its value from one of `args`. `pick` gets the args of a
+// group, cast to a common type and broadcast to the same length, and returns which arg the row at
+// the given position takes its value from, or None if the row is NULL.
+fn pick_args(
+    name: &str,
+    args: Vec<DBResult>,
+    pick: fn(&[ColKeys], usize) -> Option<usize>,
+) -> DBResult {
+    let ref_table = args.iter().find_map(|arg| arg.ref_table.clone());
+    let mut cols = HashMap::new();
+    let mut nulls = HashMap::new();
+    for group in args[0].cols.keys() {
+        let group_cols = args
+            .iter()
+            .map(|arg| {
+                arg.cols
+                    .get(group)
+                    .unwrap_or_else(|| panic!("Arg to {} doesn't have some group", name))
+            })
+            .collect::<Vec<_>>();
+        let len = group_cols.iter().map(|col| col.len()).max().unwrap();
+        let data_types = group_cols
+            .iter()
+            .map(|col| col.data_type())
+            .collect::<Vec<_>>();
+        let common_type = data_types[1..]
+            .iter()
+            .try_fold(data_types[0], |common_type, data_type| {
+                join::key_type(common_type, *data_type)
+            })
+            .unwrap_or_else(|| panic!("Args to {} have incompatible types {:?}", name, data_types));
+
+        let group_args = args
+            .iter()
+            .zip(group_cols)
+            .map(|(arg, col)| {
+                let null_mask = expr::broadcast_null_mask(arg.null_mask(group), col.len(), len);
+                let mut col = if col.data_type() == common_type {
+                    col.clone()
+                } else {
+                    join::cast_keys(col, common_type)
+                };
+                if col.len() == 1 && len > 1 {
+                    col.repeat(len);
+                }
+                (col, null_mask)
+            })
+            .collect::<Vec<_>>();
+        let keys = group_args
+            .iter()
+            .map(|(col, null_mask)| (col, null_mask.as_ref()))
+            .collect::<Vec<_>>();
+
+        // The values of all args are appended in turn, and then gathered by the pick of each row.
+        let mut idxs = Vec::with_capacity(len);
+        let mut null_mask = RoaringBitmap::new();
+        for i in 0..len {
+            match pick(&keys, i) {
+                Some(arg) if !keys[arg].1.is_some_and(|nulls| nulls.contains(i as u32)) => {
+                    idxs.push(arg * len + i);
+                }
+                _ => {
+                    idxs.push(0);
+                    null_mask.insert(i as u32);
+                }
+            }
+        }
+        let mut all_vals: Option<DBCol> = None;
+        for (col, _) in group_args {
+            match &mut all_vals {
+                Some(all_vals) => all_vals.append(col),
+                None => all_vals = Some(col),
+            }
+        }
+        cols.insert(group.clone(), all_vals.unwrap().gather(&idxs));
+        if !null_mask.is_empty() {
+            nulls.insert(group.clone(), null_mask);
+        }
     }
-    arg
+    DBResult {
+        cols,
+        nulls,
+        ref_table,
+    }
+}
+
+fn is_null((_, nulls): ColKeys, i: usize) -> bool {
+    nulls.is_some_and(|nulls| nulls.contains(i as u32))
+}
+
+// The first arg which is not NULL.
+fn coalesce(args: Vec<DBResult>) -> DBResult {
+    assert!(!args.is_empty());
+    pick_args("coalesce", args, |keys, i| {
+        keys.iter().position(|arg| !is_null(*arg, i))
+    })
+}
+
+// NULL if the two args are equal, and otherwise the first.
+fn nullif(args: Vec<DBResult>) -> DBResult {
+    assert!(args.len() == 2);
+    pick_args("nullif", args, |keys, i| {
+        if join::compare_at((keys[0], i), &BinaryOperator::Eq, (keys[1], i)) {
+            None
+        } else {
+            Some(0)
+        }
+    })
+}
+
+// The largest arg which is not NULL, as Postgres has it. Only all NULL args give NULL.
+fn greatest(args: Vec<DBResult>) -> DBResult {
+    assert!(!args.is_empty());
+    pick_args("greatest", args, |keys, i| {
+        pick_extreme(keys, i, BinaryOperator::Gt)
+    })
+}
+
+// The smallest arg which is not NULL, like `greatest`.
+fn least(args: Vec<DBResult>) -> DBResult {
+    assert!(!args.is_empty());
+    pick_args("least", args, |keys, i| {
+        pick_extreme(keys, i, BinaryOperator::Lt)
+    })
+}
+
+fn pick_extreme(keys: &[ColKeys], i: usize, op: BinaryOperator) -> Option<usize> {
+    (0..keys.len())
+        .filter(|arg| !is_null(keys[*arg], i))
+        .reduce(|best, arg| {
+            if join::compare_at((keys[arg], i), &op, (keys[best], i)) {
+                arg
+            } else {
+                best
+            }
+        })
 }
 
 // FIXME This version of json_path_lookup does nothing but return a copy of the first column.
@@ -380,6 +556,7 @@ fn now(args: Vec<DBResult>) -> DBResult {
         cols: vec![(vec![], DBCol::DateTime(vec![Utc::now()]))]
             .into_iter()
             .collect(),
+        nulls: HashMap::new(),
         ref_table: None,
     }
 }
@@ -0,0 +1,775 @@
+use super::config::{BLOOM_FILTER_BITS_PER_KEY, BLOOM_FILTER_NUM_HASHES};
+use super::expr::{BinaryOperator, DBCol, DBResult, DataType, Expr};
+use super::table::{JoinType, NULL_IDX};
+use chrono::{DateTime, Duration, Utc};
+use log::debug;
+use roaring::RoaringBitmap;
+use std::cmp::{self, Ordering};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+// Join algorithms used by `JoinTable::eval_join`. Each one returns the (left row, right row) pairs
+// which satisfy a join constraint, where rows are positions in the evaluated join keys. NULL keys
+// never satisfy a constraint. `build_join_idx` then turns the pairs into the join index.
+
+// The values of a join key along with the positions at which it is NULL.
+pub type Keys<'a, T> = (&'a [T], Option<&'a RoaringBitmap>);
+pub type ColKeys<'a> = (&'a DBCol, Option<&'a RoaringBitmap>);
+
+// Calls `$join` with the vecs of `$left` and `$right`, which have to be the same type of col.
+macro_rules! join_same_types {
+    ($join:ident, $left:expr, $right:expr $(, $arg:expr)*) => {
+        match ($left.0, $right.0) {
+            (DBCol::Int(left), DBCol::Int(right)) => {
+                $join((left, $left.1), (right, $right.1) $(, $arg)*)
+            }
+            (DBCol::Long(left), DBCol::Long(right)) => {
+                $join((left, $left.1), (right, $right.1) $(, $arg)*)
+            }
+            (DBCol::Float(left), DBCol::Float(right)) => {
+                $join((left, $left.1), (right, $right.1) $(, $arg)*)
+            }
+            (DBCol::Double(left), DBCol::Double(right)) => {
+                $join((left, $left.1), (right, $right.1) $(, $arg)*)
+            }
+            (DBCol::Str(left), DBCol::Str(right)) => {
+                $join((left, $left.1), (right, $right.1) $(, $arg)*)
+            }
+            (DBCol::Bool(left), DBCol::Bool(right)) => {
+                $join((left, $left.1), (right, $right.1) $(, $arg)*)
+            }
+            (DBCol::DateTime(left), DBCol::DateTime(right)) => {
+                $join((left, $left.1), (right, $right.1) $(, $arg)*)
+            }
+            (DBCol::Duration(left), DBCol::Duration(right)) => {
+                $join((left, $left.1), (right, $right.1) $(, $arg)*)
+            }
+            (left, right) => {
+                panic!(
+                    "unsupported join types {:?} and {:?}",
+                    left.data_type(),
+                    right.data_type()
+                );
+            }
+        }
+    };
+}
+
+// One conjunct of a join constraint, with each side evaluated under the side of the join it refers
+// to.
+pub enum JoinCond {
+    // `left op right`
+    Cmp {
+        left: DBResult,
+        right: DBResult,
+        op: BinaryOperator,
+    },
+    // `expr BETWEEN low AND high`, where `expr` refers to the left side of the join if
+    // `expr_on_left` and `low` and `high` refer to the other side.
+    Band {
+        expr: DBResult,
+        low: DBResult,
+        high: DBResult,
+        expr_on_left: bool,
+    },
+}
+
+impl JoinCond {
+    // Casts the keys of every side to a common type, so that they can be compared. Panics if the
+    // keys of `constraint`, from which the condition was evaluated, cannot be compared.
+    pub fn normalize_keys(&mut self, constraint: &Expr) {
+        let mut results = match self {
+            JoinCond::Cmp { left, right, .. } => vec![left, right],
+            JoinCond::Band {
+                expr, low, high, ..
+            } => vec![expr, low, high],
+        };
+        let data_types = results
+            .iter()
+            .map(|result| keys(result).0.data_type())
+            .collect::<Vec<_>>();
+        let common_type = data_types[1..]
+            .iter()
+            .try_fold(data_types[0], |common_type, data_type| {
+                key_type(common_type, *data_type)
+            })
+            .unwrap_or_else(|| {
+                panic!(
+                    "Cannot join on ({}) since its keys have incompatible types {:?}",
+                    constraint, data_types
+                )
+            });
+
+        for result in results.iter_mut() {
+            let col = result.cols.get_mut(&vec![]).unwrap();
+            if col.data_type() != common_type {
+                *col = cast_keys(col, common_type);
+            }
+        }
+    }
+
+    fn join(&self) -> Vec<(usize, usize)> {
+        match self {
+            JoinCond::Cmp { left, right, op } => join_on(keys(left), keys(right), op),
+            JoinCond::Band {
+                expr,
+                low,
+                high,
+                expr_on_left,
+            } => {
+                let pairs = join_on_between(keys(expr), keys(low), keys(high));
+                if *expr_on_left {
+                    pairs
+                } else {
+                    pairs
+                        .into_iter()
+                        .map(|(right, left)| (left, right))
+                        .collect()
+                }
+            }
+        }
+    }
+
+    // Keeps only the rows at `positions` of the left side of the join if `on_left`, or else of the
+    // right side, renumbering them from 0 in order.
+    pub fn retain_rows(&mut self, on_left: bool, positions: &[usize]) {
+        let results = match self {
+            JoinCond::Cmp { left, right, .. } => vec![if on_left { left } else { right }],
+            JoinCond::Band {
+                expr,
+                low,
+                high,
+                expr_on_left,
+            } => {
+                if *expr_on_left == on_left {
+                    vec![expr]
+                } else {
+                    vec![low, high]
+                }
+            }
+        };
+        for result in results {
+            let col = result.cols.get_mut(&vec![]).unwrap();
+            *col = col.gather(positions);
+            if let Some(nulls) = result.nulls.get_mut(&vec![]) {
+                *nulls = positions
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pos)| nulls.contains(**pos as u32))
+                    .map(|(i, _)| i as u32)
+                    .collect();
+            }
+        }
+    }
+
+    // Whether the left row `left_idx` and right row `right_idx` satisfy the condition.
+    fn holds(&self, left_idx: usize, right_idx: usize) -> bool {
+        match self {
+            JoinCond::Cmp { left, right, op } => {
+                compare_at((keys(left), left_idx), op, (keys(right), right_idx))
+            }
+            JoinCond::Band {
+                expr,
+                low,
+                high,
+                expr_on_left,
+            } => {
+                let (expr_idx, band_idx) = if *expr_on_left {
+                    (left_idx, right_idx)
+                } else {
+                    (right_idx, left_idx)
+                };
+                compare_at(
+                    (keys(expr), expr_idx),
+                    &BinaryOperator::GtEq,
+                    (keys(low), band_idx),
+                ) && compare_at(
+                    (keys(expr), expr_idx),
+                    &BinaryOperator::LtEq,
+                    (keys(high), band_idx),
+                )
+            }
+        }
+    }
+}
+
+// Joins on the conjunction of `conds`. All equalities are combined into one hash join on a
+// composite key, or with `sort_merge`, the first equality is joined by `sort_merge_join`; without
+// equalities, the first condition picks the algorithm. The remaining conditions are then checked on
+// each joined pair.
+pub fn join_conds(conds: &[JoinCond], sort_merge: bool) -> Vec<(usize, usize)> {
+    assert!(!conds.is_empty());
+    let (eqs, others): (Vec<&JoinCond>, Vec<&JoinCond>) = conds.iter().partition(|cond| {
+        matches!(
+            cond,
+            JoinCond::Cmp {
+                op: BinaryOperator::Eq,
+                ..
+            }
+        )
+    });
+
+    let (mut pairs, residual) = if sort_merge && !eqs.is_empty() {
+        let (left, right) = match eqs[0] {
+            JoinCond::Cmp { left, right, .. } => (keys(left), keys(right)),
+            _ => unreachable!(),
+        };
+        let pairs = join_same_types!(sort_merge_join, left, right);
+        (pairs, eqs[1..].iter().chain(&others).copied().collect())
+    } else if eqs.len() > 1 {
+        let keys: Vec<(ColKeys, ColKeys)> = eqs
+            .iter()
+            .map(|cond| match cond {
+                JoinCond::Cmp { left, right, .. } => (keys(left), keys(right)),
+                _ => unreachable!(),
+            })
+            .collect();
+        (composite_hash_join(&keys), others)
+    } else if eqs.len() == 1 {
+        (eqs[0].join(), others)
+    } else {
+        (others[0].join(), others[1..].to_vec())
+    };
+
+    if !residual.is_empty() {
+        pairs.retain(|(left_idx, right_idx)| {
+            residual
+                .iter()
+                .all(|cond| cond.holds(*left_idx, *right_idx))
+        });
+    }
+    pairs
+}
+
+// The values of one side of a join constraint, which is never grouped.
+pub fn keys(result: &DBResult) -> ColKeys<'_> {
+    (
+        result.cols.get(&vec![]).expect("We're grouping on joins?"),
+        result.null_mask(&[]),
+    )
+}
+
+// The type under which keys of types `left` and `right` are compared, or None if they cannot be
+// compared. Numbers are widened, Int and Long to Long and anything with a Float or Double to Double.
+// Other types only compare with themselves.
+pub fn key_type(left: DataType, right: DataType) -> Option<DataType> {
+    use DataType::*;
+    match (left, right) {
+        (left, right) if left == right => Some(left),
+        (Int, Long) | (Long, Int) => Some(Long),
+        (Int | Long | Float | Double, Int | Long | Float | Double) => Some(Double),
+        _ => None,
+    }
+}
+
+// Casts numeric keys to the wider `data_type` picked by `key_type`.
+pub fn cast_keys(col: &DBCol, data_type: DataType) -> DBCol {
+    match data_type {
+        DataType::Long => DBCol::Long(col.iter_as_long().collect()),
+        DataType::Double => DBCol::Double(col.double_iter().collect()),
+        _ => {
+            panic!("Cannot cast {:?} keys to {:?}", col.data_type(), data_type);
+        }
+    }
+}
+
+// Joins on `left op right`, picking the algorithm from the operator: a hash join for equality, a
+// sort-based join for ranges and a nested loop for anything else. Floats are not `Hash`, so they are
+// hash joined on the bits of their `KeyVal`s.
+pub fn join_on(left: ColKeys, right: ColKeys, op: &BinaryOperator) -> Vec<(usize, usize)> {
+    match op {
+        BinaryOperator::Eq => match (left.0, right.0) {
+            (DBCol::Float(_), DBCol::Float(_)) | (DBCol::Double(_), DBCol::Double(_)) => {
+                composite_hash_join(&[(left, right)])
+            }
+            (DBCol::Int(left_vals), DBCol::Int(right_vals)) => {
+                hash_join((left_vals, left.1), (right_vals, right.1))
+            }
+            (DBCol::Long(left_vals), DBCol::Long(right_vals)) => {
+                hash_join((left_vals, left.1), (right_vals, right.1))
+            }
+            (DBCol::Str(left_vals), DBCol::Str(right_vals)) => {
+                hash_join((left_vals, left.1), (right_vals, right.1))
+            }
+            (DBCol::Bool(left_vals), DBCol::Bool(right_vals)) => {
+                hash_join((left_vals, left.1), (right_vals, right.1))
+            }
+            (DBCol::DateTime(left_vals), DBCol::DateTime(right_vals)) => {
+                hash_join((left_vals, left.1), (right_vals, right.1))
+            }
+            (DBCol::Duration(left_vals), DBCol::Duration(right_vals)) => {
+                hash_join((left_vals, left.1), (right_vals, right.1))
+            }
+            _ => join_same_types!(nested_loop_join, left, right, op),
+        },
+        BinaryOperator::Lt | BinaryOperator::LtEq | BinaryOperator::Gt | BinaryOperator::GtEq => {
+            join_same_types!(range_join, left, right, op)
+        }
+        BinaryOperator::NotEq => join_same_types!(nested_loop_join, left, right, op),
+        _ => {
+            panic!("We do not support join operator {:?}", op);
+        }
+    }
+}
+
+// Joins on `left BETWEEN low AND high`, where `low` and `high` are keys of the right rows.
+pub fn join_on_between(left: ColKeys, low: ColKeys, high: ColKeys) -> Vec<(usize, usize)> {
+    match (left.0, low.0, high.0) {
+        (DBCol::Int(vals), DBCol::Int(low_vals), DBCol::Int(high_vals)) => {
+            band_join((vals, left.1), (low_vals, low.1), (high_vals, high.1))
+        }
+        (DBCol::Long(vals), DBCol::Long(low_vals), DBCol::Long(high_vals)) => {
+            band_join((vals, left.1), (low_vals, low.1), (high_vals, high.1))
+        }
+        (DBCol::Float(vals), DBCol::Float(low_vals), DBCol::Float(high_vals)) => {
+            band_join((vals, left.1), (low_vals, low.1), (high_vals, high.1))
+        }
+        (DBCol::Double(vals), DBCol::Double(low_vals), DBCol::Double(high_vals)) => {
+            band_join((vals, left.1), (low_vals, low.1), (high_vals, high.1))
+        }
+        (DBCol::Str(vals), DBCol::Str(low_vals), DBCol::Str(high_vals)) => {
+            band_join((vals, left.1), (low_vals, low.1), (high_vals, high.1))
+        }
+        (DBCol::DateTime(vals), DBCol::DateTime(low_vals), DBCol::DateTime(high_vals)) => {
+            band_join((vals, left.1), (low_vals, low.1), (high_vals, high.1))
+        }
+        (DBCol::Duration(vals), DBCol::Duration(low_vals), DBCol::Duration(high_vals)) => {
+            band_join((vals, left.1), (low_vals, low.1), (high_vals, high.1))
+        }
+        (vals, low_vals, high_vals) => {
+            panic!(
+                "unsupported join types {:?} between {:?} and {:?}",
+                vals.data_type(),
+                low_vals.data_type(),
+                high_vals.data_type()
+            );
+        }
+    }
+}
+
+// The operator which gives the same constraint once its operands are swapped.
+pub fn flip(op: &BinaryOperator) -> BinaryOperator {
+    match op {
+        BinaryOperator::Lt => BinaryOperator::Gt,
+        BinaryOperator::LtEq => BinaryOperator::GtEq,
+        BinaryOperator::Gt => BinaryOperator::Lt,
+        BinaryOperator::GtEq => BinaryOperator::LtEq,
+        op => op.clone(),
+    }
+}
+
+pub fn hash_join<T: Eq + Hash>(left: Keys<T>, right: Keys<T>) -> Vec<(usize, usize)> {
+    let mut left_rev_map: HashMap<&T, Vec<usize>> = HashMap::new();
+    for (i, val) in non_null(left) {
+        left_rev_map.entry(val).or_default().push(i);
+    }
+
+    let mut pairs = vec![];
+    for (right_idx, val) in non_null(right) {
+        if let Some(left_idxs) = left_rev_map.get(val) {
+            pairs.extend(left_idxs.iter().map(|left_idx| (*left_idx, right_idx)));
+        }
+    }
+    pairs
+}
+
+// A value of a join or group key which can be hashed whatever its type, for composite keys.
+#[derive(PartialEq, Eq, Hash)]
+pub enum KeyVal<'a> {
+    Int(i32),
+    Long(i64),
+    // Floats are compared by their bits, as given by `float_bits` and `double_bits`.
+    Float(u32),
+    Double(u64),
+    Str(&'a str),
+    Bool(bool),
+    DateTime(&'a DateTime<Utc>),
+    Duration(&'a Duration),
+    Months(i32),
+}
+
+impl<'a> KeyVal<'a> {
+    pub fn new(col: &'a DBCol, i: usize) -> Self {
+        match col {
+            DBCol::Int(vals) => KeyVal::Int(vals[i]),
+            DBCol::Long(vals) => KeyVal::Long(vals[i]),
+            DBCol::Float(vals) => KeyVal::Float(float_bits(vals[i])),
+            DBCol::Double(vals) => KeyVal::Double(double_bits(vals[i])),
+            DBCol::Str(vals) => KeyVal::Str(&vals[i]),
+            DBCol::Bool(vals) => KeyVal::Bool(vals[i]),
+            DBCol::DateTime(vals) => KeyVal::DateTime(&vals[i]),
+            DBCol::Duration(vals) => KeyVal::Duration(&vals[i]),
+            DBCol::Months(vals) => KeyVal::Months(vals[i]),
+        }
+    }
+}
+
+// The bits of a float, by which float keys are hashed and compared. -0 is the same as 0, and all
+// NaNs are the same, so that rows with either fall into one group.
+pub fn float_bits(val: f32) -> u32 {
+    if val.is_nan() {
+        f32::NAN.to_bits()
+    } else {
+        (val + 0.).to_bits()
+    }
+}
+
+pub fn double_bits(val: f64) -> u64 {
+    if val.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        (val + 0.).to_bits()
+    }
+}
+
+// The number of distinct non-NULL keys, which estimates how selective an equality join on them is.
+pub fn distinct_count((col, nulls): ColKeys) -> usize {
+    (0..col.len())
+        .filter(|i| !nulls.is_some_and(|nulls| nulls.contains(*i as u32)))
+        .map(|i| KeyVal::new(col, i))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+// A bloom filter over the keys of one side of an equality join, used to drop the rows of the other
+// side whose keys cannot match any of them before joining. Some rows which pass may still not
+// match, but no row which matches is ever dropped.
+pub struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    // Builds the filter from the non-NULL `keys`.
+    pub fn new((col, nulls): ColKeys) -> Self {
+        let num_bits = cmp::max(col.len() * BLOOM_FILTER_BITS_PER_KEY, 64);
+        let mut filter = BloomFilter {
+            bits: vec![0; num_bits.div_ceil(64)],
+        };
+        for i in 0..col.len() {
+            if !nulls.is_some_and(|nulls| nulls.contains(i as u32)) {
+                for bit in filter.bit_idxs(col, i) {
+                    filter.bits[bit / 64] |= 1 << (bit % 64);
+                }
+            }
+        }
+        filter
+    }
+
+    // The positions of `keys` which may match a key of the filter. NULL keys never match.
+    pub fn probe(&self, (col, nulls): ColKeys) -> Vec<usize> {
+        (0..col.len())
+            .filter(|i| !nulls.is_some_and(|nulls| nulls.contains(*i as u32)))
+            .filter(|i| {
+                self.bit_idxs(col, *i)
+                    .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+            })
+            .collect()
+    }
+
+    // The bits set for the key at `i`, derived from one hash by double hashing.
+    fn bit_idxs(&self, col: &DBCol, i: usize) -> impl Iterator<Item = usize> {
+        let mut hasher = DefaultHasher::new();
+        KeyVal::new(col, i).hash(&mut hasher);
+        let hash = hasher.finish();
+        let (first, second) = (hash & u32::MAX as u64, (hash >> 32) | 1);
+        let num_bits = self.bits.len() as u64 * 64;
+        (0..BLOOM_FILTER_NUM_HASHES as u64)
+            .map(move |k| (first.wrapping_add(k.wrapping_mul(second)) % num_bits) as usize)
+    }
+}
+
+// Hash join on several equalities at once, where `keys` holds the (left, right) sides of each. As in
+// `sorted_keys`, NaNs never match anything.
+fn composite_hash_join(keys: &[(ColKeys, ColKeys)]) -> Vec<(usize, usize)> {
+    fn composite_keys<'a>(
+        cols: impl Iterator<Item = ColKeys<'a>> + Clone,
+    ) -> Vec<Option<Vec<KeyVal<'a>>>> {
+        let len = cols.clone().next().map_or(0, |(col, _)| col.len());
+        (0..len)
+            .map(|i| {
+                cols.clone()
+                    .map(|(col, nulls)| {
+                        if nulls.is_some_and(|nulls| nulls.contains(i as u32)) || is_nan(col, i) {
+                            None
+                        } else {
+                            Some(KeyVal::new(col, i))
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    let left_keys = composite_keys(keys.iter().map(|(left, _)| *left));
+    let right_keys = composite_keys(keys.iter().map(|(_, right)| *right));
+
+    let mut left_rev_map: HashMap<&Vec<KeyVal>, Vec<usize>> = HashMap::new();
+    for (i, key) in left_keys.iter().enumerate() {
+        if let Some(key) = key {
+            left_rev_map.entry(key).or_default().push(i);
+        }
+    }
+
+    let mut pairs = vec![];
+    for (right_idx, key) in right_keys.iter().enumerate() {
+        if let Some(left_idxs) = key.as_ref().and_then(|key| left_rev_map.get(key)) {
+            pairs.extend(left_idxs.iter().map(|left_idx| (*left_idx, right_idx)));
+        }
+    }
+    pairs
+}
+
+// Sort-merge join for `left = right`. Both sides are sorted, which is cheap for keys that are
+// already in order since the sort is adaptive, and then merged, matching each run of equal right
+// keys with the run of equal left keys. The pairs are then emitted by right row, in the same order
+// as from `hash_join`, so that either gives the same join index. Since the sort is stable, each
+// left run is already in row order.
+pub fn sort_merge_join<T: PartialOrd>(left: Keys<T>, right: Keys<T>) -> Vec<(usize, usize)> {
+    let num_right = right.0.len();
+    let left = sorted_keys(left);
+    let right = sorted_keys(right);
+    // The run of `left` which each right row joins with
+    let mut left_runs = vec![None; num_right];
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        match left[i].1.partial_cmp(right[j].1) {
+            Some(Ordering::Less) => i += 1,
+            Some(Ordering::Greater) => j += 1,
+            _ => {
+                let left_end = i + left[i..].partition_point(|(_, l)| l <= &right[j].1);
+                let right_end = j + right[j..].partition_point(|(_, r)| r <= &left[i].1);
+                for (right_idx, _) in &right[j..right_end] {
+                    left_runs[*right_idx] = Some(i..left_end);
+                }
+                i = left_end;
+                j = right_end;
+            }
+        }
+    }
+
+    let mut pairs = vec![];
+    for (right_idx, run) in left_runs.into_iter().enumerate() {
+        if let Some(run) = run {
+            pairs.extend(left[run].iter().map(|(left_idx, _)| (*left_idx, right_idx)));
+        }
+    }
+    pairs
+}
+
+// Sort-based join for `left op right` where `op` is one of <, <=, > or >=. The right keys are
+// sorted once, and then each left key binary searches for the run of right keys which satisfy the
+// constraint.
+pub fn range_join<T: PartialOrd>(
+    left: Keys<T>,
+    right: Keys<T>,
+    op: &BinaryOperator,
+) -> Vec<(usize, usize)> {
+    let sorted = sorted_keys(right);
+    let mut pairs = vec![];
+    for (left_idx, val) in non_null(left) {
+        let matches = match op {
+            BinaryOperator::Lt => &sorted[sorted.partition_point(|(_, r)| *r <= val)..],
+            BinaryOperator::LtEq => &sorted[sorted.partition_point(|(_, r)| *r < val)..],
+            BinaryOperator::Gt => &sorted[..sorted.partition_point(|(_, r)| *r < val)],
+            BinaryOperator::GtEq => &sorted[..sorted.partition_point(|(_, r)| *r <= val)],
+            _ => {
+                panic!("{:?} is not a range operator", op);
+            }
+        };
+        pairs.extend(matches.iter().map(|(right_idx, _)| (left_idx, *right_idx)));
+    }
+    pairs
+}
+
+// Sort-based join for `left BETWEEN low AND high`. The left keys are sorted once, and then each
+// right row binary searches for the run of left keys within its band.
+pub fn band_join<T: PartialOrd>(left: Keys<T>, low: Keys<T>, high: Keys<T>) -> Vec<(usize, usize)> {
+    let sorted = sorted_keys(left);
+    let high: HashMap<usize, &T> = non_null(high).collect();
+    let mut pairs = vec![];
+    for (right_idx, low_val) in non_null(low) {
+        if let Some(high_val) = high.get(&right_idx) {
+            let start = sorted.partition_point(|(_, l)| *l < low_val);
+            let end = sorted.partition_point(|(_, l)| l <= high_val);
+            if start < end {
+                pairs.extend(
+                    sorted[start..end]
+                        .iter()
+                        .map(|(left_idx, _)| (*left_idx, right_idx)),
+                );
+            }
+        }
+    }
+    pairs
+}
+
+// Compares every pair of keys, for constraints which none of the other algorithms handle.
+pub fn nested_loop_join<T: PartialOrd>(
+    left: Keys<T>,
+    right: Keys<T>,
+    op: &BinaryOperator,
+) -> Vec<(usize, usize)> {
+    let right: Vec<(usize, &T)> = non_null(right).collect();
+    let mut pairs = vec![];
+    for (left_idx, left_val) in non_null(left) {
+        for (right_idx, right_val) in &right {
+            if compare(left_val, op, right_val) {
+                pairs.push((left_idx, *right_idx));
+            }
+        }
+    }
+    pairs
+}
+
+fn compare<T: PartialOrd>(left: &T, op: &BinaryOperator, right: &T) -> bool {
+    match op {
+        BinaryOperator::Eq => left == right,
+        BinaryOperator::NotEq => left != right,
+        BinaryOperator::Lt => left < right,
+        BinaryOperator::LtEq => left <= right,
+        BinaryOperator::Gt => left > right,
+        BinaryOperator::GtEq => left >= right,
+        _ => {
+            panic!("{:?} is not a comparison operator", op);
+        }
+    }
+}
+
+// Compares the key at `left_idx` of `left` with the one at `right_idx` of `right`. NULLs never
+// compare as true.
+pub fn compare_at(
+    (left, left_idx): (ColKeys, usize),
+    op: &BinaryOperator,
+    (right, right_idx): (ColKeys, usize),
+) -> bool {
+    fn compare_vals<T: PartialOrd>(
+        (left, _): Keys<T>,
+        (right, _): Keys<T>,
+        (left_idx, op, right_idx): (usize, &BinaryOperator, usize),
+    ) -> bool {
+        compare(&left[left_idx], op, &right[right_idx])
+    }
+
+    let is_null =
+        |(_, nulls): ColKeys, idx: usize| nulls.is_some_and(|nulls| nulls.contains(idx as u32));
+    if is_null(left, left_idx) || is_null(right, right_idx) {
+        return false;
+    }
+    join_same_types!(compare_vals, left, right, (left_idx, op, right_idx))
+}
+
+// Orders the value at `left_idx` of `left` before or after the one at `right_idx` of `right`, which
+// has to be the same type of col. Incomparable values, e.g., NaNs, are treated as equal.
+pub fn order_at(
+    (left, left_idx): (&DBCol, usize),
+    (right, right_idx): (&DBCol, usize),
+) -> Ordering {
+    fn order_vals<T: PartialOrd>(
+        (left, _): Keys<T>,
+        (right, _): Keys<T>,
+        (left_idx, right_idx): (usize, usize),
+    ) -> Ordering {
+        left[left_idx]
+            .partial_cmp(&right[right_idx])
+            .unwrap_or(Ordering::Equal)
+    }
+
+    // Months are no join keys, but can be sort keys
+    if let (DBCol::Months(left), DBCol::Months(right)) = (left, right) {
+        return left[left_idx].cmp(&right[right_idx]);
+    }
+    let (left, right): (ColKeys, ColKeys) = ((left, None), (right, None));
+    join_same_types!(order_vals, left, right, (left_idx, right_idx))
+}
+
+// Turns the joined `pairs` into the join index: for each table of either side, the row of that
+// table for every joined row. For outer joins, rows without a match are kept and padded with
+// `NULL_IDX` on the other side.
+pub fn build_join_idx(
+    join_type: &JoinType,
+    left_map: &HashMap<String, Vec<usize>>,
+    right_map: &HashMap<String, Vec<usize>>,
+    pairs: &[(usize, usize)],
+) -> HashMap<String, Vec<usize>> {
+    let left_maps: Vec<(&String, &Vec<usize>)> = left_map.iter().collect();
+    let right_maps: Vec<(&String, &Vec<usize>)> = right_map.iter().collect();
+    let left_len = left_maps.first().map_or(0, |(_, map)| map.len());
+    let right_len = right_maps.first().map_or(0, |(_, map)| map.len());
+
+    let mut all_maps: Vec<(String, Vec<usize>)> =
+        Vec::with_capacity(left_maps.len() + right_maps.len());
+    for (name, _) in left_maps.iter().chain(right_maps.iter()) {
+        all_maps.push((name.to_string(), Vec::with_capacity(pairs.len())));
+    }
+
+    let mut push_row = |left_idx: Option<usize>, right_idx: Option<usize>| {
+        for (i, left_map) in left_maps.iter().enumerate() {
+            all_maps[i]
+                .1
+                .push(left_idx.map_or(NULL_IDX, |idx| left_map.1[idx]));
+        }
+        for (i, right_map) in right_maps.iter().enumerate() {
+            all_maps[i + left_maps.len()]
+                .1
+                .push(right_idx.map_or(NULL_IDX, |idx| right_map.1[idx]));
+        }
+    };
+
+    let mut left_matched = vec![false; left_len];
+    let mut right_matched = vec![false; right_len];
+    for (left_idx, right_idx) in pairs {
+        push_row(Some(*left_idx), Some(*right_idx));
+        left_matched[*left_idx] = true;
+        right_matched[*right_idx] = true;
+    }
+
+    if let JoinType::LeftOuter = join_type {
+        for (left_idx, matched) in left_matched.into_iter().enumerate() {
+            if !matched {
+                push_row(Some(left_idx), None);
+            }
+        }
+    }
+    if let JoinType::RightOuter = join_type {
+        for (right_idx, matched) in right_matched.into_iter().enumerate() {
+            if !matched {
+                push_row(None, Some(right_idx));
+            }
+        }
+    }
+
+    debug!(
+        "[Join] Joined and returning maps for [{}]",
+        all_maps
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+    all_maps.into_iter().collect()
+}
+
+fn is_nan(col: &DBCol, i: usize) -> bool {
+    match col {
+        DBCol::Float(vals) => vals[i].is_nan(),
+        DBCol::Double(vals) => vals[i].is_nan(),
+        _ => false,
+    }
+}
+
+fn non_null<'a, T>((vals, nulls): Keys<'a, T>) -> impl Iterator<Item = (usize, &'a T)> {
+    vals.iter()
+        .enumerate()
+        .filter(move |(i, _)| !nulls.is_some_and(|nulls| nulls.contains(*i as u32)))
+}
+
+// The non-NULL keys in sorted order along with their rows. Keys which are not comparable even to
+// themselves (i.e., NaNs) never match anything, so they are left out.
+fn sorted_keys<T: PartialOrd>(keys: Keys<'_, T>) -> Vec<(usize, &T)> {
+    let mut sorted: Vec<(usize, &T)> = non_null(keys)
+        .filter(|(_, val)| val.partial_cmp(val).is_some())
+        .collect();
+    sorted.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    sorted
+}
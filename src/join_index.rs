@@ -0,0 +1,252 @@
+use super::table::{FileCol, Table};
+use byteorder::{ByteOrder, NativeEndian, WriteBytesExt};
+use log::debug;
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+// Bumped whenever the layout of the index files changes, so that indexes written by an older
+// version are rebuilt instead of loaded.
+const JOIN_INDEX_VERSION: u32 = 1;
+
+// A precomputed join index for an equality join between two file columns, stored in the
+// `__join_keys__` directory of a DB. For a constraint `A.x = B.y` the directory holds:
+// `A.x = B.y.left`: For every row of A, the rows of B it joins with, concatenated.
+// `A.x = B.y.left.startpts`: The offset into `.left` at which the rows for each row of A start,
+// with one trailing entry for the total length.
+// `A.x = B.y.left.null_idxs`: The rows of A which join with nothing.
+// The `.right` files hold the same mapping from B to A. All files are packed native-endian u32s.
+// Indexes written by `JoinIndex::write` also have an `A.x = B.y.meta` file recording the version
+// and the state of both key columns when the index was built. If the columns have changed since,
+// the index is stale and is not loaded. While the index is being written, the `.meta` file marks it
+// as incomplete, so that an index without one is always taken to be written by other tools.
+pub struct JoinIndex {
+    idxs: Mmap,
+    startpts: Mmap,
+}
+
+impl JoinIndex {
+    // Loads the index mapping rows of `from` to rows of `to` from `dir`, whichever way round the
+    // constraint was written when the index was built. Returns None if there is no such index or it
+    // does not fit the current tables.
+    pub fn load(dir: &Path, from: &FileCol, to: &FileCol) -> Option<JoinIndex> {
+        let from_name = qualified_name(from);
+        let to_name = qualified_name(to);
+        let candidates = [
+            format!("{} = {}.left", from_name, to_name),
+            format!("{} = {}.right", to_name, from_name),
+        ];
+        for name in &candidates {
+            let idxs_path = dir.join(name);
+            let startpts_path = dir.join(format!("{}.startpts", name));
+            if !idxs_path.exists() || !startpts_path.exists() {
+                continue;
+            }
+
+            let meta_path = dir.join(format!("{}.meta", base_name(name)));
+            if meta_path.exists() && !JoinIndexMeta::read(&meta_path).is_fresh(from, to) {
+                debug!("[JoinIndex] Ignoring {:?} since it is stale", idxs_path);
+                continue;
+            }
+
+            let index = JoinIndex {
+                idxs: map_file(&idxs_path),
+                startpts: map_file(&startpts_path),
+            };
+            if index.fits(from, to) {
+                debug!("[JoinIndex] Using {:?}", idxs_path);
+                return Some(index);
+            } else {
+                debug!("[JoinIndex] Ignoring {:?} since it does not fit", idxs_path);
+            }
+        }
+        None
+    }
+
+    // The rows of the other table which `row` joins with.
+    pub fn get(&self, row: usize) -> impl Iterator<Item = usize> + '_ {
+        let start = read_idx(&self.startpts, row);
+        let end = read_idx(&self.startpts, row + 1);
+        (start..end).map(move |i| read_idx(&self.idxs, i))
+    }
+
+    // Writes the index of the equality join between `left` and `right` into `dir`, where `pairs`
+    // are the (left row, right row) pairs which join.
+    pub fn write(
+        dir: &Path,
+        left: &FileCol,
+        right: &FileCol,
+        pairs: impl Iterator<Item = (usize, usize)>,
+    ) {
+        let name = format!("{} = {}", qualified_name(left), qualified_name(right));
+        debug!("[JoinIndex] Writing {} to {:?}", name, dir);
+        fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Could not create {:?}", dir));
+
+        // Each file is replaced at once by renaming, but a crash between files would leave a mix of
+        // old and new ones, so the index is marked as incomplete until its metadata is written last.
+        let meta_path = dir.join(format!("{}.meta", name));
+        write_meta(&meta_path, &JoinIndexMeta::incomplete());
+
+        let mut left_rows = vec![vec![]; left.table.upgrade().unwrap().len()];
+        let mut right_rows = vec![vec![]; right.table.upgrade().unwrap().len()];
+        for (left_idx, right_idx) in pairs {
+            left_rows[left_idx].push(right_idx as u32);
+            right_rows[right_idx].push(left_idx as u32);
+        }
+        write_rows(&dir.join(format!("{}.left", name)), &left_rows);
+        write_rows(&dir.join(format!("{}.right", name)), &right_rows);
+
+        write_meta(&meta_path, &JoinIndexMeta::new(left, right));
+    }
+
+    fn fits(&self, from: &FileCol, to: &FileCol) -> bool {
+        let from_len = from.table.upgrade().unwrap().len();
+        let to_len = to.table.upgrade().unwrap().len();
+        self.startpts.len() == (from_len + 1) * 4
+            && read_idx(&self.startpts, from_len) * 4 == self.idxs.len()
+            && self.idxs.len() / 4 <= from_len * to_len
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct JoinIndexMeta {
+    version: u32,
+    cols: Vec<ColStamp>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct ColStamp {
+    name: String,
+    size: u64,
+    mtime_ns: u64,
+}
+
+impl JoinIndexMeta {
+    fn new(left: &FileCol, right: &FileCol) -> Self {
+        JoinIndexMeta {
+            version: JOIN_INDEX_VERSION,
+            cols: vec![ColStamp::new(left), ColStamp::new(right)],
+        }
+    }
+
+    // The metadata of an index which is still being written, which is never fresh.
+    fn incomplete() -> Self {
+        JoinIndexMeta {
+            version: JOIN_INDEX_VERSION,
+            cols: vec![],
+        }
+    }
+
+    fn read(path: &Path) -> Self {
+        let meta = fs::read_to_string(path).unwrap_or_else(|_| panic!("Could not read {:?}", path));
+        serde_json::from_str(&meta).unwrap_or_else(|_| panic!("Malformed {:?}", path))
+    }
+
+    // Whether the index is of the current version and neither key column changed since it was
+    // written. The order of the columns does not matter.
+    fn is_fresh(&self, from: &FileCol, to: &FileCol) -> bool {
+        let current = JoinIndexMeta::new(from, to);
+        self.version == current.version
+            && self.cols.len() == 2
+            && current.cols.iter().all(|stamp| self.cols.contains(stamp))
+    }
+}
+
+impl ColStamp {
+    fn new(col: &FileCol) -> Self {
+        let path = col.table.upgrade().unwrap().path().join(col.name());
+        let metadata = fs::metadata(&path).unwrap_or_else(|_| panic!("Could not stat {:?}", path));
+        ColStamp {
+            name: qualified_name(col),
+            size: metadata.len(),
+            mtime_ns: metadata
+                .modified()
+                .unwrap()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos() as u64,
+        }
+    }
+}
+
+// Writes `rows` in the layout described for `JoinIndex`, to `path` and its `.startpts` and
+// `.null_idxs` siblings.
+fn write_rows(path: &Path, rows: &[Vec<u32>]) {
+    let mut startpts = Vec::with_capacity(rows.len() + 1);
+    let mut null_idxs = vec![];
+    let mut idxs = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        startpts.push(idxs.len() as u32);
+        if row.is_empty() {
+            null_idxs.push(i as u32);
+        }
+        idxs.extend(row);
+    }
+    startpts.push(idxs.len() as u32);
+
+    let file_name = path.file_name().unwrap().to_str().unwrap();
+    write_u32_file(path, &idxs);
+    write_u32_file(
+        &path.with_file_name(format!("{}.startpts", file_name)),
+        &startpts,
+    );
+    write_u32_file(
+        &path.with_file_name(format!("{}.null_idxs", file_name)),
+        &null_idxs,
+    );
+}
+
+fn write_u32_file(path: &Path, vals: &[u32]) {
+    replace_file(path, |writer| {
+        for val in vals {
+            writer.write_u32::<NativeEndian>(*val).unwrap();
+        }
+    });
+}
+
+fn write_meta(path: &Path, meta: &JoinIndexMeta) {
+    replace_file(path, |writer| serde_json::to_writer(writer, meta).unwrap());
+}
+
+// Writes `path` through a temporary file which is then renamed over it, so that `path` never holds
+// a partially written file.
+fn replace_file(path: &Path, write: impl FnOnce(&mut BufWriter<File>)) {
+    let tmp_path = path.with_file_name(format!(
+        "{}.tmp",
+        path.file_name().unwrap().to_str().unwrap()
+    ));
+    let mut writer = BufWriter::new(
+        File::create(&tmp_path).unwrap_or_else(|_| panic!("Could not create {:?}", tmp_path)),
+    );
+    write(&mut writer);
+    writer.flush().unwrap();
+    fs::rename(&tmp_path, path).unwrap_or_else(|_| panic!("Could not write {:?}", path));
+}
+
+// The constraint part of an index file name, i.e., without the `.left` or `.right`.
+fn base_name(name: &str) -> &str {
+    name.rsplit_once('.').unwrap().0
+}
+
+// The name of `col` qualified by the name of its table's directory, which is also the table's name
+// unless it is aliased.
+fn qualified_name(col: &FileCol) -> String {
+    let table = col.table.upgrade().unwrap();
+    format!(
+        "{}.{}",
+        table.path().file_name().unwrap().to_str().unwrap(),
+        col.name()
+    )
+}
+
+fn map_file(path: &Path) -> Mmap {
+    let file = File::open(path).unwrap_or_else(|_| panic!("Could not open {:?}", path));
+    unsafe { Mmap::map(&file) }.unwrap_or_else(|_| panic!("Could not map {:?}", path))
+}
+
+fn read_idx(buf: &[u8], i: usize) -> usize {
+    NativeEndian::read_u32(&buf[(i * 4)..(i * 4 + 4)]) as usize
+}
@@ -0,0 +1,118 @@
+use super::config::DEFAULT_SELECTIVITY;
+use super::exec::{ExecParams, ExecStats, RunContext};
+use super::join;
+use super::table::{FileCol, Table};
+use log::debug;
+use std::rc::Rc;
+
+// Join order optimization for multi-way inner joins. The relations are numbered in the order they
+// appear in the FROM clause, and each is described by its estimated number of rows once the
+// predicates pushed down into it are applied. The optimizer picks the left-deep join tree with
+// the smallest total estimated size of its intermediate results.
+
+// A conjunct of the join constraints, as seen by the optimizer.
+pub struct JoinPred {
+    // The relations the conjunct refers to
+    pub relations: Vec<usize>,
+    // For an equality between columns of two relations, the (relation, distinct count) of the key
+    // on each side
+    pub keys: Option<[(usize, f64); 2]>,
+}
+
+// A left-deep join order: the first relation, then each relation joined to it along with the
+// predicates applied by that join.
+pub type JoinOrder<T = usize> = (usize, Vec<(usize, Vec<T>)>);
+
+// Picks the join order for relations with `rows` estimated rows, joined on `preds`. Orders of the
+// same cost are told apart by the size of the first relation. Returns None if the relations cannot
+// all be joined without a cross product.
+pub fn order(rows: &[f64], preds: &[JoinPred]) -> Option<JoinOrder> {
+    let (cost, order) = (0..rows.len())
+        .filter_map(|first| greedy_order(first, rows, preds))
+        .min_by(|(cost, (first, _)), (other_cost, (other_first, _))| {
+            (cost, rows[*first])
+                .partial_cmp(&(other_cost, rows[*other_first]))
+                .unwrap()
+        })?;
+    debug!(
+        "[JoinOrder] Picked {:?} with estimated cost {}",
+        order, cost
+    );
+    Some(order)
+}
+
+// Builds the order starting from `first`, each time joining the relation which gives the smallest
+// result. Returns the total estimated size of the intermediate results along with the order.
+fn greedy_order(first: usize, rows: &[f64], preds: &[JoinPred]) -> Option<(f64, JoinOrder)> {
+    let mut joined = vec![false; rows.len()];
+    let mut applied = vec![false; preds.len()];
+    joined[first] = true;
+    let mut size = rows[first];
+    let mut cost = 0.;
+    let mut steps = vec![];
+
+    while steps.len() + 1 < rows.len() {
+        let mut best: Option<(f64, usize, Vec<usize>)> = None;
+        for next in (0..rows.len()).filter(|next| !joined[*next]) {
+            let next_preds = (0..preds.len())
+                .filter(|pred| {
+                    !applied[*pred]
+                        && preds[*pred]
+                            .relations
+                            .iter()
+                            .all(|relation| joined[*relation] || *relation == next)
+                })
+                .collect::<Vec<_>>();
+            if !next_preds
+                .iter()
+                .any(|pred| preds[*pred].relations.contains(&next))
+            {
+                continue;
+            }
+
+            let est = next_preds.iter().fold(size * rows[next], |est, pred| {
+                est * selectivity(&preds[*pred], rows)
+            });
+            if best.as_ref().is_none_or(|(best_est, ..)| est < *best_est) {
+                best = Some((est, next, next_preds));
+            }
+        }
+
+        let (est, next, next_preds) = best?;
+        joined[next] = true;
+        for pred in &next_preds {
+            applied[*pred] = true;
+        }
+        size = est;
+        cost += est;
+        steps.push((next, next_preds));
+    }
+    Some((cost, (first, steps)))
+}
+
+// The fraction of pairs of rows which satisfy `pred`. For an equality of keys, each key value on
+// the side with fewer distinct values is assumed to match one on the other side.
+fn selectivity(pred: &JoinPred, rows: &[f64]) -> f64 {
+    match pred.keys {
+        Some([(left, left_distinct), (right, right_distinct)]) => {
+            let distinct = left_distinct
+                .min(rows[left])
+                .max(right_distinct.min(rows[right]))
+                .max(1.);
+            1. / distinct
+        }
+        None => DEFAULT_SELECTIVITY,
+    }
+}
+
+// The number of distinct values of `col` in `table`.
+pub fn distinct_count(col: &FileCol, table: &Rc<dyn Table>) -> f64 {
+    let run_context = RunContext {
+        index: None,
+        groups: Default::default(),
+        ref_table: None,
+        exec_params: ExecParams::default(),
+    };
+    let result = col.read(&run_context, &mut ExecStats::new(), table);
+    join::distinct_count(join::keys(&result)) as f64
+}
@@ -1,13 +1,18 @@
 mod bdc;
 pub mod bin_utils;
 mod byp;
+mod cast;
 pub mod config;
 mod db;
 mod exec;
 mod expr;
 mod funcs;
+mod join;
+mod join_index;
+mod join_order;
 mod parser;
 pub mod query_utils;
+mod semi_join;
 mod table;
 mod tdacb;
 mod utils;
@@ -17,10 +22,13 @@ extern crate lazy_static;
 
 pub use config as cham_config;
 pub use db::DB;
-pub use exec::{read_selectivities, ApproxOptType, ExecParams, ExecStats, Executor, RunContext};
-pub use expr::{BinaryOperator, DBCol, DBResult, Expr};
+pub use exec::{
+    read_selectivities, ApproxOptType, ExecError, ExecParams, ExecStats, Executor, Groups,
+    RunContext,
+};
+pub use expr::{BinaryOperator, DBCol, DBResult, Expr, ExprCreateError};
 use log::debug;
-pub use parser::{JoinTableType, ParseParams, Parser, PredNode, Query};
+pub use parser::{JoinTableType, ParseError, ParseParams, Parser, PredNode, Query};
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
@@ -68,7 +76,7 @@ pub struct Opt {
 // slow.
 pub fn print_results(results: &Vec<DBResult>) {
     assert!(!results.is_empty());
-    let groups: Vec<Vec<DBCol>> = results[0].cols.keys().cloned().collect();
+    let groups: Vec<Vec<DBCol>> = results[0].groups().into_iter().cloned().collect();
     for group in groups {
         let printables: Vec<Vec<Box<dyn fmt::Debug>>> = results
             .iter()
@@ -147,7 +155,15 @@ pub fn run(opt: Opt) {
             num_skipped += 1;
         } else {
             let mut exec_stats = ExecStats::new();
-            let results = exec.run(query.unwrap(), &Default::default(), &mut exec_stats);
+            let results = match exec.try_run(query.unwrap(), &Default::default(), &mut exec_stats) {
+                Ok(results) => results,
+                Err(err) => {
+                    eprintln!("Failed query: {} ({:?})", i + 1, err);
+                    num_skipped += 1;
+                    i += 1;
+                    continue;
+                }
+            };
             assert!(!results.is_empty());
             if opt.print_results {
                 print_results(&results);
@@ -1,9 +1,12 @@
 use super::byp::Byp;
-use super::config::SMOOTHING_PARAMETER;
+use super::config::{DEFAULT_SELECTIVITY, SEMI_JOIN_COST, SMOOTHING_PARAMETER};
 use super::db::DB;
-use super::exec::{ApproxOptType, ExecParams, ExecStats, RunContext};
-use super::expr::{BinaryOperator, DBCol, DBResult, Expr, ExprCreateError};
-use super::table::{FileTable, Id, JoinTable, JoinType, Table};
+use super::exec::{ApproxOptType, ExecParams, ExecStats, Groups, RunContext};
+use super::expr::{and3, or3, BinaryOperator, DBCol, DBResult, DataType, Expr, ExprCreateError};
+use super::join::{self, KeyVal};
+use super::join_order;
+use super::semi_join::SemiJoin;
+use super::table::{as_file_table, FileCol, FileTable, Id, JoinTable, JoinType, Table};
 use super::tdacb::Tdacb;
 use crate::bdc::BDC;
 use log::debug;
@@ -11,9 +14,10 @@ use roaring::RoaringBitmap;
 use sqlparser::ast;
 use sqlparser::dialect::PostgreSqlDialect;
 use sqlparser::parser::{Parser as RawParser, ParserError as RawParserError};
+use sqlparser::tokenizer::{Token, Tokenizer};
 use std::cell::RefCell;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::rc::Rc;
@@ -30,6 +34,8 @@ pub enum Node {
 #[derive(Clone)]
 pub struct SelectNode {
     items: Vec<SelectItem>,
+    // SELECT DISTINCT
+    distinct: bool,
 }
 
 #[derive(Clone)]
@@ -65,6 +71,33 @@ impl SelectNode {
         assert!(results.len() > 0);
         results
     }
+
+    // Drops the duplicate rows of `results` for a SELECT DISTINCT. Rows of different groups can be
+    // duplicates, so the rows of all groups are put into a single ungrouped col.
+    pub fn eval_distinct(&self, results: Vec<DBResult>) -> Vec<DBResult> {
+        if !self.distinct || results[0].cols.is_empty() {
+            return results;
+        }
+
+        let (groups, lens) = group_lens(&results);
+        let cols: Vec<(DBCol, RoaringBitmap)> = results
+            .iter()
+            .map(|result| flatten_groups(result, &groups, &lens))
+            .collect();
+        let mut seen = HashSet::new();
+        let positions: Vec<usize> = (0..lens.iter().sum())
+            .filter(|&i| {
+                let row: Vec<Option<DBCol>> = cols
+                    .iter()
+                    .map(|(col, nulls)| {
+                        Some(col.gather(&[i])).filter(|_| !nulls.contains(i as u32))
+                    })
+                    .collect();
+                seen.insert(row)
+            })
+            .collect();
+        gather_rows(&results, &groups, &lens, &positions)
+    }
 }
 
 impl SelectItem {
@@ -110,10 +143,20 @@ impl SelectItem {
         }
     }
 
+    fn has_aggregate(&self) -> bool {
+        match self {
+            SelectItem::OrNode(items) | SelectItem::AndNode(items) => {
+                items.iter().any(|item| item.has_aggregate())
+            }
+            SelectItem::SelectAtom(expr) => expr.has_aggregate(),
+        }
+    }
+
     fn eval(&self, run_context: &RunContext, exec_stats: &mut ExecStats) -> DBResult {
         match self {
             SelectItem::OrNode(items) => {
                 let mut result = HashMap::new();
+                let mut nulls = HashMap::new();
                 assert!(items.len() == 2);
                 let left = items[0].eval(run_context, exec_stats);
                 let right = items[1].eval(run_context, exec_stats);
@@ -122,6 +165,8 @@ impl SelectItem {
                 }
                 assert_eq!(left.cols.len(), right.cols.len());
                 for group in left.cols.keys() {
+                    let left_nulls = left.null_mask(group);
+                    let right_nulls = right.null_mask(group);
                     let left = left.cols.get(group).unwrap();
                     let right = right
                         .cols
@@ -129,21 +174,38 @@ impl SelectItem {
                         .expect("left and right don't have same groups");
                     assert_eq!(left.len(), right.len());
                     if let (DBCol::Bool(left), DBCol::Bool(right)) = (left, right) {
+                        let mut null_mask = RoaringBitmap::new();
                         result.insert(
                             group.clone(),
                             DBCol::Bool(
                                 left.iter()
                                     .zip(right.iter())
-                                    .map(|(x, y)| *x || *y)
+                                    .enumerate()
+                                    .map(|(i, (x, y))| {
+                                        let i = i as u32;
+                                        let x = Some(*x)
+                                            .filter(|_| !left_nulls.is_some_and(|m| m.contains(i)));
+                                        let y = Some(*y).filter(|_| {
+                                            !right_nulls.is_some_and(|m| m.contains(i))
+                                        });
+                                        or3(x, y).unwrap_or_else(|| {
+                                            null_mask.insert(i);
+                                            false
+                                        })
+                                    })
                                     .collect(),
                             ),
                         );
+                        if !null_mask.is_empty() {
+                            nulls.insert(group.clone(), null_mask);
+                        }
                     } else {
                         panic!("Incompatible OR types");
                     }
                 }
                 DBResult {
                     cols: result,
+                    nulls,
                     ref_table: left
                         .ref_table
                         .or(right.ref_table)
@@ -152,6 +214,7 @@ impl SelectItem {
             }
             SelectItem::AndNode(items) => {
                 let mut result = HashMap::new();
+                let mut nulls = HashMap::new();
                 assert!(items.len() == 2);
                 let left = items[0].eval(run_context, exec_stats);
                 let right = items[1].eval(run_context, exec_stats);
@@ -160,6 +223,8 @@ impl SelectItem {
                 }
                 assert_eq!(left.cols.len(), right.cols.len());
                 for group in left.cols.keys() {
+                    let left_nulls = left.null_mask(group);
+                    let right_nulls = right.null_mask(group);
                     let left = left.cols.get(group).unwrap();
                     let right = right
                         .cols
@@ -167,21 +232,38 @@ impl SelectItem {
                         .expect("left and right don't have same groups");
                     assert_eq!(left.len(), right.len());
                     if let (DBCol::Bool(left), DBCol::Bool(right)) = (left, right) {
+                        let mut null_mask = RoaringBitmap::new();
                         result.insert(
                             group.clone(),
                             DBCol::Bool(
                                 left.iter()
                                     .zip(right.iter())
-                                    .map(|(x, y)| *x && *y)
+                                    .enumerate()
+                                    .map(|(i, (x, y))| {
+                                        let i = i as u32;
+                                        let x = Some(*x)
+                                            .filter(|_| !left_nulls.is_some_and(|m| m.contains(i)));
+                                        let y = Some(*y).filter(|_| {
+                                            !right_nulls.is_some_and(|m| m.contains(i))
+                                        });
+                                        and3(x, y).unwrap_or_else(|| {
+                                            null_mask.insert(i);
+                                            false
+                                        })
+                                    })
                                     .collect(),
                             ),
                         );
+                        if !null_mask.is_empty() {
+                            nulls.insert(group.clone(), null_mask);
+                        }
                     } else {
                         panic!("Incompatible OR types");
                     }
                 }
                 DBResult {
                     cols: result,
+                    nulls,
                     ref_table: left
                         .ref_table
                         .or(right.ref_table)
@@ -719,14 +801,14 @@ impl PredNode {
             let now = Instant::now();
             let tdacb = Tdacb::new(self);
             let plan = tdacb.find_plan(exec_stats);
-            exec_stats.plan_time_ms = now.elapsed().as_millis();
+            exec_stats.plan_time_ms += now.elapsed().as_millis();
             return tdacb.eval(&plan, index, exec_params, exec_stats);
         } else if let ApproxOptType::BDC | ApproxOptType::BDCWithBestD = exec_params.approx_opt_type
         {
             let now = Instant::now();
             let bdc = BDC::new(self);
             let plan = bdc.plan();
-            exec_stats.plan_time_ms = now.elapsed().as_millis();
+            exec_stats.plan_time_ms += now.elapsed().as_millis();
             if exec_params.check_plan_only {
                 plan.check_ordering();
                 return RoaringBitmap::new();
@@ -971,6 +1053,51 @@ impl PredNode {
         tables
     }
 
+    // The atoms ANDed at the top of the predicate which only reference the file table `table_id`.
+    // Rows of that table which fail any of them can be dropped before it is joined.
+    pub fn pushdown_atoms(&self, table_id: Id) -> Vec<&PredAtomNode> {
+        match self {
+            PredNode::PredAtomNode(atom) => {
+                if atom.only_refers_to(&[table_id]) {
+                    vec![atom]
+                } else {
+                    vec![]
+                }
+            }
+            PredNode::AndNode(children) => children
+                .iter()
+                .flat_map(|child| child.pushdown_atoms(table_id))
+                .collect(),
+            PredNode::OrNode(_) => vec![],
+        }
+    }
+
+    // Removes the atoms that `pushdown_atoms` returns for any of `table_ids`, since they were
+    // already applied while joining. Returns None if nothing is left.
+    pub fn without_pushdown_atoms(self, table_ids: &[Id]) -> Option<PredNode> {
+        match self {
+            PredNode::PredAtomNode(atom) => {
+                if atom.only_refers_to(table_ids) {
+                    None
+                } else {
+                    Some(PredNode::PredAtomNode(atom))
+                }
+            }
+            PredNode::AndNode(children) => {
+                let mut children = children
+                    .into_iter()
+                    .filter_map(|child| child.without_pushdown_atoms(table_ids))
+                    .collect::<Vec<_>>();
+                match children.len() {
+                    0 => None,
+                    1 => children.pop(),
+                    _ => Some(PredNode::AndNode(children)),
+                }
+            }
+            PredNode::OrNode(_) => Some(self),
+        }
+    }
+
     pub fn is_outwardly_conjunctive(&self) -> bool {
         match self {
             PredNode::PredAtomNode(_) => true,
@@ -1034,11 +1161,16 @@ impl PredAtomNode {
     }
 
     pub fn lookup_cost(&self) -> f64 {
-        *self
-            .cost_map
+        // Semi-joins evaluate a whole subquery, so they are much costlier than other atoms.
+        let default_cost = match self.expr {
+            Expr::SemiJoin(_) => SEMI_JOIN_COST,
+            _ => 1.0,
+        };
+        self.cost_map
             .as_ref()
             .and_then(|map| map.get(&self.expr.to_string()))
-            .unwrap_or(&1.0)
+            .cloned()
+            .unwrap_or(default_cost)
     }
 
     pub fn eval(
@@ -1046,28 +1178,53 @@ impl PredAtomNode {
         index: &RoaringBitmap,
         exec_params: &ExecParams,
         exec_stats: &mut ExecStats,
+    ) -> RoaringBitmap {
+        self.eval_with_ref_table(index, None, exec_params, exec_stats)
+    }
+
+    // Evaluates the atom on the rows `index` of `table` itself, rather than of the table its
+    // columns were parsed against. Used to filter a file table before it is joined.
+    pub fn eval_on_table(
+        &self,
+        index: &RoaringBitmap,
+        table: &Rc<dyn Table>,
+        exec_params: &ExecParams,
+        exec_stats: &mut ExecStats,
+    ) -> RoaringBitmap {
+        self.eval_with_ref_table(index, Some(table.clone()), exec_params, exec_stats)
+    }
+
+    fn eval_with_ref_table(
+        &self,
+        index: &RoaringBitmap,
+        ref_table: Option<Rc<dyn Table>>,
+        exec_params: &ExecParams,
+        exec_stats: &mut ExecStats,
     ) -> RoaringBitmap {
         let result = self.expr.eval(
             &RunContext {
                 index: Some(index.clone()),
-                groups: vec![],
-                ref_table: None,
+                groups: Default::default(),
+                ref_table,
                 exec_params: exec_params.clone(),
             },
             exec_stats,
         );
         exec_stats.num_preds_evaled += index.len() as u128;
         assert!(result.cols.len() == 1);
-        let (_, col) = result.cols.iter().next().unwrap();
+        let (group, col) = result.cols.iter().next().unwrap();
         if let DBCol::Bool(vals) = col {
+            // Only rows for which the atom is TRUE are returned; NULL counts the same as FALSE.
+            // Since predicates are ANDs and ORs of atoms, this gives the three-valued result of the
+            // whole predicate whenever it is TRUE, which is all that filtering needs.
             let ret: RoaringBitmap = index
                 .iter()
                 .enumerate()
                 .filter(|(i, _)| {
                     if result.ref_table.is_none() {
-                        vals[0]
+                        vals[0] && !result.is_null(group, 0)
                     } else {
-                        vals[*i]
+                        vals[*i] && !result.is_null(group, *i)
                     }
                 })
                 .map(|(_, idx)| idx)
@@ -1085,6 +1242,14 @@ impl PredAtomNode {
         }
     }
 
+    // Whether the atom references columns of exactly one file table, which is one of `table_ids`.
+    fn only_refers_to(&self, table_ids: &[Id]) -> bool {
+        let tables = self.get_ref_tables();
+        !tables.is_empty()
+            && tables.iter().all(|table| table.id() == tables[0].id())
+            && table_ids.contains(&tables[0].id())
+    }
+
     pub fn get_ref_tables(&self) -> Vec<Rc<FileTable>> {
         let cols = self.expr.get_all_cols();
         cols.iter()
@@ -1099,51 +1264,309 @@ pub struct GroupByNode {
 }
 
 impl GroupByNode {
-    pub fn eval(&self, run_context: &RunContext, exec_stats: &mut ExecStats) -> Vec<Vec<DBCol>> {
-        let mut groups: Vec<Vec<DBCol>> = vec![];
-        for item in &self.items {
-            let mut result = item.eval(&run_context, exec_stats);
-            assert!(result.cols.len() == 1);
-            let (_, col) = result.cols.iter_mut().next().unwrap();
-            if let DBCol::Float(_) | DBCol::Double(_) = col {
-                // Don't want to deal with float/double-based groups
-                continue;
+    // Hashes each row into its group by the values of the GROUP BY items in the row.
+    pub fn eval(&self, run_context: &RunContext, exec_stats: &mut ExecStats) -> Groups {
+        let results: Vec<DBResult> = self
+            .items
+            .iter()
+            .map(|item| item.eval(run_context, exec_stats))
+            .collect();
+        assert!(results.iter().all(|result| result.is_ungrouped()));
+        let num_rows = results.iter().map(|result| result.len()).max().unwrap_or(0);
+        // A value, e.g., a literal, puts every row into the same group.
+        let keys: Vec<join::ColKeys> = results
+            .iter()
+            .filter(|result| result.ref_table.is_some() || result.len() == num_rows)
+            .map(join::keys)
+            .collect();
+
+        let mut groups = Groups::default();
+        if keys.is_empty() {
+            return groups;
+        }
+        // NULL values of the keys are None.
+        let mut group_ids: HashMap<Vec<Option<KeyVal>>, usize> = HashMap::new();
+        for i in 0..num_rows {
+            let key = keys
+                .iter()
+                .map(|(col, nulls)| {
+                    Some(KeyVal::new(col, i))
+                        .filter(|_| !nulls.is_some_and(|nulls| nulls.contains(i as u32)))
+                })
+                .collect();
+            let id = *group_ids.entry(key).or_insert_with(|| {
+                // NULLs all fall into one group, keyed by an empty col.
+                groups.keys.push(
+                    keys.iter()
+                        .map(|(col, nulls)| match nulls {
+                            Some(nulls) if nulls.contains(i as u32) => col.gather(&[]),
+                            _ => col.gather(&[i]),
+                        })
+                        .collect(),
+                );
+                groups.rows.push(vec![]);
+                groups.rows.len() - 1
+            });
+            groups.rows[id].push(i);
+        }
+        groups
+    }
+}
+
+// A HAVING condition, which drops the groups of the projection's results for which it is not
+// true. It is evaluated per group, so it may have aggregates which are not projected.
+#[derive(Clone)]
+pub struct HavingNode {
+    pred: SelectItem,
+}
+
+impl fmt::Display for HavingNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, " HAVING {}", self.pred)
+    }
+}
+
+impl HavingNode {
+    pub fn eval(
+        &self,
+        mut results: Vec<DBResult>,
+        run_context: &RunContext,
+        exec_stats: &mut ExecStats,
+    ) -> Vec<DBResult> {
+        let mut pred = self.pred.eval(run_context, exec_stats);
+        if !run_context.groups.is_empty() && pred.is_ungrouped() {
+            pred.group(&run_context.groups);
+        }
+        let groups: Vec<Vec<DBCol>> = results[0].cols.keys().cloned().collect();
+        for group in groups {
+            // A condition on the rows of a group can only refer to the group's keys and aggregates,
+            // which are the same for every row, so the first row decides.
+            let holds = match pred.cols.get(&group) {
+                Some(DBCol::Bool(vals)) => vals[0] && !pred.is_null(&group, 0),
+                Some(col) => panic!(
+                    "HAVING ({}) must be a boolean, not {:?}",
+                    self.pred,
+                    col.data_type()
+                ),
+                None => panic!("HAVING ({}) was not evaluated for every group", self.pred),
+            };
+            if !holds {
+                for result in results.iter_mut() {
+                    result.cols.remove(&group);
+                    result.nulls.remove(&group);
+                }
             }
-            debug!("what is index: {:?}", col.len());
+        }
+        results
+    }
+}
 
-            // XXX We are using this to see if this is a value-based result. It might not be that
-            // only value-based results have no ref_table.
-            if result.ref_table.is_none() {
-                if groups.len() > col.len() {
-                    col.repeat(groups.len());
-                } else {
-                    continue;
+// A key of an ORDER BY. Keys naming a projection, by alias or ordinal, reuse its result instead of
+// being evaluated again.
+#[derive(Clone)]
+pub enum OrderByKey {
+    Projection(usize),
+    Item(SelectItem),
+}
+
+#[derive(Clone)]
+pub struct OrderByItem {
+    key: OrderByKey,
+    asc: bool,
+    nulls_first: bool,
+}
+
+impl fmt::Display for OrderByItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            // The ordinal within the printed projection
+            OrderByKey::Projection(idx) => write!(f, "{}", idx + 1)?,
+            OrderByKey::Item(item) => write!(f, "{}", item)?,
+        }
+        write!(
+            f,
+            " {} NULLS {}",
+            if self.asc { "ASC" } else { "DESC" },
+            if self.nulls_first { "FIRST" } else { "LAST" }
+        )
+    }
+}
+
+// ORDER BY along with LIMIT/OFFSET, applied to the results of the projection.
+#[derive(Clone, Default)]
+pub struct OrderByNode {
+    items: Vec<OrderByItem>,
+    limit: Option<usize>,
+    offset: usize,
+}
+
+impl fmt::Display for OrderByNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.items.is_empty() {
+            let items: Vec<String> = self.items.iter().map(|i| i.to_string()).collect();
+            write!(f, " ORDER BY {}", items.join(", "))?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        if self.offset > 0 {
+            write!(f, " OFFSET {}", self.offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl OrderByNode {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.limit.is_none() && self.offset == 0
+    }
+
+    // Sorts the rows of `results` and cuts them down to the LIMIT/OFFSET. Since the groups of a
+    // result have no order among themselves, the rows of all groups are put into a single
+    // ungrouped col in their sorted order.
+    pub fn eval(
+        &self,
+        results: Vec<DBResult>,
+        run_context: &RunContext,
+        exec_stats: &mut ExecStats,
+    ) -> Vec<DBResult> {
+        if self.is_empty() || results[0].cols.is_empty() {
+            return results;
+        }
+
+        let (groups, lens) = group_lens(&results);
+        let keys: Vec<(DBCol, RoaringBitmap)> = self
+            .items
+            .iter()
+            .map(|item| match &item.key {
+                OrderByKey::Projection(idx) => flatten_groups(&results[*idx], &groups, &lens),
+                OrderByKey::Item(key) => {
+                    flatten_groups(&key.eval(run_context, exec_stats), &groups, &lens)
+                }
+            })
+            .collect();
+
+        let mut order: Vec<usize> = (0..lens.iter().sum()).collect();
+        order.sort_by(|&a, &b| {
+            for (item, (col, nulls)) in self.items.iter().zip(&keys) {
+                let ord = match (nulls.contains(a as u32), nulls.contains(b as u32)) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) if item.nulls_first => Ordering::Less,
+                    (true, false) => Ordering::Greater,
+                    (false, true) if item.nulls_first => Ordering::Greater,
+                    (false, true) => Ordering::Less,
+                    (false, false) if item.asc => join::order_at((col, a), (col, b)),
+                    (false, false) => join::order_at((col, a), (col, b)).reverse(),
+                };
+                if ord != Ordering::Equal {
+                    return ord;
                 }
+            }
+            Ordering::Equal
+        });
+        let order: Vec<usize> = order
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        gather_rows(&results, &groups, &lens, &order)
+    }
+}
+
+// The groups of `results`, along with the number of rows of each. Under GROUP BY, each group is a
+// single row, even though its non-aggregates keep a value for each row of the group.
+fn group_lens(results: &[DBResult]) -> (Vec<Vec<DBCol>>, Vec<usize>) {
+    let groups: Vec<Vec<DBCol>> = results[0].groups().into_iter().cloned().collect();
+    let lens = groups
+        .iter()
+        .map(|group| {
+            let len = results
+                .iter()
+                .map(|result| result.cols.get(group).map_or(0, |col| col.len()))
+                .max()
+                .unwrap();
+            if group.is_empty() {
+                len
             } else {
-                groups.resize_with(col.len(), Default::default);
+                len.min(1)
             }
-            for i in 0..col.len() {
-                match col {
-                    DBCol::Int(vals) => {
-                        groups[i].push(DBCol::Int(vec![vals[i]]));
-                    }
-                    DBCol::Long(vals) => {
-                        groups[i].push(DBCol::Long(vec![vals[i]]));
-                    }
-                    DBCol::Str(vals) => {
-                        groups[i].push(DBCol::Str(vec![vals[i].clone()]));
-                    }
-                    DBCol::Bool(vals) => {
-                        groups[i].push(DBCol::Bool(vec![vals[i]]));
-                    }
-                    _ => {
-                        panic!("Don't support group by type");
-                    }
-                }
+        })
+        .collect();
+    (groups, lens)
+}
+
+// Puts the rows at `positions` of the concatenated `groups` of each result into an ungrouped result.
+fn gather_rows(
+    results: &[DBResult],
+    groups: &[Vec<DBCol>],
+    lens: &[usize],
+    positions: &[usize],
+) -> Vec<DBResult> {
+    results
+        .iter()
+        .map(|result| {
+            let (col, nulls) = flatten_groups(result, groups, lens);
+            let nulls: RoaringBitmap = positions
+                .iter()
+                .enumerate()
+                .filter(|(_, pos)| nulls.contains(**pos as u32))
+                .map(|(i, _)| i as u32)
+                .collect();
+            DBResult {
+                cols: vec![(vec![], col.gather(positions))].into_iter().collect(),
+                nulls: vec![(vec![], nulls)].into_iter().collect(),
+                ref_table: result.ref_table.clone(),
+            }
+        })
+        .collect()
+}
+
+// Concatenates the cols of the `groups` of `result`, in that order, and returns the positions of
+// the NULLs within the concatenated col. Each group has `lens` rows; a col with a single value for
+// a group, e.g., an aggregate, is repeated for each row of the group, and only the first `lens`
+// values of a longer col are kept.
+fn flatten_groups(
+    result: &DBResult,
+    groups: &[Vec<DBCol>],
+    lens: &[usize],
+) -> (DBCol, RoaringBitmap) {
+    let mut flat: Option<DBCol> = None;
+    let mut nulls = RoaringBitmap::new();
+    let mut offset = 0;
+    for (group, len) in groups.iter().zip(lens) {
+        let mut col = result
+            .cols
+            .get(group)
+            .expect("Col didn't have group")
+            .clone();
+        let is_null = |i: u32| result.is_null(group, i as usize);
+        if col.len() == 1 && *len != 1 {
+            col.repeat(*len);
+            if is_null(0) {
+                nulls.insert_range(offset..offset + *len as u32);
+            }
+        } else if col.len() > *len {
+            col = col.gather(&(0..*len).collect::<Vec<_>>());
+            nulls.extend((0..*len as u32).filter(|i| is_null(*i)).map(|i| i + offset));
+        } else {
+            assert_eq!(
+                *len,
+                col.len(),
+                "Cols of group {:?} differ in length",
+                group
+            );
+            if let Some(mask) = result.null_mask(group) {
+                nulls.extend(mask.iter().map(|i| i + offset));
             }
         }
-        groups
+        offset += *len as u32;
+        match flat.as_mut() {
+            Some(flat) => flat.append(col),
+            None => flat = Some(col),
+        }
     }
+    (flat.unwrap(), nulls)
 }
 
 // This is just another pointer to the `ref_table` in `ParseContext`
@@ -1161,15 +1584,43 @@ pub struct Query<'a> {
     pub projection: SelectNode,
     pub filter: Option<PredNode>,
     pub group_by: GroupByNode,
+    pub having: Option<HavingNode>,
+    pub order_by: OrderByNode,
     pub table: TableNode,
     pub context: ParseContext<'a>,
 }
 
+impl Query<'_> {
+    // The number of rows passing the filter which are enough to answer the query, if it has a
+    // LIMIT and only needs the first rows, i.e., it does not sort, group or aggregate them.
+    pub fn row_limit(&self) -> Option<usize> {
+        let order_by = &self.order_by;
+        if !order_by.items.is_empty()
+            || !self.group_by.items.is_empty()
+            || self.having.is_some()
+            || self.projection.distinct
+            || self
+                .projection
+                .items
+                .iter()
+                .any(|item| item.has_aggregate())
+        {
+            return None;
+        }
+        order_by.limit.map(|limit| order_by.offset + limit)
+    }
+}
+
 impl fmt::Display for Query<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "SELECT {} FROM {} {}{};",
+            "SELECT {}{} FROM {} {}{}{}{};",
+            if self.projection.distinct {
+                "DISTINCT "
+            } else {
+                ""
+            },
             self.projection
                 .items
                 .iter()
@@ -1193,7 +1644,11 @@ impl fmt::Display for Query<'_> {
                         .collect::<Vec<String>>()
                         .join(", ")
                 )
-            }
+            },
+            self.having
+                .as_ref()
+                .map_or("".to_string(), |having| having.to_string()),
+            self.order_by
         )
     }
 }
@@ -1213,6 +1668,28 @@ pub struct ParseContext<'a> {
 pub enum ParseError {
     RawParserError(RawParserError),
     JoinConstraintError(ExprCreateError),
+    // An atom of the WHERE clause which cannot be evaluated
+    FilterError(ExprCreateError),
+    // An item of the select list which cannot be evaluated
+    ProjectionError(ExprCreateError),
+    // A join constraint between columns whose types cannot be compared, e.g., a string and an int.
+    JoinKeyTypeError(String, Vec<DataType>),
+    // A comma-separated FROM item which nothing in the WHERE clause joins to the items before it,
+    // since cross joins are not supported.
+    CrossJoin(String),
+    // A table referenced more than once in a FROM clause without an alias to tell them apart
+    DuplicateTableName(String),
+    // A HAVING condition which cannot be evaluated
+    HavingError(ExprCreateError),
+    // An ORDER BY key which cannot be evaluated, e.g., a position past the end of the select list
+    OrderByError(String),
+    // A LIMIT, OFFSET or FETCH clause which is not supported, e.g., a LIMIT which is not a number
+    LimitError(String),
+    // A table of the FROM clause which is not in the database
+    TableDoesNotExist(String),
+    // An item of the FROM clause which is not a plain table name, e.g., a subquery or a
+    // schema-qualified name
+    UnsupportedRelation(String),
 }
 
 pub struct Parser<'a> {
@@ -1227,16 +1704,85 @@ pub enum JoinTableType {
 
 pub struct ParseParams {
     pub join_table_type: JoinTableType,
+    // Order multi-way inner joins by estimated cost rather than as written in the FROM clause. This
+    // is off by default, since estimating the cost counts the distinct keys of the join columns.
+    pub reorder_joins: bool,
+    // Selectivities of predicate atoms, keyed by the atom, used to order joins
+    pub selectivities: HashMap<String, f64>,
 }
 
 impl Default for ParseParams {
     fn default() -> Self {
         Self {
             join_table_type: JoinTableType::Normal,
+            reorder_joins: false,
+            selectivities: HashMap::new(),
         }
     }
 }
 
+// The constraint of one join of the left-deep join tree.
+enum StepConstraint<'q> {
+    // The constraint as written for the join in the query
+    Written(&'q ast::JoinConstraint),
+    // Conjuncts of ON constraints, gathered from wherever they were written
+    Conjuncts(Vec<&'q ast::Expr>),
+}
+
+// Resolves columns against all the tables of a FROM clause at once, to find which of them an
+// expression refers to. The join of the tables is only used for name resolution and is never
+// evaluated.
+struct Relations<'a, 't> {
+    tables: &'t [Rc<dyn Table>],
+    context: ParseContext<'a>,
+}
+
+impl<'a, 't> Relations<'a, 't> {
+    fn new(
+        tables: &'t [Rc<dyn Table>],
+        aliases: HashMap<String, Rc<dyn Table>>,
+        db: &'a DB,
+    ) -> Self {
+        let mut all_tables = tables[0].clone();
+        for table in &tables[1..] {
+            all_tables = JoinTable::new(JoinType::Inner, vec![], &all_tables, table);
+        }
+        Relations {
+            tables,
+            context: ParseContext {
+                ref_table: all_tables,
+                other_tables: vec![],
+                db,
+                aliases,
+                file_tables: db.file_tables(),
+            },
+        }
+    }
+
+    // The index of the table `col` belongs to. Returns None if the table appears more than once.
+    fn relation_of(&self, col: &FileCol) -> Option<usize> {
+        let table = col.table.upgrade().unwrap();
+        let mut matches = (0..self.tables.len()).filter(|i| self.tables[*i].id() == table.id());
+        let relation = matches.next()?;
+        if matches.next().is_some() {
+            return None;
+        }
+        Some(relation)
+    }
+
+    // The indexes of the tables `expr` refers to, or None if any of them appears more than once.
+    fn refers_to(&self, expr: &Expr) -> Option<Vec<usize>> {
+        let mut relations = vec![];
+        for col in expr.get_all_cols() {
+            let relation = self.relation_of(&col)?;
+            if !relations.contains(&relation) {
+                relations.push(relation);
+            }
+        }
+        Some(relations)
+    }
+}
+
 impl<'a> Parser<'a> {
     pub fn new(db: &'a DB) -> Parser<'a> {
         Parser {
@@ -1246,8 +1792,10 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse(&self, sql: &str, params: &ParseParams) -> Result<Query<'a>, ParseError> {
-        let parsed = &RawParser::parse_sql(&self.dialect, sql.to_string())
-            .map_err(|err| ParseError::RawParserError(err))?[0];
+        let (statements, nulls_first) =
+            parse_raw_sql(&self.dialect, sql).map_err(|err| ParseError::RawParserError(err))?;
+        let parsed = &statements[0];
+        let raw_query;
         let query;
         if let ast::Statement::Query(parsed) = parsed {
             if let ast::Query {
@@ -1255,6 +1803,7 @@ impl<'a> Parser<'a> {
                 ..
             } = parsed.as_ref()
             {
+                raw_query = parsed.as_ref();
                 query = select.as_ref()
             } else {
                 panic!("Did not match expected select pattern");
@@ -1263,9 +1812,10 @@ impl<'a> Parser<'a> {
             panic!("Did not match expected select pattern");
         }
 
-        assert!(query.from.len() == 1, "query has more than one FROM table");
+        let (from, selection) =
+            self.promote_implicit_joins(&query.from, query.selection.as_ref())?;
         let context = if let JoinTableType::Normal = params.join_table_type {
-            self.parse_table(&query.from[0])?
+            self.parse_table(&from, selection.as_ref(), params)?
         } else {
             panic!("no other type");
         };
@@ -1273,19 +1823,40 @@ impl<'a> Parser<'a> {
             table: context.ref_table.clone(),
             join_table_type: params.join_table_type.clone(),
         };
-        let filter = query
-            .selection
+        let filter = match selection
             .as_ref()
-            .and_then(|s| Some(self.parse_predicate(&s, &context)))
-            .and_then(|n| n.and_then(|n| Ok(self.flatten_predicate(n))).ok());
-        let (projection, valid_projs) = self.parse_projection(&query.projection, &context);
+            .map(|s| self.parse_predicate(s, &context))
+            .transpose()
+        {
+            Ok(filter) => filter.map(|n| self.flatten_predicate(n)),
+            Err(err) if err.is_unsupported() => return Err(ParseError::FilterError(err)),
+            Err(_) => None,
+        };
+        let (projection, valid_projs) =
+            self.parse_projection(&query.projection, query.distinct, &context)?;
+        let order_by = self.parse_order_by(
+            raw_query,
+            &nulls_first,
+            &query.projection,
+            &context,
+            &projection,
+            &valid_projs,
+        )?;
         let group_by = self.parse_group_by(&query.group_by, &context, &projection, valid_projs);
+        let having = query
+            .having
+            .as_ref()
+            .map(|having| SelectItem::new(having, &context).map(|pred| HavingNode { pred }))
+            .transpose()
+            .map_err(ParseError::HavingError)?;
 
         Ok(Query {
             context,
             filter,
             projection,
             group_by,
+            having,
+            order_by,
             table,
         })
     }
@@ -1293,32 +1864,33 @@ impl<'a> Parser<'a> {
     fn parse_projection(
         &self,
         items: &Vec<ast::SelectItem>,
+        distinct: bool,
         context: &ParseContext,
-    ) -> (SelectNode, Vec<usize>) {
+    ) -> Result<(SelectNode, Vec<usize>), ParseError> {
         debug!("*** PARSING PROJECTION ***");
         let mut valid_projections = Vec::new();
+        let mut sel_items = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            let ret = match item {
+                ast::SelectItem::UnnamedExpr(expr) => SelectItem::new(expr, context),
+                ast::SelectItem::ExprWithAlias { expr, .. } => SelectItem::new(expr, context),
+                ast::SelectItem::QualifiedWildcard(ast::ObjectName(idents)) => {
+                    SelectItem::new(&ast::Expr::QualifiedWildcard(idents.to_vec()), context)
+                }
+                ast::SelectItem::Wildcard => SelectItem::new(&ast::Expr::Wildcard, context),
+            };
+            match ret {
+                Ok(item) => {
+                    valid_projections.push(i);
+                    sel_items.push(item);
+                }
+                Err(err) if err.is_unsupported() => return Err(ParseError::ProjectionError(err)),
+                Err(_) => {}
+            }
+        }
         let sel = SelectNode {
-            items: items
-                .iter()
-                .enumerate()
-                .filter_map(|(i, item)| {
-                    let ret = match item {
-                        ast::SelectItem::UnnamedExpr(expr) => SelectItem::new(expr, context),
-                        ast::SelectItem::ExprWithAlias { expr, .. } => {
-                            SelectItem::new(expr, context)
-                        }
-                        ast::SelectItem::QualifiedWildcard(ast::ObjectName(idents)) => {
-                            SelectItem::new(&ast::Expr::QualifiedWildcard(idents.to_vec()), context)
-                        }
-                        ast::SelectItem::Wildcard => SelectItem::new(&ast::Expr::Wildcard, context),
-                    }
-                    .ok();
-                    if ret.is_some() {
-                        valid_projections.push(i);
-                    }
-                    ret
-                })
-                .collect(),
+            distinct,
+            items: sel_items,
         };
         debug!(
             "[Projection] Items:\n{}",
@@ -1328,7 +1900,108 @@ impl<'a> Parser<'a> {
                 .collect::<Vec<String>>()
                 .join(",\n")
         );
-        (sel, valid_projections)
+        Ok((sel, valid_projections))
+    }
+
+    // Keys naming a projection, by its alias or ordinal, or which are the same expression, refer to
+    // the projection's result. Without NULLS FIRST/LAST, NULLs sort as if they were larger than any
+    // value, as in Postgres.
+    fn parse_order_by(
+        &self,
+        query: &ast::Query,
+        nulls_first: &[Option<bool>],
+        items: &[ast::SelectItem],
+        context: &ParseContext,
+        projection: &SelectNode,
+        valid_projs: &[usize],
+    ) -> Result<OrderByNode, ParseError> {
+        debug!("*** PARSING ORDER BY ***");
+        if query.fetch.is_some() {
+            return Err(ParseError::LimitError(
+                "FETCH is not supported, use LIMIT instead".to_string(),
+            ));
+        }
+        let parse_count = |expr: &Option<ast::Expr>, clause: &str| match expr {
+            None => Ok(None),
+            Some(ast::Expr::Value(ast::Value::Long(num))) => Ok(Some(*num as usize)),
+            Some(expr) => Err(ParseError::LimitError(format!(
+                "{} {} is not supported",
+                clause, expr
+            ))),
+        };
+        let order_by_error = |msg: String| Err(ParseError::OrderByError(msg));
+
+        let mut order_by_items = vec![];
+        for (i, item) in query.order_by.iter().enumerate() {
+            let proj = match &item.expr {
+                ast::Expr::Value(ast::Value::Long(num)) => {
+                    if *num < 1 || *num as usize > items.len() {
+                        return order_by_error(format!(
+                            "ORDER BY position {} is not in select list",
+                            num
+                        ));
+                    }
+                    Some(*num as usize - 1)
+                }
+                ast::Expr::Identifier(ident) => items.iter().position(|proj| {
+                    matches!(proj, ast::SelectItem::ExprWithAlias { alias, .. } if alias == ident)
+                }),
+                _ => None,
+            };
+            let key = match proj {
+                Some(num) => match valid_projs.binary_search(&num) {
+                    Ok(idx) => OrderByKey::Projection(idx),
+                    Err(_) => {
+                        return order_by_error(format!(
+                            "ORDER BY {} refers to a select item which cannot be evaluated",
+                            item.expr
+                        ));
+                    }
+                },
+                None => {
+                    let key = match SelectItem::new(&item.expr, context) {
+                        Ok(key) => key,
+                        Err(err) => {
+                            return order_by_error(format!(
+                                "Could not parse ORDER BY {}: {:?}",
+                                item.expr, err
+                            ));
+                        }
+                    };
+                    let key_str = key.to_string();
+                    match projection
+                        .items
+                        .iter()
+                        .position(|i| i.to_string() == key_str)
+                    {
+                        Some(idx) => OrderByKey::Projection(idx),
+                        // The rows of a SELECT DISTINCT no longer line up with the rows the key
+                        // would be evaluated on.
+                        None if projection.distinct => {
+                            return order_by_error(format!(
+                                "For SELECT DISTINCT, ORDER BY expressions must appear in select list ({})",
+                                item.expr
+                            ));
+                        }
+                        None => OrderByKey::Item(key),
+                    }
+                }
+            };
+            let asc = item.asc.unwrap_or(true);
+            order_by_items.push(OrderByItem {
+                key,
+                asc,
+                nulls_first: nulls_first.get(i).cloned().flatten().unwrap_or(!asc),
+            });
+        }
+
+        let node = OrderByNode {
+            items: order_by_items,
+            limit: parse_count(&query.limit, "LIMIT")?,
+            offset: parse_count(&query.offset, "OFFSET")?.unwrap_or(0),
+        };
+        debug!("[OrderBy]{}", node);
+        Ok(node)
     }
 
     // FIXME We skip over all the groups identified by aliases
@@ -1412,16 +2085,14 @@ impl<'a> Parser<'a> {
                 op: ast::BinaryOperator::And,
                 right,
             } => {
-                let left = self.parse_predicate(left, context);
-                let right = self.parse_predicate(right, context);
-                if left.is_ok() && right.is_ok() {
-                    Ok(PredNode::AndNode(vec![left.unwrap(), right.unwrap()]))
-                } else if left.is_ok() {
-                    left
-                } else if right.is_ok() {
-                    right
-                } else {
-                    Err(ExprCreateError::ColDoesNotExist)
+                match (
+                    self.parse_predicate(left, context),
+                    self.parse_predicate(right, context),
+                ) {
+                    (Err(err), _) | (_, Err(err)) if err.is_unsupported() => Err(err),
+                    (Ok(left), Ok(right)) => Ok(PredNode::AndNode(vec![left, right])),
+                    (Ok(node), Err(_)) | (Err(_), Ok(node)) => Ok(node),
+                    (Err(_), Err(_)) => Err(ExprCreateError::ColDoesNotExist),
                 }
             }
             ast::Expr::BinaryOp {
@@ -1429,16 +2100,14 @@ impl<'a> Parser<'a> {
                 op: ast::BinaryOperator::Or,
                 right,
             } => {
-                let left = self.parse_predicate(left, context);
-                let right = self.parse_predicate(right, context);
-                if left.is_ok() && right.is_ok() {
-                    Ok(PredNode::OrNode(vec![left.unwrap(), right.unwrap()]))
-                } else if left.is_ok() {
-                    left
-                } else if right.is_ok() {
-                    right
-                } else {
-                    Err(ExprCreateError::ColDoesNotExist)
+                match (
+                    self.parse_predicate(left, context),
+                    self.parse_predicate(right, context),
+                ) {
+                    (Err(err), _) | (_, Err(err)) if err.is_unsupported() => Err(err),
+                    (Ok(left), Ok(right)) => Ok(PredNode::OrNode(vec![left, right])),
+                    (Ok(node), Err(_)) | (Err(_), Ok(node)) => Ok(node),
+                    (Err(_), Err(_)) => Err(ExprCreateError::ColDoesNotExist),
                 }
             }
             ast::Expr::Nested(expr) => self.parse_predicate(expr, context),
@@ -1451,102 +2120,377 @@ impl<'a> Parser<'a> {
         }
     }
 
-    // Parses the FROM table of the query and returns a ParseContext where the ref_table part is
-    // the overall joined table in the query.
-    fn parse_table(&self, table: &ast::TableWithJoins) -> Result<ParseContext<'a>, ParseError> {
-        // This submethod also adds to aliases if an alias exists.
-        fn unwrap_table<'a>(
-            table: &ast::TableFactor,
-            db: &'a DB,
-            aliases: &mut HashMap<String, Rc<dyn Table>>,
-        ) -> &'a Rc<dyn Table> {
-            if let ast::TableFactor::Table { name, alias, .. } = table {
-                assert!(name.0.len() == 1);
-                let name = &name.0[0];
-                let result = db.file_tables().get(name).unwrap();
-                if alias.is_some() {
-                    let alias = &alias.as_ref().unwrap().name;
-                    aliases.insert(alias.to_string(), result.clone());
+    // Splits a join constraint into its ANDed conjuncts, e.g., `a.x = b.x AND a.y = b.y` into
+    // `a.x = b.x` and `a.y = b.y`.
+    fn parse_join_constraint(
+        &self,
+        expr: &ast::Expr,
+        context: &ParseContext,
+    ) -> Result<Vec<Expr>, ExprCreateError> {
+        split_conjuncts(expr)
+            .into_iter()
+            .map(|conjunct| Expr::new(conjunct, context))
+            .collect()
+    }
+
+    // Parses the subquery of `outer_key IN (subquery)`, or of `EXISTS (subquery)` if there is no
+    // `outer_key`, into a semi-join with the query parsed under `context`. Besides the IN key, the
+    // subquery is joined to the outer query on the equalities in its WHERE clause between its own
+    // columns and those of the outer query. The rest of its WHERE clause filters the subquery.
+    pub fn parse_semi_join(
+        &self,
+        outer_key: Option<Expr>,
+        subquery: &ast::Query,
+        negated: bool,
+        context: &ParseContext,
+    ) -> Result<SemiJoin, ExprCreateError> {
+        let unsupported = || ExprCreateError::UnsupportedSubquery(subquery.to_string());
+        let select = match &subquery.body {
+            ast::SetExpr::Select(select) => select.as_ref(),
+            _ => return Err(unsupported()),
+        };
+        // We do not handle aggregates in subqueries
+        if !select.group_by.is_empty() || select.having.is_some() {
+            return Err(unsupported());
+        }
+
+        let subquery_error = |err| ExprCreateError::SubqueryError(Box::new(err));
+        let (from, selection) = self
+            .promote_implicit_joins(&select.from, select.selection.as_ref())
+            .map_err(subquery_error)?;
+        let inner = self
+            .parse_table(&from, selection.as_ref(), &ParseParams::default())
+            .map_err(subquery_error)?;
+        let mut correlated_context = inner.clone();
+        correlated_context.other_tables = vec![context.ref_table.clone()];
+        correlated_context
+            .other_tables
+            .extend(context.other_tables.iter().cloned());
+
+        let mut keys = vec![];
+        let is_in = outer_key.is_some();
+        if let Some(outer_key) = outer_key {
+            let inner_key = match select.projection.as_slice() {
+                [ast::SelectItem::UnnamedExpr(expr)]
+                | [ast::SelectItem::ExprWithAlias { expr, .. }] => Expr::new(expr, &inner)?,
+                // An IN subquery must select exactly one column
+                _ => return Err(unsupported()),
+            };
+            keys.push((outer_key, inner_key));
+        }
+        let mut conjuncts = vec![];
+        for conjunct in selection.as_ref().map_or(vec![], split_conjuncts) {
+            match Expr::new(conjunct, &inner) {
+                Ok(_) => conjuncts.push(conjunct.clone()),
+                Err(err) if err.is_unsupported() => return Err(err),
+                Err(_) => {
+                    // We only support equalities between a subquery and its outer query
+                    let expr = Expr::new(conjunct, &correlated_context)?;
+                    keys.push(correlated_keys(&expr, &inner.ref_table).ok_or_else(unsupported)?);
+                }
+            }
+        }
+        let filter = join_conjuncts(conjuncts)
+            .map(|selection| self.parse_predicate(&selection, &inner))
+            .transpose()?
+            .map(|node| self.flatten_predicate(node));
+
+        Ok(SemiJoin::new(
+            inner.ref_table,
+            filter,
+            keys,
+            is_in,
+            negated,
+            subquery.to_string(),
+        ))
+    }
+
+    // Turns a FROM clause of comma-separated items into a single item, joining each later item on
+    // the equalities in the WHERE clause between its first table and the tables before it, e.g.,
+    // `from a, b where a.x = b.y and a.z > 1` into `from a join b on a.x = b.y where a.z > 1`.
+    // Returns the item along with the rest of the WHERE clause. Equalities which cannot be used to
+    // join, e.g., those between two tables of the same item, are left in the WHERE clause.
+    fn promote_implicit_joins(
+        &self,
+        from: &[ast::TableWithJoins],
+        selection: Option<&ast::Expr>,
+    ) -> Result<(ast::TableWithJoins, Option<ast::Expr>), ParseError> {
+        if from.len() == 1 {
+            return Ok((from[0].clone(), selection.cloned()));
+        }
+
+        let mut aliases = HashMap::new();
+        let mut tables = vec![];
+        let mut firsts = vec![];
+        for item in from {
+            firsts.push(tables.len());
+            tables.push(unwrap_table(&item.relation, self.db, &mut aliases)?);
+            for join in &item.joins {
+                tables.push(unwrap_table(&join.relation, self.db, &mut aliases)?);
+            }
+        }
+        check_distinct_names(&tables)?;
+        let relations = Relations::new(&tables, aliases, self.db);
+
+        // The pair of tables each conjunct of the WHERE clause joins on, if it is an equality
+        // between two tables.
+        let conjuncts = selection.map_or(vec![], split_conjuncts);
+        let join_keys = conjuncts
+            .iter()
+            .map(|conjunct| match conjunct {
+                ast::Expr::BinaryOp {
+                    op: ast::BinaryOperator::Eq,
+                    ..
+                } => Expr::new(conjunct, &relations.context)
+                    .ok()
+                    .and_then(|expr| relations.refers_to(&expr))
+                    .filter(|relations| relations.len() == 2),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut joined = vec![false; tables.len()];
+        let mut promoted = vec![false; conjuncts.len()];
+        // The conjuncts not yet promoted which join `table` to the tables joined so far
+        let joining = |table: usize, joined: &[bool], promoted: &[bool]| {
+            (0..conjuncts.len())
+                .filter(|i| {
+                    !promoted[*i]
+                        && join_keys[*i].as_ref().is_some_and(|relations| {
+                            relations.contains(&table)
+                                && relations
+                                    .iter()
+                                    .all(|relation| *relation == table || joined[*relation])
+                        })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut item = from[0].clone();
+        let mut remaining = (1..from.len()).collect::<Vec<_>>();
+        (0..firsts[1]).for_each(|table| joined[table] = true);
+        while !remaining.is_empty() {
+            let next = remaining
+                .iter()
+                .position(|i| !joining(firsts[*i], &joined, &promoted).is_empty())
+                .ok_or_else(|| ParseError::CrossJoin(from[remaining[0]].to_string()))?;
+            let i = remaining.remove(next);
+            let on = joining(firsts[i], &joined, &promoted);
+            for conjunct in &on {
+                promoted[*conjunct] = true;
+            }
+            item.joins.push(ast::Join {
+                relation: from[i].relation.clone(),
+                join_operator: ast::JoinOperator::Inner(ast::JoinConstraint::On(
+                    join_conjuncts(on.into_iter().map(|j| conjuncts[j].clone()).collect()).unwrap(),
+                )),
+            });
+            item.joins.extend(from[i].joins.iter().cloned());
+            let end = firsts.get(i + 1).cloned().unwrap_or(tables.len());
+            (firsts[i]..end).for_each(|table| joined[table] = true);
+        }
+
+        let selection = join_conjuncts(
+            (0..conjuncts.len())
+                .filter(|i| !promoted[*i])
+                .map(|i| conjuncts[i].clone())
+                .collect(),
+        );
+        Ok((item, selection))
+    }
+
+    // Picks the order of a multi-way inner join with `join_order`, where `tables` are the tables
+    // of `table` in the order they are written. Returns the index of the first table and, for each
+    // table joined to it, the ON conjuncts to join it on. Returns None if the joins should be built
+    // as written, which is always the case for outer joins and USING constraints.
+    fn reorder_joins<'q>(
+        &self,
+        table: &'q ast::TableWithJoins,
+        tables: &[Rc<dyn Table>],
+        selection: Option<&ast::Expr>,
+        context: &ParseContext,
+        params: &ParseParams,
+    ) -> Option<join_order::JoinOrder<&'q ast::Expr>> {
+        if !params.reorder_joins || table.joins.len() < 2 {
+            return None;
+        }
+        let mut conjuncts = vec![];
+        for join in &table.joins {
+            match &join.join_operator {
+                ast::JoinOperator::Inner(ast::JoinConstraint::On(constraint)) => {
+                    conjuncts.append(&mut split_conjuncts(constraint));
+                }
+                _ => return None,
+            }
+        }
+
+        let relations = Relations::new(tables, context.aliases.clone(), self.db);
+        let mut rows = tables
+            .iter()
+            .map(|table| table.len() as f64)
+            .collect::<Vec<_>>();
+        for atom in selection.map_or(vec![], split_conjuncts) {
+            if let Ok(expr) = Expr::new(atom, &relations.context) {
+                if let Some([relation]) = relations.refers_to(&expr).as_deref() {
+                    rows[*relation] *= params
+                        .selectivities
+                        .get(&expr.to_string())
+                        .cloned()
+                        .unwrap_or(DEFAULT_SELECTIVITY);
                 }
-                result
-            } else {
-                panic!("Relation is not of form table: {:?}", table);
             }
         }
 
+        let mut preds = vec![];
+        for conjunct in &conjuncts {
+            let expr = Expr::new(conjunct, &relations.context).ok()?;
+            let pred_relations = relations.refers_to(&expr)?;
+            if pred_relations.is_empty() {
+                return None;
+            }
+            let keys = match &expr {
+                Expr::BinaryOp {
+                    left,
+                    right,
+                    op: BinaryOperator::Eq,
+                } => match (left.as_ref(), right.as_ref(), pred_relations.as_slice()) {
+                    (Expr::ColRef { col: left, .. }, Expr::ColRef { col: right, .. }, [_, _]) => {
+                        let left_relation = relations.relation_of(left)?;
+                        let right_relation = relations.relation_of(right)?;
+                        Some([
+                            (
+                                left_relation,
+                                join_order::distinct_count(left, &tables[left_relation]),
+                            ),
+                            (
+                                right_relation,
+                                join_order::distinct_count(right, &tables[right_relation]),
+                            ),
+                        ])
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+            preds.push(join_order::JoinPred {
+                relations: pred_relations,
+                keys,
+            });
+        }
+
+        let (first, steps) = join_order::order(&rows, &preds)?;
+        Some((
+            first,
+            steps
+                .into_iter()
+                .map(|(relation, step_preds)| {
+                    (
+                        relation,
+                        step_preds.into_iter().map(|pred| conjuncts[pred]).collect(),
+                    )
+                })
+                .collect(),
+        ))
+    }
+
+    // Parses the FROM table of the query and returns a ParseContext where the ref_table part is
+    // the overall joined table in the query.
+    fn parse_table(
+        &self,
+        table: &ast::TableWithJoins,
+        selection: Option<&ast::Expr>,
+        params: &ParseParams,
+    ) -> Result<ParseContext<'a>, ParseError> {
         let mut aliases = HashMap::new();
-        let mut running = unwrap_table(&table.relation, self.db, &mut aliases).clone();
+        let mut tables = vec![unwrap_table(&table.relation, self.db, &mut aliases)?];
+        for join in &table.joins {
+            tables.push(unwrap_table(&join.relation, self.db, &mut aliases)?);
+        }
+        check_distinct_names(&tables)?;
         let mut context = ParseContext {
-            ref_table: running.clone(),
+            ref_table: tables[0].clone(),
             other_tables: vec![],
             db: self.db,
             aliases: aliases,
             file_tables: self.db.file_tables(),
         };
-        for join in &table.joins {
-            let to_join = unwrap_table(&join.relation, self.db, &mut context.aliases);
-            context.ref_table = running.clone();
-            context.other_tables = vec![to_join.clone()];
-            running = match &join.join_operator {
-                ast::JoinOperator::Inner(constraint) => {
-                    let constraint = match constraint {
-                        ast::JoinConstraint::On(constraint) => Expr::new(&constraint, &context)
-                            .map_err(|err| ParseError::JoinConstraintError(err))?,
-                        ast::JoinConstraint::Using(idents) => {
-                            assert_eq!(idents.len(), 1);
-                            let left =
-                                Expr::new(&ast::Expr::Identifier(idents[0].clone()), &context)
-                                    .map_err(|err| ParseError::JoinConstraintError(err))?;
-                            context.ref_table = to_join.clone();
-                            context.other_tables = vec![running.clone()];
-                            let right =
-                                Expr::new(&ast::Expr::Identifier(idents[0].clone()), &context)
-                                    .map_err(|err| ParseError::JoinConstraintError(err))?;
-                            Expr::BinaryOp {
-                                left: Box::new(left),
-                                right: Box::new(right),
-                                op: BinaryOperator::Eq,
-                            }
-                        }
-                        _ => {
-                            panic!("Unknown join constraint type: {:?}", constraint);
+
+        // Each join is given by the index of the table it joins, its type and its constraint.
+        let (first, steps) = match self.reorder_joins(table, &tables, selection, &context, params) {
+            Some((first, steps)) => (
+                first,
+                steps
+                    .into_iter()
+                    .map(|(i, conjuncts)| {
+                        (i, JoinType::Inner, StepConstraint::Conjuncts(conjuncts))
+                    })
+                    .collect(),
+            ),
+            None => {
+                let mut steps = vec![];
+                for (i, join) in table.joins.iter().enumerate() {
+                    let (join_type, constraint) = match &join.join_operator {
+                        ast::JoinOperator::Inner(constraint) => (JoinType::Inner, constraint),
+                        ast::JoinOperator::LeftOuter(constraint) => {
+                            (JoinType::LeftOuter, constraint)
                         }
-                    };
-                    JoinTable::new(JoinType::Inner, constraint, &running, &to_join)
-                }
-                ast::JoinOperator::LeftOuter(constraint) => {
-                    let constraint = match constraint {
-                        ast::JoinConstraint::On(constraint) => Expr::new(&constraint, &context)
-                            .map_err(|err| ParseError::JoinConstraintError(err))?,
-                        ast::JoinConstraint::Using(idents) => {
-                            assert_eq!(idents.len(), 1);
-                            let left =
-                                Expr::new(&ast::Expr::Identifier(idents[0].clone()), &context)
-                                    .map_err(|err| ParseError::JoinConstraintError(err))?;
-                            context.ref_table = to_join.clone();
-                            context.other_tables = vec![running.clone()];
-                            let right =
-                                Expr::new(&ast::Expr::Identifier(idents[0].clone()), &context)
-                                    .map_err(|err| ParseError::JoinConstraintError(err))?;
-                            Expr::BinaryOp {
-                                left: Box::new(left),
-                                right: Box::new(right),
-                                op: BinaryOperator::Eq,
-                            }
+                        ast::JoinOperator::RightOuter(constraint) => {
+                            (JoinType::RightOuter, constraint)
                         }
                         _ => {
-                            panic!("Unknown join constraint type: {:?}", constraint);
+                            panic!(
+                                "We do not handle join type ({:?}) for now",
+                                join.join_operator
+                            );
                         }
                     };
-                    JoinTable::new(JoinType::LeftOuter, constraint, &running, &to_join)
+                    steps.push((i + 1, join_type, StepConstraint::Written(constraint)));
                 }
-                _ => {
-                    panic!(
-                        "We do not handle join type ({:?}) for now",
-                        join.join_operator
-                    );
+                (0, steps)
+            }
+        };
+
+        let mut running = tables[first].clone();
+        for (i, join_type, constraint) in steps {
+            let to_join = tables[i].clone();
+            context.ref_table = running.clone();
+            context.other_tables = vec![to_join.clone()];
+            let constraints = match constraint {
+                StepConstraint::Written(ast::JoinConstraint::On(constraint)) => self
+                    .parse_join_constraint(&constraint, &context)
+                    .map_err(|err| ParseError::JoinConstraintError(err))?,
+                StepConstraint::Written(ast::JoinConstraint::Using(idents)) => {
+                    let mut constraints = vec![];
+                    for ident in idents {
+                        context.ref_table = running.clone();
+                        context.other_tables = vec![to_join.clone()];
+                        let left = Expr::new(&ast::Expr::Identifier(ident.clone()), &context)
+                            .map_err(|err| ParseError::JoinConstraintError(err))?;
+                        context.ref_table = to_join.clone();
+                        context.other_tables = vec![running.clone()];
+                        let right = Expr::new(&ast::Expr::Identifier(ident.clone()), &context)
+                            .map_err(|err| ParseError::JoinConstraintError(err))?;
+                        constraints.push(Expr::BinaryOp {
+                            left: Box::new(left),
+                            right: Box::new(right),
+                            op: BinaryOperator::Eq,
+                        });
+                    }
+                    constraints
+                }
+                StepConstraint::Written(constraint) => {
+                    panic!("Unknown join constraint type: {:?}", constraint);
                 }
+                StepConstraint::Conjuncts(conjuncts) => conjuncts
+                    .into_iter()
+                    .map(|conjunct| Expr::new(conjunct, &context))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(ParseError::JoinConstraintError)?,
             };
+            for constraint in &constraints {
+                check_join_key_types(constraint)?;
+            }
+            running = JoinTable::new(join_type, constraints, &running, &to_join);
         }
         Ok(ParseContext {
             ref_table: running,
@@ -1557,3 +2501,235 @@ impl<'a> Parser<'a> {
         })
     }
 }
+
+// Looks up the table of a FROM item. An aliased table is a copy of the table under the alias, so
+// that a table can be referenced more than once, and the copy is also added to aliases.
+fn unwrap_table(
+    table: &ast::TableFactor,
+    db: &DB,
+    aliases: &mut HashMap<String, Rc<dyn Table>>,
+) -> Result<Rc<dyn Table>, ParseError> {
+    let unsupported = || ParseError::UnsupportedRelation(table.to_string());
+    match table {
+        ast::TableFactor::Table { name, alias, .. } => {
+            if name.0.len() != 1 {
+                return Err(unsupported());
+            }
+            let name = &name.0[0];
+            let mut result = db
+                .file_tables()
+                .get(name)
+                .ok_or_else(|| ParseError::TableDoesNotExist(name.to_string()))?
+                .clone();
+            if let Some(alias) = alias {
+                result = as_file_table(&result)
+                    .ok_or_else(unsupported)?
+                    .with_alias(&alias.name);
+                aliases.insert(alias.name.to_string(), result.clone());
+            }
+            Ok(result)
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+// Checks that no table of a FROM clause is referenced twice under the same name, since columns
+// could then not be told apart, e.g., `from A join A on ...` rather than `from A a1 join A a2 on ...`.
+fn check_distinct_names(tables: &[Rc<dyn Table>]) -> Result<(), ParseError> {
+    for (i, table) in tables.iter().enumerate() {
+        if tables[..i].iter().any(|other| other.name() == table.name()) {
+            return Err(ParseError::DuplicateTableName(table.name().to_string()));
+        }
+    }
+    Ok(())
+}
+
+// The ANDed conjuncts of `expr`, looking through parentheses.
+fn split_conjuncts(expr: &ast::Expr) -> Vec<&ast::Expr> {
+    match expr {
+        ast::Expr::BinaryOp {
+            left,
+            op: ast::BinaryOperator::And,
+            right,
+        } => {
+            let mut conjuncts = split_conjuncts(left);
+            conjuncts.append(&mut split_conjuncts(right));
+            conjuncts
+        }
+        ast::Expr::Nested(expr) => split_conjuncts(expr),
+        _ => vec![expr],
+    }
+}
+
+// Splits an equality between the columns of a subquery, whose tables are `inner`, and those of its
+// outer query into its (outer, inner) sides.
+fn correlated_keys(expr: &Expr, inner: &Rc<dyn Table>) -> Option<(Expr, Expr)> {
+    // Whether every column of `side` is of the subquery, or none is
+    let is_inner = |side: &Expr| {
+        let cols = side.get_all_cols();
+        let num_inner = cols
+            .iter()
+            .filter(|col| inner.contains_subtable(col.table.upgrade().unwrap().name()))
+            .count();
+        match num_inner {
+            _ if cols.is_empty() => None,
+            0 => Some(false),
+            _ if num_inner == cols.len() => Some(true),
+            _ => None,
+        }
+    };
+    match expr {
+        Expr::BinaryOp {
+            left,
+            right,
+            op: BinaryOperator::Eq,
+        } => match (is_inner(left)?, is_inner(right)?) {
+            (false, true) => Some((left.as_ref().clone(), right.as_ref().clone())),
+            (true, false) => Some((right.as_ref().clone(), left.as_ref().clone())),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// ANDs `conjuncts` back together. Returns None if there are none.
+fn join_conjuncts(conjuncts: Vec<ast::Expr>) -> Option<ast::Expr> {
+    conjuncts
+        .into_iter()
+        .reduce(|left, right| ast::Expr::BinaryOp {
+            left: Box::new(left),
+            op: ast::BinaryOperator::And,
+            right: Box::new(right),
+        })
+}
+
+// Checks that the sides compared by a join constraint have types which can be compared once
+// widened, so that mismatched keys are reported here rather than when joining. Sides whose type
+// depends on their values, e.g., function calls, are only checked when joining.
+fn check_join_key_types(constraint: &Expr) -> Result<(), ParseError> {
+    let sides = match constraint {
+        Expr::BinaryOp { left, right, .. } => vec![left, right],
+        Expr::Between {
+            expr, low, high, ..
+        } => vec![expr, low, high],
+        _ => vec![],
+    };
+    let data_types = sides
+        .into_iter()
+        .filter_map(|side| side.data_type())
+        .collect::<Vec<_>>();
+    let compatible = data_types.iter().all(|data_type| {
+        data_types
+            .iter()
+            .all(|other| join::key_type(*data_type, *other).is_some())
+    });
+    if compatible {
+        Ok(())
+    } else {
+        Err(ParseError::JoinKeyTypeError(
+            constraint.to_string(),
+            data_types,
+        ))
+    }
+}
+
+// Parses `sql` like the raw parser, except that the clauses after the body of a plain SELECT are
+// parsed here: the raw parser knows neither `NULLS FIRST`/`NULLS LAST` after an ORDER BY key nor an
+// OFFSET without `ROWS`. Returns the statements along with whether each ORDER BY key puts NULLs
+// first, if it says so. Any other statement is left to the raw parser.
+fn parse_raw_sql(
+    dialect: &PostgreSqlDialect,
+    sql: &str,
+) -> Result<(Vec<ast::Statement>, Vec<Option<bool>>), RawParserError> {
+    let mut parser = RawParser::new(Tokenizer::new(dialect, sql).tokenize()?);
+    if !parser.parse_keyword("SELECT") {
+        return Ok((RawParser::parse_sql(dialect, sql.to_string())?, vec![]));
+    }
+    let body = parser.parse_select()?;
+    if ["UNION", "EXCEPT", "INTERSECT"]
+        .iter()
+        .any(|op| is_raw_word(&parser, op))
+    {
+        return Ok((RawParser::parse_sql(dialect, sql.to_string())?, vec![]));
+    }
+
+    let mut order_by = vec![];
+    let mut nulls_first = vec![];
+    if parser.parse_keywords(vec!["ORDER", "BY"]) {
+        loop {
+            let expr = parser.parse_expr()?;
+            let asc = if parser.parse_keyword("ASC") {
+                Some(true)
+            } else if parser.parse_keyword("DESC") {
+                Some(false)
+            } else {
+                None
+            };
+            order_by.push(ast::OrderByExpr { expr, asc });
+            // NULLS and LAST are no keywords of the raw parser
+            nulls_first.push(if is_raw_word(&parser, "NULLS") {
+                parser.next_token();
+                if parser.parse_keyword("FIRST") {
+                    Some(true)
+                } else if is_raw_word(&parser, "LAST") {
+                    parser.next_token();
+                    Some(false)
+                } else {
+                    return Err(unexpected_token("FIRST or LAST", &parser));
+                }
+            } else {
+                None
+            });
+            if !parser.consume_token(&Token::Comma) {
+                break;
+            }
+        }
+    }
+    let limit = if parser.parse_keyword("LIMIT") {
+        parser.parse_limit()?
+    } else {
+        None
+    };
+    let offset = if parser.parse_keyword("OFFSET") {
+        let offset = parser.parse_literal_uint()?;
+        let _ = parser.parse_one_of_keywords(&["ROW", "ROWS"]);
+        Some(ast::Expr::Value(ast::Value::Long(offset)))
+    } else {
+        None
+    };
+    let fetch = if parser.parse_keyword("FETCH") {
+        Some(parser.parse_fetch()?)
+    } else {
+        None
+    };
+    while parser.consume_token(&Token::SemiColon) {}
+    if parser.peek_token().is_some() {
+        return Err(unexpected_token("end of statement", &parser));
+    }
+
+    let query = ast::Query {
+        ctes: vec![],
+        body: ast::SetExpr::Select(Box::new(body)),
+        order_by,
+        limit,
+        offset,
+        fetch,
+    };
+    Ok((vec![ast::Statement::Query(Box::new(query))], nulls_first))
+}
+
+// Whether the next token of `parser` is the unquoted word `word`, which need not be a keyword
+fn is_raw_word(parser: &RawParser, word: &str) -> bool {
+    match parser.peek_token() {
+        Some(Token::Word(w)) => w.quote_style.is_none() && w.value.eq_ignore_ascii_case(word),
+        _ => false,
+    }
+}
+
+// The error the raw parser gives when the next token of `parser` is not the `expected` one
+fn unexpected_token(expected: &str, parser: &RawParser) -> RawParserError {
+    let found = parser
+        .peek_token()
+        .map_or("EOF".to_string(), |token| token.to_string());
+    RawParserError::ParserError(format!("Expected {}, found: {}", expected, found))
+}
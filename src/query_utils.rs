@@ -46,7 +46,7 @@ pub fn estimate_selectivities(node: &PredNode, selectivities: &mut HashMap<Strin
             let result = node.expr.eval(
                 &RunContext {
                     index: None,
-                    groups: vec![],
+                    groups: Default::default(),
                     ref_table: Some(table.clone()),
                     exec_params: Default::default(),
                 },
@@ -75,6 +75,7 @@ pub enum DBVal {
     Bool(bool),
     DateTime(DateTime<Utc>),
     Duration(Duration),
+    Months(i32),
 }
 
 impl Eq for DBVal {}
@@ -106,6 +107,9 @@ impl Hash for DBVal {
             DBVal::Duration(d) => {
                 d.hash(state);
             }
+            DBVal::Months(m) => {
+                m.hash(state);
+            }
         }
     }
 }
@@ -152,6 +156,7 @@ pub fn process_dbresults(results: Vec<DBResult>) -> ResultSet {
                     DBCol::Bool(vals) => DBVal::Bool(vals[i]),
                     DBCol::DateTime(vals) => DBVal::DateTime(vals[i]),
                     DBCol::Duration(vals) => DBVal::Duration(vals[i]),
+                    DBCol::Months(vals) => DBVal::Months(vals[i]),
                 });
             }
             rows.insert(row);
@@ -167,6 +172,7 @@ pub fn process_dbresults(results: Vec<DBResult>) -> ResultSet {
                 DBCol::Bool(vals) => DBVal::Bool(vals[0]),
                 DBCol::DateTime(vals) => DBVal::DateTime(vals[0]),
                 DBCol::Duration(vals) => DBVal::Duration(vals[0]),
+                DBCol::Months(vals) => DBVal::Months(vals[0]),
             })
             .collect();
         parsed_results.insert(group, rows);
@@ -0,0 +1,220 @@
+use super::exec::{ExecParams, ExecStats, RunContext};
+use super::expr::{BinaryOperator, DBCol, DBResult, Expr};
+use super::join::{self, JoinCond};
+use super::parser::PredNode;
+use super::table::{Id, Table};
+use log::debug;
+use roaring::RoaringBitmap;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// A semi-join of the rows of the outer query against the rows of a subquery, for the predicate
+// atoms `x IN (SELECT y FROM ...)` and `EXISTS (SELECT ... FROM ... WHERE y = x)`. An outer row
+// passes if some row of the subquery has the same keys. The subquery is evaluated once, the first
+// time the atom is, and its keys are kept in `ExecStats::semi_join_results` for every later
+// evaluation.
+#[derive(Debug)]
+pub struct SemiJoin {
+    // The FROM clause and the rest of the WHERE clause of the subquery
+    table: Rc<dyn Table>,
+    filter: Option<PredNode>,
+    // The keys to join on, evaluated under the outer and subquery tables respectively. For IN, the
+    // first keys are `x` and `y`. The rest come from the equalities between the subquery and the
+    // outer query in the subquery's WHERE clause.
+    outer_keys: Vec<Expr>,
+    inner_keys: Vec<Expr>,
+    // Whether this is `x IN (...)` rather than `EXISTS (...)`
+    is_in: bool,
+    negated: bool,
+    // The subquery as written, for display
+    subquery: String,
+    // Identifies the results of the subquery in `ExecStats::semi_join_results`
+    id: Id,
+}
+
+impl SemiJoin {
+    pub fn new(
+        table: Rc<dyn Table>,
+        filter: Option<PredNode>,
+        keys: Vec<(Expr, Expr)>,
+        is_in: bool,
+        negated: bool,
+        subquery: String,
+    ) -> Self {
+        let (outer_keys, inner_keys) = keys.into_iter().unzip();
+        SemiJoin {
+            table,
+            filter,
+            outer_keys,
+            inner_keys,
+            is_in,
+            negated,
+            subquery,
+            id: Id::new(),
+        }
+    }
+
+    // Evaluates the atom on the rows of the outer query given by `run_context`. For IN, a row with
+    // no match is NULL rather than FALSE if its key is NULL or a subquery row which matches the
+    // rest of its keys has a NULL key, as SQL has it.
+    pub fn eval(&self, run_context: &RunContext, exec_stats: &mut ExecStats) -> DBResult {
+        let inner_results = match exec_stats.semi_join_results.get(&self.id) {
+            Some(inner_results) => inner_results.clone(),
+            None => {
+                let inner_results =
+                    Rc::new(self.eval_subquery(&run_context.exec_params, exec_stats));
+                exec_stats
+                    .semi_join_results
+                    .insert(self.id, inner_results.clone());
+                inner_results
+            }
+        };
+        let (inner_len, inner_results) = inner_results.as_ref();
+
+        // An uncorrelated EXISTS is the same for every row.
+        if self.outer_keys.is_empty() {
+            return DBResult {
+                cols: vec![(vec![], DBCol::Bool(vec![*inner_len > 0]))]
+                    .into_iter()
+                    .collect(),
+                nulls: HashMap::new(),
+                ref_table: None,
+            };
+        }
+
+        let outer_results = self
+            .outer_keys
+            .iter()
+            .map(|key| key.eval(run_context, exec_stats))
+            .collect::<Vec<_>>();
+        let len = outer_results[0].len();
+        let ref_table = outer_results[0].ref_table.clone();
+        let outer_nulls = outer_results[0].null_mask(&[]).cloned();
+
+        let mut matched = RoaringBitmap::new();
+        let mut unknown = RoaringBitmap::new();
+        if *inner_len > 0 {
+            let conds = outer_results
+                .into_iter()
+                .zip(inner_results)
+                .zip(self.outer_keys.iter().zip(&self.inner_keys))
+                .map(|((outer, inner), (outer_key, inner_key))| {
+                    let mut cond = JoinCond::Cmp {
+                        left: outer,
+                        right: inner.clone(),
+                        op: BinaryOperator::Eq,
+                    };
+                    cond.normalize_keys(&Expr::BinaryOp {
+                        left: Box::new(outer_key.clone()),
+                        right: Box::new(inner_key.clone()),
+                        op: BinaryOperator::Eq,
+                    });
+                    cond
+                })
+                .collect::<Vec<_>>();
+            matched = join::join_conds(&conds, run_context.exec_params.sort_merge_joins)
+                .into_iter()
+                .map(|(outer_idx, _)| outer_idx as u32)
+                .collect();
+
+            if self.is_in {
+                let inner_nulls = inner_results[0].null_mask(&[]);
+                unknown = if conds.len() == 1 {
+                    match inner_nulls {
+                        Some(_) => (0..len as u32).collect(),
+                        None => outer_nulls.unwrap_or_default(),
+                    }
+                } else {
+                    join::join_conds(&conds[1..], run_context.exec_params.sort_merge_joins)
+                        .into_iter()
+                        .filter(|(outer_idx, inner_idx)| {
+                            outer_nulls
+                                .as_ref()
+                                .is_some_and(|nulls| nulls.contains(*outer_idx as u32))
+                                || inner_nulls
+                                    .is_some_and(|nulls| nulls.contains(*inner_idx as u32))
+                        })
+                        .map(|(outer_idx, _)| outer_idx as u32)
+                        .collect()
+                };
+                unknown -= &matched;
+            }
+        }
+        debug!(
+            "[SemiJoin] {} of {} rows matched {}",
+            matched.len(),
+            len,
+            self
+        );
+
+        let vals = (0..len as u32)
+            .map(|i| matched.contains(i) != self.negated)
+            .collect();
+        let mut nulls = HashMap::new();
+        if !unknown.is_empty() {
+            nulls.insert(vec![], unknown);
+        }
+        DBResult {
+            cols: vec![(vec![], DBCol::Bool(vals))].into_iter().collect(),
+            nulls,
+            ref_table,
+        }
+    }
+
+    // Joins and filters the subquery the same way `Executor::run` does, then evaluates its keys.
+    fn eval_subquery(
+        &self,
+        exec_params: &ExecParams,
+        exec_stats: &mut ExecStats,
+    ) -> (usize, Vec<DBResult>) {
+        let pushed_tables = self
+            .table
+            .eval_join(exec_params, exec_stats, self.filter.as_ref());
+        let data_num = self.table.len();
+        let index =
+            self.filter
+                .clone()
+                .map(|pred| match pred.without_pushdown_atoms(&pushed_tables) {
+                    Some(pred) => {
+                        pred.eval(&(0..data_num as u32).collect(), exec_params, exec_stats)
+                    }
+                    None => (0..data_num as u32).collect(),
+                });
+        let len = index
+            .as_ref()
+            .map_or(data_num, |index| index.len() as usize);
+        if len == 0 {
+            return (0, vec![]);
+        }
+
+        let run_context = RunContext {
+            index,
+            groups: Default::default(),
+            ref_table: None,
+            exec_params: exec_params.clone(),
+        };
+        let results = self
+            .inner_keys
+            .iter()
+            .map(|key| key.eval(&run_context, exec_stats))
+            .collect();
+        (len, results)
+    }
+
+    // The keys of the outer query, which are all the atom refers to outside the subquery.
+    pub fn outer_keys(&self) -> &[Expr] {
+        &self.outer_keys
+    }
+}
+
+impl fmt::Display for SemiJoin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_in {
+            let op = if self.negated { "NOT IN" } else { "IN" };
+            write!(f, "{} {} ({})", self.outer_keys[0], op, self.subquery)
+        } else {
+            write!(f, "EXISTS ({})", self.subquery)
+        }
+    }
+}
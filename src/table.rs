@@ -1,7 +1,10 @@
 use super::config::{BLOCK_SIZE, READ_BUF_SIZE};
 use super::exec::{ExecParams, ExecStats, RunContext};
 use super::expr::{BinaryOperator, DBCol, DBResult, DataType, Expr};
+use super::join;
+use super::join_index::JoinIndex;
 use super::parser::PredNode;
+use super::utils;
 use byteorder::{NativeEndian, ReadBytesExt};
 use chrono::{Duration, TimeZone, Utc};
 use log::debug;
@@ -14,7 +17,6 @@ use std::collections::HashMap;
 use std::ffi::CStr;
 use std::fmt;
 use std::fs::{self, File};
-use std::hash::Hash;
 use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::mem;
 use std::ops::Deref;
@@ -25,6 +27,10 @@ use std::slice;
 
 pub type Id = ProcessUniqueId;
 
+// Marks a row of a join index which has no match on that side of an outer join. Any column read at
+// such a row is NULL.
+pub const NULL_IDX: usize = usize::MAX;
+
 // A column that refers to a specific column of a specific table.
 // Here, `table` should always refer to a FileTable.
 #[derive(Debug)]
@@ -33,6 +39,9 @@ pub struct FileCol {
     name: String,
     data_type: DataType,
     pub table: Weak<FileTable>,
+    // File indices of the rows where this column is NULL, read from the optional
+    // `<name>.null_idxs` file next to the column.
+    null_idxs: Option<RoaringBitmap>,
 }
 
 // `ReadContext` holds various mappings from which elements to read from the file to
@@ -42,7 +51,8 @@ pub struct FileCol {
 // `file_index`: A bitmap which stores the indices of the elements we have to read from file (we
 // call these file indices). Note this is different from `index` from the `exec` module. That index
 // is with respect to the joined reference table, whereas this is with respect to the file itself.
-// `output_index`: A Vec<usize> in which the ith element refers to an index in file_index
+// `output_index`: A Vec<usize> in which the ith element refers to an index in file_index, or is
+// `NULL_IDX` if the ith output is a NULL padded by an outer join.
 #[derive(Debug, Clone)]
 pub struct ReadContext {
     pub file_index: RoaringBitmap,
@@ -91,12 +101,17 @@ impl FileCol {
             DataType::Bool => 1,
             DataType::DateTime => mem::size_of::<raw::c_long>(),
             DataType::Duration => mem::size_of::<raw::c_long>(),
+            DataType::Months => mem::size_of::<raw::c_int>(),
         }
     }
 
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    pub fn data_type(&self) -> DataType {
+        self.data_type
+    }
 }
 
 #[derive(Debug)]
@@ -123,7 +138,8 @@ pub struct JoinTable {
     right: Rc<dyn Table>,
     cols: HashMap<String, Vec<Rc<FileCol>>>,
     join_type: JoinType,
-    pub constraint: Expr,
+    // The conjuncts of the join constraint
+    pub constraints: Vec<Expr>,
     // Mapping from FileCold ID
     join_idx: RefCell<HashMap<String, Vec<usize>>>,
 }
@@ -145,12 +161,15 @@ pub trait Table: fmt::Debug + fmt::Display {
 
     fn get_all_join_exprs(&self) -> HashMap<String, Expr>;
 
+    // Joins the tables and returns the file tables whose rows were filtered by the atoms of `pred`
+    // which only refer to them, so that they need not be evaluated again after the join. The join
+    // is only evaluated once, so nothing is filtered if it was already evaluated.
     fn eval_join(
         &self,
         exec_params: &ExecParams,
         exec_stats: &mut ExecStats,
         pred: Option<&PredNode>,
-    );
+    ) -> Vec<Id>;
 
     fn get_map<'a>(&'a self) -> Box<dyn Deref<Target = HashMap<String, Vec<usize>>> + 'a>;
 }
@@ -207,12 +226,14 @@ impl Table for FileTable {
         HashMap::new()
     }
 
+    // A file table on its own is not joined, so the whole predicate is left for the planner.
     fn eval_join(
         &self,
         _exec_params: &ExecParams,
         _exec_stats: &mut ExecStats,
         _pred: Option<&PredNode>,
-    ) {
+    ) -> Vec<Id> {
+        vec![]
     }
 
     fn get_map<'a>(&'a self) -> Box<dyn Deref<Target = HashMap<String, Vec<usize>>> + 'a> {
@@ -264,11 +285,21 @@ impl FileTable {
                 _ => panic!("Unknown type: {}", data_type),
             };
 
+            let null_idxs_path = path.join(format!("{}.null_idxs", name));
+            let null_idxs = if null_idxs_path.exists() {
+                let mut idxs = utils::read_u32_file(&null_idxs_path);
+                idxs.sort_unstable();
+                Some(RoaringBitmap::from_sorted_iter(idxs).unwrap())
+            } else {
+                None
+            };
+
             let col = Rc::new(FileCol {
                 id: Id::new(),
                 name: name.to_string(),
                 data_type,
                 table: Rc::downgrade(&table),
+                null_idxs,
             });
 
             let col_file_size = fs::metadata(path.join(col.name.to_string()))
@@ -293,6 +324,36 @@ impl FileTable {
         table
     }
 
+    // A copy of the table under `alias`, for a table referenced under an alias in a query, e.g.,
+    // each side of `A a1 join A a2`. The copy reads the same files but has its own ID and columns,
+    // so that each reference is resolved, joined and read as a table of its own.
+    pub fn with_alias(&self, alias: &str) -> Rc<FileTable> {
+        let table = Rc::new(FileTable {
+            id: Id::new(),
+            name: alias.to_string(),
+            path: self.path.clone(),
+            cols: RefCell::new(HashMap::new()),
+            data_num: RefCell::new(*self.data_num.borrow()),
+        });
+        for (name, col) in self.cols.borrow().iter() {
+            table.cols.borrow_mut().insert(
+                name.to_string(),
+                Rc::new(FileCol {
+                    id: Id::new(),
+                    name: col.name.to_string(),
+                    data_type: col.data_type,
+                    table: Rc::downgrade(&table),
+                    null_idxs: col.null_idxs.clone(),
+                }),
+            );
+        }
+        table
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     fn cols(&self) -> &RefCell<HashMap<String, Rc<FileCol>>> {
         &self.cols
     }
@@ -311,6 +372,7 @@ impl FileTable {
         //));
 
         let mut file = File::open(self.path.join(&col.name)).expect("Could not find file");
+        let null_idxs = col.null_idxs.as_ref();
         //let mut file = OpenOptions::new()
         //    .read(true)
         //    .custom_flags(libc::O_DIRECT | libc::O_SYNC)
@@ -334,6 +396,7 @@ impl FileTable {
             DataType::Bool => DBCol::Bool(Vec::with_capacity(data_num)),
             DataType::DateTime => DBCol::DateTime(Vec::with_capacity(data_num)),
             DataType::Duration => DBCol::Duration(Vec::with_capacity(data_num)),
+            DataType::Months => DBCol::Months(Vec::with_capacity(data_num)),
         };
         let mut num_bufs_read = 0;
         // XXX Screw random I/O for a moment
@@ -470,6 +533,9 @@ impl FileTable {
                     let dur = rdr.read_i64::<NativeEndian>().unwrap();
                     vals.push(Duration::seconds(dur));
                 }
+                DBCol::Months(vals) => {
+                    vals.push(rdr.read_i32::<NativeEndian>().unwrap());
+                }
             }
         }
         //}
@@ -492,30 +558,69 @@ impl FileTable {
         //    file_size / (READ_BUF_SIZE) + (if file_size % READ_BUF_SIZE != 0 { 1 } else { 0 })
         //);
 
+        // Positions in the output which are NULL, either because the value in the file is NULL or
+        // because the row was padded by an outer join. `pidx` in `output_index` indexes into the
+        // rows read in `file_index` order.
+        let null_pidxs: Option<Vec<bool>> = null_idxs
+            .filter(|null_idxs| !(&read_context.file_index & *null_idxs).is_empty())
+            .map(|null_idxs| {
+                read_context
+                    .file_index
+                    .iter()
+                    .map(|idx| null_idxs.contains(idx))
+                    .collect()
+            });
+        let null_mask: RoaringBitmap = read_context
+            .output_index
+            .iter()
+            .enumerate()
+            .filter(|(_, pidx)| {
+                **pidx == NULL_IDX || null_pidxs.as_ref().is_some_and(|nulls| nulls[**pidx])
+            })
+            .map(|(i, _)| i as u32)
+            .collect();
+
         fn make_output_col<T: Clone + fmt::Debug>(
             vals: Vec<T>,
             read_context: ReadContext,
+            null_val: T,
         ) -> Vec<T> {
             read_context
                 .output_index
                 .iter()
-                .map(|pidx| vals[*pidx].clone())
+                .map(|pidx| {
+                    if *pidx == NULL_IDX {
+                        null_val.clone()
+                    } else {
+                        vals[*pidx].clone()
+                    }
+                })
                 .collect()
         }
 
         let output_col = match col {
-            DBCol::Int(vals) => DBCol::Int(make_output_col(vals, read_context)),
-            DBCol::Long(vals) => DBCol::Long(make_output_col(vals, read_context)),
-            DBCol::Float(vals) => DBCol::Float(make_output_col(vals, read_context)),
-            DBCol::Double(vals) => DBCol::Double(make_output_col(vals, read_context)),
-            DBCol::Str(vals) => DBCol::Str(make_output_col(vals, read_context)),
-            DBCol::Bool(vals) => DBCol::Bool(make_output_col(vals, read_context)),
-            DBCol::DateTime(vals) => DBCol::DateTime(make_output_col(vals, read_context)),
-            DBCol::Duration(vals) => DBCol::Duration(make_output_col(vals, read_context)),
+            DBCol::Int(vals) => DBCol::Int(make_output_col(vals, read_context, 0)),
+            DBCol::Long(vals) => DBCol::Long(make_output_col(vals, read_context, 0)),
+            DBCol::Float(vals) => DBCol::Float(make_output_col(vals, read_context, 0.)),
+            DBCol::Double(vals) => DBCol::Double(make_output_col(vals, read_context, 0.)),
+            DBCol::Str(vals) => DBCol::Str(make_output_col(vals, read_context, String::new())),
+            DBCol::Bool(vals) => DBCol::Bool(make_output_col(vals, read_context, false)),
+            DBCol::DateTime(vals) => {
+                DBCol::DateTime(make_output_col(vals, read_context, Utc.timestamp(0, 0)))
+            }
+            DBCol::Duration(vals) => {
+                DBCol::Duration(make_output_col(vals, read_context, Duration::zero()))
+            }
+            DBCol::Months(vals) => DBCol::Months(make_output_col(vals, read_context, 0)),
         };
 
         let mut result = DBResult {
             cols: vec![(vec![], output_col)].into_iter().collect(),
+            nulls: Some(null_mask)
+                .filter(|mask| !mask.is_empty())
+                .map(|mask| (vec![], mask))
+                .into_iter()
+                .collect(),
             ref_table: None,
         };
 
@@ -567,7 +672,9 @@ where {
 
             let mut output_index = vec![];
             for idx in idxs {
-                file_index.insert(*idx as u32);
+                if *idx != NULL_IDX {
+                    file_index.insert(*idx as u32);
+                }
                 output_index.push(*idx);
             }
             let rev_map = file_index
@@ -577,7 +684,13 @@ where {
                 .collect::<HashMap<u32, usize>>();
             let output_index = output_index
                 .iter()
-                .map(|fidx| *rev_map.get(&(*fidx as u32)).unwrap())
+                .map(|fidx| {
+                    if *fidx == NULL_IDX {
+                        NULL_IDX
+                    } else {
+                        *rev_map.get(&(*fidx as u32)).unwrap()
+                    }
+                })
                 .collect::<Vec<usize>>();
 
             ReadContext {
@@ -632,7 +745,9 @@ where {
             .into_iter()
             .chain(right_exprs)
             .collect::<HashMap<String, Expr>>();
-        all_exprs.insert(self.constraint.to_string(), self.constraint.clone());
+        for constraint in &self.constraints {
+            all_exprs.insert(constraint.to_string(), constraint.clone());
+        }
         all_exprs
     }
 
@@ -641,158 +756,79 @@ where {
         exec_params: &ExecParams,
         exec_stats: &mut ExecStats,
         pred: Option<&PredNode>,
-    ) {
+    ) -> Vec<Id> {
         debug!("[JoinTable] Evaluating {}", self.name);
         //let mut join_idx = self.join_idx.borrow_mut();
         if !self.join_idx.borrow().is_empty() {
-            return;
+            return vec![];
         }
 
-        self.left.eval_join(exec_params, exec_stats, pred);
-        self.right.eval_join(exec_params, exec_stats, pred);
-
-        let left_result;
-        let right_result;
-
-        if let Expr::BinaryOp {
-            left,
-            right,
-            op: BinaryOperator::Eq,
-        } = &self.constraint
-        {
-            left_result = left.eval(
-                &RunContext {
-                    index: None,
-                    groups: vec![],
-                    ref_table: None,
-                    exec_params: exec_params.clone(),
-                },
-                exec_stats,
-            );
-            right_result = right.eval(
-                &RunContext {
-                    index: None,
-                    groups: vec![],
-                    ref_table: None,
-                    exec_params: exec_params.clone(),
-                },
-                exec_stats,
-            );
-        } else {
-            panic!(
-                "We do not support non-equality join constraint ({:?})",
-                self.constraint
-            );
-        }
-
-        fn inner_join<'a, T: Eq + Hash + fmt::Debug + Clone>(
-            left_vals: &Vec<T>,
-            left_map: Box<dyn Deref<Target = HashMap<String, Vec<usize>>> + 'a>,
-            right_vals: &Vec<T>,
-            right_map: Box<dyn Deref<Target = HashMap<String, Vec<usize>>> + 'a>,
-        ) -> HashMap<String, Vec<usize>> {
-            let left_maps: Vec<(&String, &Vec<usize>)> = left_map.iter().collect();
-            let right_maps: Vec<(&String, &Vec<usize>)> = right_map.iter().collect();
-
-            let mut all_maps: Vec<(String, Vec<usize>)> =
-                Vec::with_capacity(left_maps.len() + right_maps.len());
-            for (name, _) in &left_maps {
-                all_maps.push((name.to_string(), vec![]));
-            }
-            for (name, _) in &right_maps {
-                all_maps.push((name.to_string(), vec![]));
+        // Predicates are not pushed into the side which an outer join pads with NULLs, since rows
+        // dropped there would come back as padded rows.
+        let (left_pushable, right_pushable) = self.pushable_sides();
+        let left_pred = pred.filter(|_| left_pushable);
+        let right_pred = pred.filter(|_| right_pushable);
+        let mut pushed = self.left.eval_join(exec_params, exec_stats, left_pred);
+        pushed.append(&mut self.right.eval_join(exec_params, exec_stats, right_pred));
+        let left_rows = pushdown(&self.left, left_pred, exec_params, exec_stats);
+        let right_rows = pushdown(&self.right, right_pred, exec_params, exec_stats);
+        for (side, rows) in [(&self.left, &left_rows), (&self.right, &right_rows)] {
+            if rows.is_some() {
+                pushed.push(side.id());
             }
-
-            let mut left_rev_map: HashMap<T, Vec<usize>> = HashMap::new();
-            for (i, val) in left_vals.iter().enumerate() {
-                left_rev_map.entry(val.clone()).or_default().push(i);
-            }
-
-            for (right_idx, val) in right_vals.iter().enumerate() {
-                let left_idxs = left_rev_map.get(val);
-                if left_idxs.is_some() {
-                    for left_idx in left_idxs.unwrap() {
-                        for (i, left_map) in left_maps.iter().enumerate() {
-                            all_maps[i].1.push(left_map.1[*left_idx]);
-                        }
-                        for (i, right_map) in right_maps.iter().enumerate() {
-                            all_maps[i + left_maps.len()].1.push(right_map.1[right_idx]);
-                        }
-                    }
-                }
-            }
-
-            debug!(
-                "[JoinTable] Joined and returning maps for [{}]",
-                all_maps
-                    .iter()
-                    .map(|(name, _)| name.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            );
-            all_maps.into_iter().collect()
         }
 
-        #[allow(unused_variables)]
-        fn left_outer_join<T: Eq + Hash + Clone>(
-            left_vals: &Vec<T>,
-            left_map: Box<dyn Deref<Target = HashMap<Id, Vec<usize>>>>,
-            right_vals: &Vec<T>,
-            right_map: Box<dyn Deref<Target = HashMap<Id, Vec<usize>>>>,
-        ) -> HashMap<Id, Vec<usize>> {
-            unimplemented!();
+        if let Some(join_idx) = self.indexed_join(
+            left_rows.as_ref(),
+            right_rows.as_ref(),
+            exec_params,
+            exec_stats,
+            left_pred,
+        ) {
+            exec_stats.num_joined_records += join_idx_len(&join_idx) as u128;
+            self.join_idx.replace(join_idx);
+            debug!("[Join] Done evaluating {} from join index", self.name);
+            return pushed;
         }
 
-        let left_vals = left_result
-            .cols
-            .get(&vec![])
-            .expect("We're grouping on joins?");
-        let left_table = left_result.ref_table.expect("join requires ref table");
-        left_table.eval_join(exec_params, exec_stats, pred);
-        let left_map = left_table.get_map();
-        let right_vals = right_result
-            .cols
-            .get(&vec![])
-            .expect("We're grouping on joins?");
-        let right_table = right_result.ref_table.expect("join requires ref table");
-        right_table.eval_join(exec_params, exec_stats, pred);
-        let right_map = right_table.get_map();
-
-        if let JoinType::Inner = self.join_type {
-            // TODO We may have to cast types here if the key types don't match exactly.
-            self.join_idx.replace(match (left_vals, right_vals) {
-                (DBCol::Int(left_vals), DBCol::Int(right_vals)) => {
-                    inner_join(left_vals, left_map, right_vals, right_map)
-                }
-                (DBCol::Long(left_vals), DBCol::Long(right_vals)) => {
-                    inner_join(left_vals, left_map, right_vals, right_map)
-                }
-                (DBCol::Str(left_vals), DBCol::Str(right_vals)) => {
-                    inner_join(left_vals, left_map, right_vals, right_map)
-                }
-                _ => {
-                    panic!("unuspported join types");
-                }
-            });
+        let mut conds = self.constraints.iter().map(|_| None).collect::<Vec<_>>();
+        let (left_rows, right_rows) = if exec_params.bloom_filter_joins {
+            self.bloom_filter(&mut conds, left_rows, right_rows, exec_params, exec_stats)
         } else {
-            // XXX All joins are inner joins! MUHAHAHA
-            self.join_idx.replace(match (left_vals, right_vals) {
-                (DBCol::Int(left_vals), DBCol::Int(right_vals)) => {
-                    inner_join(left_vals, left_map, right_vals, right_map)
-                }
-                (DBCol::Long(left_vals), DBCol::Long(right_vals)) => {
-                    inner_join(left_vals, left_map, right_vals, right_map)
-                }
-                (DBCol::Str(left_vals), DBCol::Str(right_vals)) => {
-                    inner_join(left_vals, left_map, right_vals, right_map)
-                }
-                _ => {
-                    panic!("unuspported join types");
-                }
-            });
+            (left_rows, right_rows)
+        };
+        let conds = self
+            .constraints
+            .iter()
+            .zip(conds)
+            .map(|(constraint, cond)| {
+                cond.unwrap_or_else(|| {
+                    self.join_cond(
+                        constraint,
+                        left_rows.as_ref(),
+                        right_rows.as_ref(),
+                        exec_params,
+                        exec_stats,
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        let pairs = join::join_conds(&conds, exec_params.sort_merge_joins);
+        let join_idx = join::build_join_idx(
+            &self.join_type,
+            &side_map(&self.left, left_rows.as_ref()),
+            &side_map(&self.right, right_rows.as_ref()),
+            &pairs,
+        );
+        // An index over filtered rows would be incomplete for later runs.
+        if exec_params.persist_join_index && left_rows.is_none() && right_rows.is_none() {
+            self.persist_join_index(&pairs);
         }
+        exec_stats.num_joined_records += join_idx_len(&join_idx) as u128;
+        self.join_idx.replace(join_idx);
 
         debug!("[Join] Done evaluating {}", self.name);
+        pushed
     }
 
     fn get_map<'a>(&'a self) -> Box<dyn Deref<Target = HashMap<String, Vec<usize>>> + 'a> {
@@ -800,10 +836,71 @@ where {
     }
 }
 
+// The file table `table` is, if it is one rather than a join.
+pub fn as_file_table(table: &Rc<dyn Table>) -> Option<Rc<FileTable>> {
+    table
+        .all_cols()
+        .first()
+        .map(|col| col.table.upgrade().unwrap())
+        .filter(|file_table| file_table.id() == table.id())
+}
+
+// Filters the rows of `side`, if it is a file table, by the atoms of `pred` which only refer to it.
+// Returns the file indices of the rows which pass, or None if nothing was filtered.
+fn pushdown(
+    side: &Rc<dyn Table>,
+    pred: Option<&PredNode>,
+    exec_params: &ExecParams,
+    exec_stats: &mut ExecStats,
+) -> Option<RoaringBitmap> {
+    let atoms = pred?.pushdown_atoms(as_file_table(side)?.id());
+    if atoms.is_empty() {
+        return None;
+    }
+
+    let mut rows: RoaringBitmap = (0..side.len() as u32).collect();
+    for atom in atoms {
+        rows = atom.eval_on_table(&rows, side, exec_params, exec_stats);
+        debug!(
+            "[JoinTable] Pushed down {} into {}, {} rows left",
+            atom.expr,
+            side.name(),
+            rows.len()
+        );
+    }
+    Some(rows)
+}
+
+// The join map of `side`, restricted to `rows` if its rows were filtered by `pushdown`.
+fn side_map<'a>(
+    side: &'a Rc<dyn Table>,
+    rows: Option<&RoaringBitmap>,
+) -> Box<dyn Deref<Target = HashMap<String, Vec<usize>>> + 'a> {
+    match rows {
+        Some(rows) => {
+            let mut map = HashMap::new();
+            map.insert(
+                side.name().to_string(),
+                rows.iter().map(|row| row as usize).collect(),
+            );
+            Box::new(Box::new(map))
+        }
+        None => side.get_map(),
+    }
+}
+
+// The number of rows of a join index.
+fn join_idx_len(join_idx: &HashMap<String, Vec<usize>>) -> usize {
+    join_idx.values().next().map_or(0, |rows| rows.len())
+}
+
+// A column of a join constraint along with the table it is read under.
+type ColSide<'a> = (&'a Rc<FileCol>, &'a Rc<dyn Table>);
+
 impl JoinTable {
     pub fn new(
         join_type: JoinType,
-        constraint: Expr,
+        constraints: Vec<Expr>,
         left: &Rc<dyn Table>,
         right: &Rc<dyn Table>,
     ) -> Rc<dyn Table> {
@@ -814,7 +911,7 @@ impl JoinTable {
             right: right.clone(),
             cols: HashMap::new(),
             join_type,
-            constraint,
+            constraints,
             join_idx: RefCell::new(HashMap::new()),
         };
         for col in left.all_cols().iter().chain(right.all_cols().iter()) {
@@ -830,6 +927,301 @@ impl JoinTable {
     fn cols(&self) -> &HashMap<String, Vec<Rc<FileCol>>> {
         &self.cols
     }
+
+    // Whether predicates may be pushed into the (left, right) side of the join.
+    fn pushable_sides(&self) -> (bool, bool) {
+        match self.join_type {
+            JoinType::Inner => (true, true),
+            JoinType::LeftOuter => (true, false),
+            JoinType::RightOuter => (false, true),
+        }
+    }
+
+    // If the constraint is a single equality between two columns, returns the (col, ref table) of each
+    // side, oriented so that the first refers to the left side of the join.
+    fn col_constraint(&self) -> Option<(ColSide<'_>, ColSide<'_>)> {
+        let (left, right) = match self.constraints.as_slice() {
+            [Expr::BinaryOp {
+                left,
+                right,
+                op: BinaryOperator::Eq,
+            }] => match (left.as_ref(), right.as_ref()) {
+                (
+                    Expr::ColRef {
+                        col: left_col,
+                        ref_table: left_ref,
+                    },
+                    Expr::ColRef {
+                        col: right_col,
+                        ref_table: right_ref,
+                    },
+                ) => ((left_col, left_ref), (right_col, right_ref)),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        if left.1.id() == self.right.id() {
+            Some((right, left))
+        } else {
+            Some((left, right))
+        }
+    }
+
+    // Writes the joined `pairs` into `__join_keys__` so that later runs can load them. Only joins
+    // directly between two file tables on a column equality can be stored this way.
+    fn persist_join_index(&self, pairs: &[(usize, usize)]) {
+        let ((left_col, left_ref), (right_col, right_ref)) = match self.col_constraint() {
+            Some(cols) => cols,
+            None => return,
+        };
+        let left_file_table = left_col.table.upgrade().unwrap();
+        let right_file_table = right_col.table.upgrade().unwrap();
+        if left_ref.id() != left_file_table.id() || right_ref.id() != right_file_table.id() {
+            return;
+        }
+
+        JoinIndex::write(
+            &left_file_table
+                .path()
+                .parent()
+                .unwrap()
+                .join("__join_keys__"),
+            left_col,
+            right_col,
+            pairs.iter().cloned(),
+        );
+    }
+
+    // Whether `expr`, one side of a join constraint, refers to the right side of the join.
+    fn refers_to_right(&self, expr: &Expr) -> bool {
+        expr.get_all_cols().iter().any(|col| {
+            self.right
+                .contains_subtable(col.table.upgrade().unwrap().name())
+        })
+    }
+
+    // Evaluates one conjunct of the join constraint. Each side is evaluated under the side of the
+    // join it refers to, and the sides are oriented so that the left refers to the left side of
+    // the join, which is the side kept by a left outer join.
+    fn join_cond(
+        &self,
+        constraint: &Expr,
+        left_rows: Option<&RoaringBitmap>,
+        right_rows: Option<&RoaringBitmap>,
+        exec_params: &ExecParams,
+        exec_stats: &mut ExecStats,
+    ) -> join::JoinCond {
+        let mut eval_side = |expr: &Expr, table: &Rc<dyn Table>| {
+            let rows = if Rc::ptr_eq(table, &self.left) {
+                left_rows
+            } else {
+                right_rows
+            };
+            exec_stats.num_join_key_records +=
+                rows.map_or(table.len(), |rows| rows.len() as usize) as u128;
+            let run_context = RunContext {
+                index: rows.cloned(),
+                groups: Default::default(),
+                ref_table: Some(table.clone()),
+                exec_params: exec_params.clone(),
+            };
+            expr.eval(&run_context, exec_stats)
+        };
+
+        let mut cond = match constraint {
+            Expr::BinaryOp { left, right, op } => {
+                let (left, right, op) = if self.refers_to_right(left) {
+                    (right, left, join::flip(op))
+                } else {
+                    (left, right, op.clone())
+                };
+                join::JoinCond::Cmp {
+                    left: eval_side(left, &self.left),
+                    right: eval_side(right, &self.right),
+                    op,
+                }
+            }
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated: false,
+            } => {
+                let expr_on_left = !self.refers_to_right(expr);
+                let (expr_side, band_side) = if expr_on_left {
+                    (&self.left, &self.right)
+                } else {
+                    (&self.right, &self.left)
+                };
+                join::JoinCond::Band {
+                    expr: eval_side(expr, expr_side),
+                    low: eval_side(low, band_side),
+                    high: eval_side(high, band_side),
+                    expr_on_left,
+                }
+            }
+            _ => {
+                panic!("We do not support join constraint ({})", constraint);
+            }
+        };
+        cond.normalize_keys(constraint);
+        cond
+    }
+
+    // Passes information sideways between the sides of the join: if one side was filtered by
+    // `pushdown` down to fewer rows than the other has, builds a bloom filter from its keys of the
+    // first equality of the constraint and drops the rows of the other side whose keys cannot
+    // match. Only that equality is evaluated here, into its slot of `conds`, so the keys of the
+    // other conjuncts are only read for the rows which are left. The other side has to be a file
+    // table, and rows are never dropped from a side which an outer join keeps whether or not they
+    // match. Returns the rows of each side to join.
+    fn bloom_filter(
+        &self,
+        conds: &mut [Option<join::JoinCond>],
+        left_rows: Option<RoaringBitmap>,
+        right_rows: Option<RoaringBitmap>,
+        exec_params: &ExecParams,
+        exec_stats: &mut ExecStats,
+    ) -> (Option<RoaringBitmap>, Option<RoaringBitmap>) {
+        let side_len = |side: &Rc<dyn Table>, rows: &Option<RoaringBitmap>| {
+            rows.as_ref().map_or(side.len(), |rows| rows.len() as usize)
+        };
+        let left_len = side_len(&self.left, &left_rows);
+        let right_len = side_len(&self.right, &right_rows);
+        // Rows are dropped from the larger side, and only the filtered side is built into a filter.
+        let probe_left = match (&left_rows, &right_rows) {
+            (None, Some(_)) => true,
+            (Some(_), None) => false,
+            (Some(_), Some(_)) => left_len > right_len,
+            (None, None) => return (left_rows, right_rows),
+        };
+        let (probe, probe_rows, probe_len, build_len) = if probe_left {
+            (&self.left, &left_rows, left_len, right_len)
+        } else {
+            (&self.right, &right_rows, right_len, left_len)
+        };
+        let probe_kept = match self.join_type {
+            JoinType::Inner => false,
+            JoinType::LeftOuter => probe_left,
+            JoinType::RightOuter => !probe_left,
+        };
+        if build_len >= probe_len || probe_kept || as_file_table(probe).is_none() {
+            return (left_rows, right_rows);
+        }
+        let eq_idx = match self.constraints.iter().position(|constraint| {
+            matches!(
+                constraint,
+                Expr::BinaryOp {
+                    op: BinaryOperator::Eq,
+                    ..
+                }
+            )
+        }) {
+            Some(eq_idx) => eq_idx,
+            None => return (left_rows, right_rows),
+        };
+        let mut cond = self.join_cond(
+            &self.constraints[eq_idx],
+            left_rows.as_ref(),
+            right_rows.as_ref(),
+            exec_params,
+            exec_stats,
+        );
+        let positions = match &cond {
+            join::JoinCond::Cmp { left, right, .. } => {
+                let (build_keys, probe_keys) = if probe_left {
+                    (right, left)
+                } else {
+                    (left, right)
+                };
+                let filter = join::BloomFilter::new(join::keys(build_keys));
+                filter.probe(join::keys(probe_keys))
+            }
+            join::JoinCond::Band { .. } => {
+                panic!("Equality {} is not a comparison", self.constraints[eq_idx]);
+            }
+        };
+        if positions.len() == probe_len {
+            conds[eq_idx] = Some(cond);
+            return (left_rows, right_rows);
+        }
+        exec_stats.num_bloom_filtered_records += (probe_len - positions.len()) as u128;
+        debug!(
+            "[JoinTable] Bloom filter dropped {} of {} rows of {}",
+            probe_len - positions.len(),
+            probe_len,
+            probe.name()
+        );
+        cond.retain_rows(probe_left, &positions);
+        conds[eq_idx] = Some(cond);
+        let file_rows = match probe_rows {
+            Some(rows) => {
+                let rows = rows.iter().collect::<Vec<_>>();
+                positions.iter().map(|pos| rows[*pos]).collect()
+            }
+            None => positions.iter().map(|pos| *pos as u32).collect(),
+        };
+        if probe_left {
+            (Some(file_rows), right_rows)
+        } else {
+            (left_rows, Some(file_rows))
+        }
+    }
+
+    // Builds the join index from a precomputed index in `__join_keys__` instead of hashing. This
+    // is only possible if the constraint is an equality between two columns and the right side of
+    // the join is a file table. `left_rows` and `right_rows` are the rows of each side which
+    // survived `pushdown`, if any were filtered.
+    fn indexed_join(
+        &self,
+        left_rows: Option<&RoaringBitmap>,
+        right_rows: Option<&RoaringBitmap>,
+        exec_params: &ExecParams,
+        exec_stats: &mut ExecStats,
+        pred: Option<&PredNode>,
+    ) -> Option<HashMap<String, Vec<usize>>> {
+        let ((left_col, left_ref), (right_col, right_ref)) = self.col_constraint()?;
+        let left_file_table = left_col.table.upgrade().unwrap();
+        let right_file_table = right_col.table.upgrade().unwrap();
+        if right_ref.id() != right_file_table.id() {
+            return None;
+        }
+        if left_rows.is_some() && !Rc::ptr_eq(left_ref, &self.left) {
+            return None;
+        }
+        let join_keys_path = left_file_table.path.parent()?.join("__join_keys__");
+        let index = JoinIndex::load(&join_keys_path, left_col, right_col)?;
+
+        left_ref.eval_join(exec_params, exec_stats, pred);
+        let left_map = side_map(left_ref, left_rows);
+        let left_file_rows = left_map.get(left_file_table.name())?;
+        let mut pairs = vec![];
+        for (left_idx, left_row) in left_file_rows.iter().enumerate() {
+            if *left_row != NULL_IDX {
+                // Right rows are file rows, which become positions among the surviving rows if
+                // the right side was filtered.
+                pairs.extend(
+                    index
+                        .get(*left_row)
+                        .filter_map(|right_row| match right_rows {
+                            Some(rows) if rows.contains(right_row as u32) => {
+                                Some(rows.rank(right_row as u32) as usize - 1)
+                            }
+                            Some(_) => None,
+                            None => Some(right_row),
+                        })
+                        .map(|right_idx| (left_idx, right_idx)),
+                );
+            }
+        }
+
+        Some(join::build_join_idx(
+            &self.join_type,
+            &left_map,
+            &side_map(right_ref, right_rows),
+            &pairs,
+        ))
+    }
 }
 
 impl fmt::Display for JoinTable {
@@ -837,7 +1229,13 @@ impl fmt::Display for JoinTable {
         write!(
             f,
             "{} JOIN {} ON {}",
-            self.left, self.right, self.constraint
+            self.left,
+            self.right,
+            self.constraints
+                .iter()
+                .map(|constraint| constraint.to_string())
+                .collect::<Vec<_>>()
+                .join(" AND ")
         )
     }
 }
@@ -1,8 +1,8 @@
 use super::config::BLOCK_SIZE;
-use byteorder::{NativeEndian, ReadBytesExt};
-use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use byteorder::{ByteOrder, NativeEndian, ReadBytesExt};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
 use std::alloc::{self, Layout};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::marker::PhantomData;
 use std::mem;
@@ -28,53 +28,77 @@ pub fn sql_pattern_to_regex(s: &str) -> String {
 }
 
 pub fn parse_duration(s: &str) -> Duration {
+    try_parse_duration(s).unwrap_or_else(|| panic!("Could not parse {} as duration", s))
+}
+
+// Parses either `hh:mm` or a number of some unit (e.g., `3 days`), or None if `s` is neither.
+pub fn try_parse_duration(s: &str) -> Option<Duration> {
     let tokens: Vec<&str> = s.split_whitespace().collect();
     if tokens.len() == 1 {
-        assert!(tokens[0].contains(":"));
-        let mut it = tokens[0].split(":");
-        let hour = it
-            .next()
-            .unwrap()
-            .parse::<i64>()
-            .expect(&format!("Could not parse hour portion of {}", tokens[0]));
-        let min = it
-            .next()
-            .unwrap()
-            .parse::<i64>()
-            .expect(&format!("Could not parse minute portion of {}", tokens[0]));
-
-        Duration::minutes(hour * 60 + min)
-    } else {
-        assert_eq!(tokens.len(), 2);
-        let num = tokens[0]
-            .parse()
-            .expect(&format!("Could not parse {} {}", tokens[0], tokens[1]));
+        let mut it = tokens[0].split(':');
+        let hour = it.next()?.parse::<i64>().ok()?;
+        let min = it.next()?.parse::<i64>().ok()?;
+        Some(Duration::minutes(hour * 60 + min))
+    } else if tokens.len() == 2 {
+        let num = tokens[0].parse().ok()?;
         let unit = tokens[1];
         // XXX For simplicity, 1 year is 52 weeks and 1 month is 30 days
         match unit {
-            _ if unit.starts_with("year") => Duration::weeks(num * 52),
-            _ if unit.starts_with("mon") => Duration::days(num * 30),
-            _ if unit.starts_with("week") => Duration::weeks(num),
-            _ if unit.starts_with("day") => Duration::days(num),
-            _ if unit.starts_with("hour") => Duration::hours(num),
-            _ if unit.starts_with("minute") => Duration::minutes(num),
-            _ if unit.starts_with("second") => Duration::seconds(num),
-            _ => {
-                panic!("Don't support unit {}", unit);
-            }
+            _ if unit.starts_with("year") => Some(Duration::weeks(num * 52)),
+            _ if unit.starts_with("mon") => Some(Duration::days(num * 30)),
+            _ if unit.starts_with("week") => Some(Duration::weeks(num)),
+            _ if unit.starts_with("day") => Some(Duration::days(num)),
+            _ if unit.starts_with("hour") => Some(Duration::hours(num)),
+            _ if unit.starts_with("minute") => Some(Duration::minutes(num)),
+            _ if unit.starts_with("second") => Some(Duration::seconds(num)),
+            _ => None,
         }
+    } else {
+        None
     }
 }
 
+// Formats a number of months as years and months, as in Postgres (e.g., `1 year 2 mons`).
+pub fn format_months(months: i32) -> String {
+    let (years, months) = (months / 12, months % 12);
+    let plural = |num: i32| if num.abs() == 1 { "" } else { "s" };
+    match (years, months) {
+        (0, months) => format!("{} mon{}", months, plural(months)),
+        (years, 0) => format!("{} year{}", years, plural(years)),
+        (years, months) => format!(
+            "{} year{} {} mon{}",
+            years,
+            plural(years),
+            months,
+            plural(months)
+        ),
+    }
+}
+
+// Adds `months` to `datetime` by the calendar. A day which does not exist in the resulting month
+// becomes the last day of it (e.g., Jan 31 + 1 month is Feb 28), as in Postgres.
+pub fn add_months(datetime: DateTime<Utc>, months: i32) -> DateTime<Utc> {
+    let date = datetime.naive_utc().date();
+    let month0 = date.year() * 12 + date.month0() as i32 + months;
+    let (year, month) = (month0.div_euclid(12), month0.rem_euclid(12) as u32 + 1);
+    let day = (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap();
+    DateTime::from_utc(day.and_time(datetime.naive_utc().time()), Utc)
+}
+
 pub fn parse_datetime(s: &str) -> DateTime<Utc> {
-    DateTime::from_utc(
-        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
-            .or_else(|_| {
-                NaiveDate::parse_from_str(s, "%Y%m%d").and_then(|date| Ok(date.and_hms(0, 0, 0)))
-            })
-            .expect(&format!("Could not parse {} as datetime", s)),
-        Utc,
-    )
+    try_parse_datetime(s).unwrap_or_else(|| panic!("Could not parse {} as datetime", s))
+}
+
+// Parses `yyyy-mm-dd hh:mm:ss`, `yyyy-mm-dd` or `yyyymmdd`, or None if `s` is none of them.
+pub fn try_parse_datetime(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|date| date.and_hms(0, 0, 0)))
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y%m%d").map(|date| date.and_hms(0, 0, 0)))
+        .ok()
+        .map(|datetime| DateTime::from_utc(datetime, Utc))
 }
 
 // Works only on int-types
@@ -171,6 +195,13 @@ impl<'a, T: CursorReadable> BufFile<'a, T> {
     //}
 }
 
+// Reads a whole file of packed native-endian u32s, e.g. row indices.
+pub fn read_u32_file(path: &Path) -> Vec<u32> {
+    let bytes = fs::read(path).unwrap_or_else(|_| panic!("Could not read {:?}", path));
+    assert_eq!(bytes.len() % 4, 0, "{:?} is not a u32 file", path);
+    bytes.chunks(4).map(NativeEndian::read_u32).collect()
+}
+
 pub unsafe fn alloc_aligned_buf<'a>(buf_size: usize) -> &'a mut [u8] {
     slice::from_raw_parts_mut(
         alloc::alloc(
@@ -96,3 +96,62 @@ fn approx_better() {
         approx_exec_stats.pred_only_time_ms, approx_exec_stats.num_preds_evaled
     );
 }
+
+#[test]
+fn limit_early_termination() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+    let query = parser
+        .parse(
+            "select a, b from table1 where (a < 0.5) and (b < 0.3 or (c < 0.5 and d < 0.9)) limit 20",
+            &Default::default(),
+        )
+        .unwrap();
+
+    let selectivities = query.filter.as_ref().and_then(|f| {
+        let mut selectivities = HashMap::new();
+        query_utils::estimate_selectivities(&f, &mut selectivities);
+        Some(selectivities)
+    });
+
+    let mut exec = Executor::new(&db, selectivities, None);
+
+    let exec_params = Default::default();
+    let mut exec_stats = ExecStats::new();
+    let result =
+        utils::process_ordered_dbresults(exec.run(query.clone(), &exec_params, &mut exec_stats));
+    assert_eq!(20, result.len());
+    assert_eq!(10000, exec_stats.num_scanned_records);
+
+    for approx_opt_type in vec![
+        ApproxOptType::NoApproxOpt,
+        ApproxOptType::Tdacb,
+        ApproxOptType::BDCWithBestD,
+    ] {
+        let mut chunked_exec_params: ExecParams = Default::default();
+        chunked_exec_params.approx_opt_type = approx_opt_type.clone();
+        chunked_exec_params.limit_chunk_size = Some(100);
+        let mut chunked_exec_stats = ExecStats::new();
+        let chunked_result = utils::process_ordered_dbresults(exec.run(
+            query.clone(),
+            &chunked_exec_params,
+            &mut chunked_exec_stats,
+        ));
+        assert_eq!(result, chunked_result, "{:?}", approx_opt_type);
+        assert!(chunked_exec_stats.num_scanned_records < 10000);
+        assert_eq!(0, chunked_exec_stats.num_scanned_records % 100);
+    }
+
+    // The rows have to be sorted first, so they are all scanned.
+    let query = parser
+        .parse(
+            "select a from table1 where (a < 0.5) and (b < 0.3 or c < 0.5) order by a limit 20",
+            &Default::default(),
+        )
+        .unwrap();
+    let mut chunked_exec_params: ExecParams = Default::default();
+    chunked_exec_params.limit_chunk_size = Some(100);
+    let mut chunked_exec_stats = ExecStats::new();
+    exec.run(query, &chunked_exec_params, &mut chunked_exec_stats);
+    assert_eq!(10000, chunked_exec_stats.num_scanned_records);
+}
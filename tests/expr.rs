@@ -0,0 +1,565 @@
+mod utils;
+
+use chameleon::{ExecError, ExecStats, Executor, ExprCreateError, ParseError, Parser, DB};
+use chrono::{Duration, TimeZone, Utc};
+use std::collections::HashSet;
+use std::path::Path;
+use utils::{DBVal, ResultSet};
+
+const DB_PATH: &str = "data/test-data/null-test";
+
+// table1 is:
+//    a | b    | c
+//    1 | x    | 1.5
+// NULL | y    | 2.5
+//    3 | NULL | NULL
+// NULL | x    | 4.5
+//    5 | y    | NULL
+//    6 | NULL | 6.5
+
+fn int(val: Option<i32>) -> DBVal {
+    val.map_or(DBVal::Null, DBVal::Int)
+}
+
+fn string(val: Option<&str>) -> DBVal {
+    val.map_or(DBVal::Null, |s| DBVal::Str(s.to_string()))
+}
+
+fn double(val: Option<f64>) -> DBVal {
+    val.map_or(DBVal::Null, DBVal::Double)
+}
+
+fn new_group() -> Vec<DBVal> {
+    vec![]
+}
+
+#[test]
+fn case_expressions() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(),
+            vec![
+                vec![int(Some(1)), string(Some("low"))],
+                vec![int(None), string(Some("high"))],
+                vec![int(Some(3)), string(Some("mid"))],
+                vec![int(None), string(Some("high"))],
+                vec![int(Some(5)), string(Some("mid"))],
+                vec![int(Some(6)), string(Some("high"))],
+            ]
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        )],
+        // The simple form, with results of different types and without an ELSE.
+        vec![(
+            new_group(),
+            vec![
+                vec![int(Some(1)), double(Some(2.))],
+                vec![int(None), double(Some(2.5))],
+                vec![int(Some(3)), double(None)],
+                vec![int(None), double(None)],
+                vec![int(Some(5)), double(None)],
+                vec![int(Some(6)), double(None)],
+            ]
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        )],
+        // Only the rows left by the filter are evaluated.
+        vec![(
+            new_group(),
+            vec![
+                vec![int(Some(3)), string(Some("b"))],
+                vec![int(Some(5)), string(Some("y"))],
+                vec![int(Some(6)), string(Some("b"))],
+            ]
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        )],
+        vec![(
+            new_group(),
+            vec![
+                vec![int(None), string(Some("x"))],
+                vec![int(Some(5)), string(Some("y"))],
+                vec![int(Some(6)), string(None)],
+            ]
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select a, case when a < 3 then 'low' when a < 6 then 'mid' else 'high' end from table1",
+        "select a, case b when 'x' then a + 1 when 'y' then c end from table1",
+        "select a, case when b is null then 'b' else b end from table1 where a > 2",
+        "select a, b from table1 where case when b = 'x' then c > 2 else a > 4 end",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn case_in_aggregates() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group(s: &str) -> Vec<DBVal> {
+        vec![DBVal::Str(s.to_string())]
+    }
+
+    fn new_rows(rows: &[Vec<DBVal>]) -> HashSet<Vec<DBVal>> {
+        rows.iter().cloned().collect()
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![
+            (new_group("x"), new_rows(&[vec![DBVal::Long(0)]])),
+            (new_group("y"), new_rows(&[vec![DBVal::Long(1)]])),
+        ],
+        // Conditions and results which aggregate are evaluated per group
+        vec![
+            (
+                new_group("x"),
+                new_rows(&[vec![string(Some("few")), DBVal::Int(1)]]),
+            ),
+            (
+                new_group("y"),
+                new_rows(&[vec![string(Some("few")), DBVal::Int(1)]]),
+            ),
+        ],
+        vec![
+            (new_group("x"), new_rows(&[vec![int(None)]])),
+            (new_group("y"), new_rows(&[vec![int(Some(5))]])),
+        ],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select sum(case when a > 2 then 1 else 0 end) from table1 \
+         where b is not null group by b",
+        "select case when count(a) > 1 then 'many' else 'few' end, count(a) from table1 \
+         where b is not null group by b",
+        "select case when sum(c) < 5 then max(a) end from table1 \
+         where b is not null group by b",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn case_branches_not_taken() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    // A result which no row takes is never evaluated, so its cast cannot fail.
+    let expected: ResultSet = vec![(
+        new_group(),
+        vec![
+            vec![int(Some(1)), int(None)],
+            vec![int(None), int(None)],
+            vec![int(Some(3)), int(None)],
+            vec![int(Some(5)), int(None)],
+            vec![int(Some(6)), int(None)],
+        ]
+        .into_iter()
+        .collect::<HashSet<_>>(),
+    )]
+    .into_iter()
+    .collect();
+    let queries = [
+        "select a, case when a > 10 then cast(b as int) end from table1",
+        "select a, case when 1 > 2 then cast(b as int) when a > 10 then 0 end from table1",
+    ];
+    for query in &queries {
+        let parsed = parser.parse(query, &Default::default()).unwrap();
+        let result = exec
+            .try_run(parsed, &Default::default(), &mut ExecStats::new())
+            .unwrap();
+        assert_eq!(expected, utils::process_dbresults(result), "{}", query);
+    }
+    // Without a condition which depends on the row, the CASE is a constant NULL.
+    let query = "select case when 1 > 2 then cast(b as int) end from table1";
+    let parsed = parser.parse(query, &Default::default()).unwrap();
+    let result = exec
+        .try_run(parsed, &Default::default(), &mut ExecStats::new())
+        .unwrap();
+    assert!(result[0].is_null(&[], 0), "{}", query);
+
+    let queries = [
+        "select case when a > 2 then 'x' else 1 end from table1",
+        "select case b when 'x' then a when 'y' then b end from table1",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(
+                result,
+                Err(ParseError::ProjectionError(ExprCreateError::CaseTypeError(
+                    ..
+                )))
+            ),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn null_handling_functions() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    let expected: Vec<ResultSet> = vec![
+        vec![
+            vec![double(Some(1.5)), DBVal::Long(1)],
+            vec![double(Some(2.5)), DBVal::Long(0)],
+            vec![double(None), DBVal::Long(3)],
+            vec![double(Some(4.5)), DBVal::Long(0)],
+            vec![double(None), DBVal::Long(5)],
+            vec![double(Some(6.5)), DBVal::Long(6)],
+        ],
+        vec![
+            vec![int(Some(1)), string(Some("x"))],
+            vec![int(None), string(Some("y"))],
+            vec![int(Some(3)), string(Some("none"))],
+            vec![int(None), string(Some("x"))],
+            vec![int(Some(5)), string(Some("y"))],
+            vec![int(Some(6)), string(Some("none"))],
+        ],
+        vec![
+            vec![int(Some(1)), string(None)],
+            vec![int(None), string(Some("y"))],
+            vec![int(Some(3)), string(None)],
+            vec![int(None), string(None)],
+            vec![int(Some(5)), string(Some("y"))],
+            vec![int(Some(6)), string(None)],
+        ],
+        // NULLs are skipped by greatest and least.
+        vec![
+            vec![int(Some(1)), double(Some(1.5))],
+            vec![int(None), double(Some(2.5))],
+            vec![int(Some(3)), double(Some(3.))],
+            vec![int(None), double(Some(4.5))],
+            vec![int(Some(5)), double(Some(5.))],
+            vec![int(Some(6)), double(Some(6.5))],
+        ],
+        vec![
+            vec![int(Some(1)), double(Some(1.))],
+            vec![int(None), double(Some(2.))],
+            vec![int(Some(3)), double(Some(2.))],
+            vec![int(Some(5)), double(Some(2.))],
+            vec![int(Some(6)), double(Some(2.))],
+        ],
+        vec![vec![int(None)], vec![int(Some(5))], vec![int(Some(6))]],
+    ]
+    .into_iter()
+    .map(|rows| {
+        vec![(new_group(), rows.into_iter().collect::<HashSet<_>>())]
+            .into_iter()
+            .collect()
+    })
+    .collect();
+
+    let queries = [
+        "select c, coalesce(a, 0) from table1",
+        "select a, coalesce(b, 'none') from table1",
+        "select a, nullif(b, 'x') from table1",
+        "select a, greatest(a, c) from table1",
+        "select a, least(a, c, 2) from table1",
+        "select a from table1 where coalesce(a, 4) > 3",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn casts() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    let expected: Vec<ResultSet> = vec![
+        vec![
+            vec![int(Some(1)), int(Some(2))],
+            vec![int(None), int(Some(3))],
+            vec![int(Some(3)), int(None)],
+            vec![int(None), int(Some(5))],
+            vec![int(Some(5)), int(None)],
+            vec![int(Some(6)), int(Some(7))],
+        ],
+        vec![
+            vec![int(Some(3)), string(Some("3"))],
+            vec![int(Some(5)), string(Some("5"))],
+            vec![int(Some(6)), string(Some("6"))],
+        ],
+        vec![vec![int(Some(5))], vec![int(Some(6))]],
+        vec![vec![
+            int(Some(1)),
+            DBVal::DateTime(Utc.ymd(1998, 12, 1).and_hms(0, 0, 0)),
+        ]],
+        vec![vec![
+            int(Some(1)),
+            DBVal::DateTime(Utc.ymd(2020, 1, 2).and_hms(0, 0, 0)),
+        ]],
+        // The values of NULLs are never converted.
+        vec![vec![int(Some(3)), int(None)], vec![int(Some(6)), int(None)]],
+    ]
+    .into_iter()
+    .map(|rows| {
+        vec![(new_group(), rows.into_iter().collect::<HashSet<_>>())]
+            .into_iter()
+            .collect()
+    })
+    .collect();
+
+    let queries = [
+        "select a, cast(c as int) from table1",
+        "select a, cast(a as varchar) from table1 where a > 2",
+        "select a from table1 where cast(a as double) / 2 > 2",
+        "select a, '1998-12-01'::date from table1 where a = 1",
+        "select a, cast('2020-01-02 10:30:00' as date) from table1 where a = 1",
+        "select a, cast(b as int) from table1 where b is null",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn bad_cast() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    // Constants, and casts between types which cannot be converted at all, fail to parse.
+    let queries = [
+        "select cast('x' as int) from table1",
+        "select cast(c as timestamp) from table1",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(
+                result,
+                Err(ParseError::ProjectionError(ExprCreateError::CastError(_)))
+            ),
+            "{}",
+            query
+        );
+    }
+    let queries = [
+        "select cast(a as time) from table1",
+        "select cast(b as uuid) from table1",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(
+                result,
+                Err(ParseError::ProjectionError(
+                    ExprCreateError::UnsupportedCast(_)
+                ))
+            ),
+            "{}",
+            query
+        );
+    }
+    let result = parser.parse(
+        "select a from table1 where a = cast('x' as int)",
+        &Default::default(),
+    );
+    assert!(matches!(
+        result,
+        Err(ParseError::FilterError(ExprCreateError::CastError(_)))
+    ));
+
+    // Values which cannot be converted fail the query when it is run.
+    let queries = [
+        "select cast(b as int) from table1",
+        "select a from table1 where cast(b as int) > 1",
+    ];
+    for query in &queries {
+        let parsed = parser.parse(query, &Default::default()).unwrap();
+        match exec.try_run(parsed, &Default::default(), &mut ExecStats::new()) {
+            Err(ExecError::CastError(err)) => assert_eq!(
+                "Cannot cast Str value \"x\" to Int",
+                err.to_string(),
+                "{}",
+                query
+            ),
+            result => panic!("{} gave {:?}", query, result.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn date_arithmetic() {
+    // table7.id is 1, 3, 5, 7, 100 and table7.day is 1970-01-01, 1970-01-02, 1970-01-03,
+    // 1970-01-02, 1970-01-06.
+    let db = DB::new(Path::new("data/test-data/join-test"));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn date(year: i32, month: u32, day: u32) -> DBVal {
+        DBVal::DateTime(Utc.ymd(year, month, day).and_hms(0, 0, 0))
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![vec![DBVal::Long(5)], vec![DBVal::Long(100)]],
+        vec![
+            vec![DBVal::Long(1)],
+            vec![DBVal::Long(3)],
+            vec![DBVal::Long(7)],
+        ],
+        vec![vec![DBVal::Long(3), date(1970, 2, 2)]],
+        vec![vec![DBVal::Long(3), date(1970, 1, 4)]],
+        // Months and years are added by the calendar.
+        vec![vec![
+            date(2020, 2, 29),
+            date(2021, 2, 28),
+            date(2019, 11, 30),
+        ]],
+        vec![vec![DBVal::Long(100), date(1968, 11, 6)]],
+        vec![vec![
+            DBVal::Months(1),
+            DBVal::Months(14),
+            DBVal::Months(-14),
+            DBVal::Duration(Duration::minutes(24 * 60 + 150)),
+        ]],
+        vec![vec![DBVal::Long(3), date(1971, 3, 2)]],
+    ]
+    .into_iter()
+    .map(|rows| {
+        vec![(new_group(), rows.into_iter().collect::<HashSet<_>>())]
+            .into_iter()
+            .collect()
+    })
+    .collect();
+
+    let queries = [
+        "select table7.id from table7 where table7.day >= date '1970-01-03'",
+        "select table7.id from table7 \
+         where table7.day < date '1970-01-06' - interval '3' day",
+        "select table7.id, table7.day + interval '1' month from table7 where table7.id = 3",
+        "select table7.id, interval '2' day + table7.day from table7 where table7.id = 3",
+        "select date '2020-01-31' + interval '1' month, \
+         date '2020-02-29' + interval '1' year, \
+         timestamp '2019-12-31 00:00:00' - interval '1' month \
+         from table7 where table7.id = 1",
+        "select table7.id, table7.day - interval '1-2' year to month \
+         from table7 where table7.id = 100",
+        "select interval '1' month, \
+         interval '1' year + interval '2' month, \
+         interval '-1-2' year to month, \
+         interval '1 02:30' day to minute \
+         from table7 where table7.id = 1",
+        "select table7.id, table7.day + (interval '1' year + interval '2' month) \
+         from table7 where table7.id = 3",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+
+    // Values which do not fit the type or fields of the literal fail to parse.
+    let queries = [
+        "select date '2020-13-01' from table7",
+        "select interval '1 day' day from table7",
+        "select interval '1:30' year to month from table7",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(
+                result,
+                Err(ParseError::ProjectionError(
+                    ExprCreateError::InvalidLiteral(_)
+                ))
+            ),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn in_list_errors() {
+    let db = DB::new(Path::new("data/test-data/join-test"));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    let query = "select table7.id from table7 where table7.day in ('1970-01-02', '1970-01-04')";
+    let parsed = parser.parse(query, &Default::default()).unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(parsed, &Default::default(), &mut exec_stats));
+    let expected: ResultSet = vec![(
+        new_group(),
+        vec![vec![DBVal::Long(3)], vec![DBVal::Long(7)]]
+            .into_iter()
+            .collect(),
+    )]
+    .into_iter()
+    .collect();
+    assert_eq!(expected, result, "{}", query);
+
+    let queries = [
+        "select table7.id from table7 where table7.id in (1, 'a')",
+        "select table7.id from table7 where table7.flag not in (1)",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(
+                result,
+                Err(ParseError::FilterError(ExprCreateError::InListTypeError(
+                    ..
+                )))
+            ),
+            "{}",
+            query
+        );
+    }
+
+    let query = "select table7.id from table7 where table7.day in ('not-a-date')";
+    let result = parser.parse(query, &Default::default());
+    assert!(
+        matches!(
+            result,
+            Err(ParseError::FilterError(ExprCreateError::InvalidLiteral(_)))
+        ),
+        "{}",
+        query
+    );
+}
@@ -1,7 +1,7 @@
 mod utils;
 
-use chameleon::{ApproxOptType, ExecParams, ExecStats, Executor, Parser, DB};
-use std::collections::HashSet;
+use chameleon::{query_utils, ApproxOptType, ExecParams, ExecStats, Executor, Parser, DB};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use utils::{DBVal, ResultSet};
 
@@ -125,3 +125,173 @@ fn simple_like() {
     let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
     assert_eq!(expected, result);
 }
+
+#[test]
+fn simple_in_list() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, i32)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(row.0), DBVal::Int(row.1)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[(4, 4), (-3, 0)]))]
+        .into_iter()
+        .collect();
+
+    let query = "select a, c from table1 where b in ('ivysaur', 'charizard', 'pikachu')";
+    let query = parser.parse(query, &Default::default()).unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn long_not_in_list() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, i32)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(row.0), DBVal::Int(row.1)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[(6, 5), (-3, 0)]))]
+        .into_iter()
+        .collect();
+
+    let query = "select a, c from table1 where a not in (5, 4, 3, 2, 1, 0, 10, 11, 12)";
+    let query = parser.parse(query, &Default::default()).unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn in_list_in_disjunction() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, i32)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(row.0), DBVal::Int(row.1)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[(5, 7), (6, 5), (-3, 0)]))]
+        .into_iter()
+        .collect();
+
+    let query =
+        "select a, c from table1 where (e in (8, 9) and c >= 0) or (f in ('caterpie') and a > 5)";
+    let query = parser.parse(query, &Default::default()).unwrap();
+
+    let selectivities = query.filter.as_ref().and_then(|f| {
+        let mut selectivities = HashMap::new();
+        query_utils::estimate_selectivities(&f, &mut selectivities);
+        Some(selectivities)
+    });
+    assert!(selectivities
+        .as_ref()
+        .unwrap()
+        .contains_key("table1.e IN (8, 9)"));
+    let mut exec = Executor::new(&db, selectivities, None);
+
+    for approx_opt_type in &[
+        ApproxOptType::OnePredLookahead,
+        ApproxOptType::Tdacb,
+        ApproxOptType::BDCWithBestD,
+    ] {
+        let mut exec_params: ExecParams = Default::default();
+        exec_params.approx_opt_type = approx_opt_type.clone();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query.clone(), &exec_params, &mut exec_stats));
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn simple_between() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, i32)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(row.0), DBVal::Int(row.1)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[(4, 4), (3, 3), (6, 5)]))]
+        .into_iter()
+        .collect();
+
+    let query = "select a, c from table1 where c between 3 and 5";
+    let query = parser.parse(query, &Default::default()).unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn mixed_type_not_between() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, i32)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(row.0), DBVal::Int(row.1)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[(5, 7), (3, 3), (6, 5)]))]
+        .into_iter()
+        .collect();
+
+    let query = "select a, c from table1 where d not between 0.4 and 0.7 and a between 0 and 6.5";
+    let query = parser.parse(query, &Default::default()).unwrap();
+    assert_eq!(
+        "(table1.d NOT BETWEEN 0.4 AND 0.7) AND (table1.a BETWEEN 0 AND 6.5)",
+        query.filter.as_ref().unwrap().to_string()
+    );
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+}
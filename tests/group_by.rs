@@ -1,6 +1,9 @@
 mod utils;
 
-use chameleon::{ApproxOptType, ExecParams, ExecStats, Executor, Parser, DB};
+use chameleon::{
+    ApproxOptType, DBCol, ExecParams, ExecStats, Executor, ExprCreateError, ParseError, Parser, DB,
+};
+use chrono::{DateTime, TimeZone, Utc};
 use std::collections::HashSet;
 use std::path::Path;
 use utils::{DBVal, ResultSet};
@@ -230,3 +233,304 @@ fn multi_groups() {
     let result = utils::process_dbresults(exec.run(query, &exec_params, &mut exec_stats));
     assert_eq!(expected, result);
 }
+
+#[test]
+fn having() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group(s: &str) -> Vec<DBVal> {
+        vec![DBVal::Str(s.to_string())]
+    }
+
+    fn new_rows(rows: &[i32]) -> HashSet<Vec<DBVal>> {
+        rows.iter().map(|row| vec![DBVal::Int(*row)]).collect()
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![
+            (new_group("a"), new_rows(&[17])),
+            (new_group("b"), new_rows(&[5])),
+        ],
+        // Aggregates which are not projected
+        vec![(new_group("b"), new_rows(&[6]))],
+        // Group keys
+        vec![(new_group("c"), new_rows(&[3]))],
+        vec![],
+        // Without a GROUP BY, the whole table is the group
+        vec![(vec![], new_rows(&[25]))],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select sum(a) from table1 group by b having sum(a) > 4",
+        "select max(a) from table1 group by b having count(a) > 1 and min(a) < 0",
+        "select a from table1 group by b having b = 'c'",
+        "select sum(a) from table1 group by b having sum(a) > 100",
+        "select sum(a) from table1 having sum(a) > 20",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let exec_params = Default::default();
+        let mut exec_stats = ExecStats::new();
+        let result = utils::process_dbresults(exec.run(query, &exec_params, &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn having_errors() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    let queries = [
+        "select sum(a) from table1 group by b having sum(z) > 4",
+        "select sum(a) from table1 group by b having array_agg(a) > 4",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(result, Err(ParseError::HavingError(_))),
+            "{}",
+            query
+        );
+    }
+    let result = parser.parse(
+        "select sum(a) from table1 having cast('x' as int) > 1",
+        &Default::default(),
+    );
+    assert!(matches!(
+        result,
+        Err(ParseError::HavingError(ExprCreateError::CastError(_)))
+    ));
+}
+
+#[test]
+fn distinct() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group(s: &str) -> Vec<DBVal> {
+        vec![DBVal::Str(s.to_string())]
+    }
+
+    fn new_rows(rows: &[&[i32]]) -> HashSet<Vec<DBVal>> {
+        rows.iter()
+            .map(|row| row.iter().map(|val| DBVal::Int(*val)).collect())
+            .collect()
+    }
+
+    fn new_str_rows(rows: &[&str]) -> HashSet<Vec<DBVal>> {
+        rows.iter()
+            .map(|row| vec![DBVal::Str(row.to_string())])
+            .collect()
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![
+            (new_group("a"), new_rows(&[&[2]])),
+            (new_group("b"), new_rows(&[&[2]])),
+            (new_group("c"), new_rows(&[&[1]])),
+        ],
+        vec![
+            (new_group("a"), new_rows(&[&[13, 13]])),
+            (new_group("b"), new_rows(&[&[3, 6]])),
+            (new_group("d"), new_rows(&[&[6, 6]])),
+        ],
+        vec![(vec![], new_str_rows(&["a", "b", "d"]))],
+        vec![(vec![], new_str_rows(&["a"]))],
+        // Duplicates across groups are dropped as well
+        vec![(vec![], new_rows(&[&[13], &[6]]))],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select count(distinct d) from table1 group by b",
+        "select sum(distinct a), sum(a) from table1 group by d",
+        "select distinct d from table1",
+        "select distinct d from table1 where a < 3",
+        "select distinct sum(a) from table1 group by d",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let exec_params = Default::default();
+        let mut exec_stats = ExecStats::new();
+        let result = utils::process_dbresults(exec.run(query, &exec_params, &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn unsupported_distinct() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    let queries = [
+        "select abs(distinct a) from table1",
+        "select a from table1 where abs(distinct a) > 1",
+        "select count(distinct a, b) from table1",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(
+                result,
+                Err(ParseError::ProjectionError(
+                    ExprCreateError::UnsupportedDistinct(_)
+                )) | Err(ParseError::FilterError(
+                    ExprCreateError::UnsupportedDistinct(_)
+                ))
+            ),
+            "{}",
+            query
+        );
+    }
+}
+
+// table7 of join-test is:
+//  id | day        | flag  | score
+//   1 | 1970-01-01 | true  | 1.5
+//   3 | 1970-01-02 | false | 3.0
+//   5 | 1970-01-03 | true  | 8.0
+//   7 | 1970-01-02 | true  | -3.0
+// 100 | 1970-01-06 | false | 6.0
+#[test]
+fn group_by_all_types() {
+    let db = DB::new(Path::new("data/test-data/join-test"));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.ymd(1970, 1, day).and_hms(0, 0, 0)
+    }
+
+    fn new_rows(rows: &[i64]) -> HashSet<Vec<DBVal>> {
+        rows.iter().map(|row| vec![DBVal::Long(*row)]).collect()
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![
+            (vec![DBVal::DateTime(day(1))], new_rows(&[1])),
+            (vec![DBVal::DateTime(day(2))], new_rows(&[10])),
+            (vec![DBVal::DateTime(day(3))], new_rows(&[5])),
+            (vec![DBVal::DateTime(day(6))], new_rows(&[100])),
+        ],
+        vec![
+            (vec![DBVal::Double(1.5)], new_rows(&[1])),
+            (vec![DBVal::Double(3.)], new_rows(&[3])),
+            (vec![DBVal::Double(8.)], new_rows(&[5])),
+            (vec![DBVal::Double(-3.)], new_rows(&[7])),
+            (vec![DBVal::Double(6.)], new_rows(&[100])),
+        ],
+        vec![
+            (vec![DBVal::Bool(true), DBVal::Bool(true)], new_rows(&[5])),
+            (
+                vec![DBVal::Bool(false), DBVal::Bool(true)],
+                new_rows(&[103]),
+            ),
+            (vec![DBVal::Bool(true), DBVal::Bool(false)], new_rows(&[8])),
+        ],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select sum(id) from table7 group by day",
+        "select sum(id) from table7 group by score",
+        "select sum(id) from table7 group by flag, score > 2",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let exec_params = Default::default();
+        let mut exec_stats = ExecStats::new();
+        let result = utils::process_dbresults(exec.run(query, &exec_params, &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+
+    // The groups are always visited in the order of their keys
+    let query = parser
+        .parse(
+            "select sum(id) from table7 group by day",
+            &Default::default(),
+        )
+        .unwrap();
+    let results = exec.run(query, &Default::default(), &mut ExecStats::new());
+    assert_eq!(
+        vec![
+            vec![DBCol::DateTime(vec![day(1)])],
+            vec![DBCol::DateTime(vec![day(2)])],
+            vec![DBCol::DateTime(vec![day(3)])],
+            vec![DBCol::DateTime(vec![day(6)])],
+        ],
+        results[0].groups().into_iter().cloned().collect::<Vec<_>>()
+    );
+}
+
+// table1 of null-test is:
+//    a | b    | c
+//    1 | x    | 1.5
+// NULL | y    | 2.5
+//    3 | NULL | NULL
+// NULL | x    | 4.5
+//    5 | y    | NULL
+//    6 | NULL | 6.5
+#[test]
+fn float_keys() {
+    let db = DB::new(Path::new("data/test-data/null-test"));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn int(val: i32) -> DBVal {
+        DBVal::Int(val)
+    }
+
+    let expected: Vec<Vec<Vec<DBVal>>> = vec![
+        vec![
+            vec![DBVal::Double(1.5), int(1)],
+            vec![DBVal::Double(2.5), int(0)],
+            vec![DBVal::Double(4.5), int(0)],
+            vec![DBVal::Double(6.5), int(1)],
+            vec![DBVal::Null, int(2)],
+        ],
+        // c - 3 is negative for the first two rows, so their keys are -0, the same as 0.
+        vec![vec![int(4), int(2)], vec![int(0), int(2)]],
+        // c / 0 - c / 0 is NaN for every non-NULL c, and all NaNs are one group.
+        vec![vec![int(2), int(7)], vec![int(2), int(8)]],
+    ];
+
+    let queries = [
+        "select c, count(a) from table1 group by c order by c",
+        "select count(c), count(a) from table1 group by (c - 3) * 0 order by 1 desc",
+        "select count(a), sum(a) from table1 group by c / 0 - c / 0 order by 2",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_ordered_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+
+    // NaN is not equal to itself, so the keys are checked separately.
+    let query = "select c / 0 - c / 0, count(a) from table1 group by c / 0 - c / 0 order by 1";
+    let parsed = parser.parse(query, &Default::default()).unwrap();
+    let result = utils::process_ordered_dbresults(exec.run(
+        parsed,
+        &Default::default(),
+        &mut ExecStats::new(),
+    ));
+    assert_eq!(2, result.len(), "{}", query);
+    assert!(
+        matches!(result[0][..], [DBVal::Double(key), DBVal::Int(2)] if key.is_nan()),
+        "{}",
+        query
+    );
+    assert_eq!(vec![DBVal::Null, int(2)], result[1], "{}", query);
+}
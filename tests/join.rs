@@ -0,0 +1,1138 @@
+mod utils;
+
+use chameleon::{ExecParams, ExecStats, Executor, ParseError, Parser, Table, DB};
+use chrono::{TimeZone, Utc};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use utils::{DBVal, ResultSet};
+
+const DB_PATH: &str = "data/test-data/join-test";
+
+fn int(val: Option<i32>) -> DBVal {
+    val.map_or(DBVal::Null, DBVal::Int)
+}
+
+#[test]
+fn inner_join() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, i32)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(row.0), DBVal::Int(row.1)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[(3, 3), (5, 2), (5, 10), (5, 30)]))]
+        .into_iter()
+        .collect();
+
+    let query = parser
+        .parse(
+            "select table1.a, table3.f from table1 join table3 on table1.id = table3.fid",
+            &Default::default(),
+        )
+        .unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn outer_joins() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<i32>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), int(row.1)]);
+        }
+        records
+    }
+
+    let mut expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(3), Some(3)),
+                (Some(5), Some(2)),
+                (Some(5), Some(10)),
+                (Some(5), Some(30)),
+                (Some(1), None),
+                (Some(2), None),
+                (Some(3), None),
+                (Some(-3), None),
+                (Some(6), None),
+                (Some(8), None),
+            ]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(3), Some(3)),
+                (Some(5), Some(2)),
+                (Some(5), Some(10)),
+                (Some(5), Some(30)),
+                (None, Some(-4)),
+            ]),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+    // Reversing the constraint's operands gives the same results
+    expected.extend(expected.clone());
+
+    let queries = [
+        "select table1.a, table3.f from table1 left outer join table3 on table1.id = table3.fid",
+        "select table1.a, table3.f from table1 right outer join table3 on table1.id = table3.fid",
+        "select table1.a, table3.f from table1 left outer join table3 on table3.fid = table1.id",
+        "select table1.a, table3.f from table1 right outer join table3 on table3.fid = table1.id",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result);
+    }
+}
+
+#[test]
+fn filter_on_outer_join() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<i32>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), int(row.1)]);
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(1), None),
+                (Some(2), None),
+                (Some(3), None),
+                (Some(-3), None),
+                (Some(6), None),
+                (Some(8), None),
+            ]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[(Some(5), Some(10)), (Some(5), Some(30))]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[(Some(5), Some(10)), (Some(5), Some(30)), (Some(8), None)]),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select table1.a, table3.f from table1 left join table3 on table1.id = table3.fid \
+         where table3.f is null",
+        "select table1.a, table3.f from table1 left join table3 on table1.id = table3.fid \
+         where table3.f > 5",
+        "select table1.a, table3.f from table1 left join table3 on table1.id = table3.fid \
+         where table3.f > 5 or table1.a > 7",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result);
+    }
+}
+
+#[test]
+fn join_index_orientations() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<i32>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), int(row.1)]);
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(3), Some(3)),
+                (Some(5), Some(2)),
+                (Some(5), Some(10)),
+                (Some(5), Some(30)),
+                (Some(1), None),
+                (Some(2), None),
+                (Some(3), None),
+                (Some(-3), None),
+                (Some(6), None),
+                (Some(8), None),
+            ]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(3), Some(3)),
+                (Some(5), Some(2)),
+                (Some(5), Some(10)),
+                (Some(5), Some(30)),
+                (None, Some(-4)),
+            ]),
+        )],
+        // table2 has no join index with table3, so this is hash joined.
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(3), Some(2)),
+                (Some(3), Some(10)),
+                (Some(3), Some(30)),
+                (Some(2), Some(3)),
+                (Some(10), Some(-4)),
+            ]),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select table1.a, table3.f from table1 left join table3 on table3.fid = table1.id",
+        "select table1.a, table3.f from table3 left join table1 on table3.fid = table1.id",
+        "select table2.f, table3.f from table2 join table3 on table2.f = table3.fid",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result);
+    }
+}
+
+#[test]
+fn multi_way_join() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, f32)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(row.0), DBVal::Float(row.1)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(
+        new_group(),
+        new_rows(&[(3, 6.7), (2, 8.95), (10, 8.95), (30, 8.95)]),
+    )]
+    .into_iter()
+    .collect();
+
+    let query = parser
+        .parse(
+            "select table3.f, table6.e from table1 join table3 on table1.id = table3.fid \
+             join table6 on table1.id = table6.fid",
+            &Default::default(),
+        )
+        .unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+}
+
+// Copies the test DB into a fresh temporary directory, leaving out the prebuilt join indexes.
+fn copy_db_without_join_keys(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("chameleon-{}-{}", name, std::process::id()));
+    if path.exists() {
+        fs::remove_dir_all(&path).unwrap();
+    }
+    for table in fs::read_dir(DB_PATH).unwrap() {
+        let table = table.unwrap();
+        if table.file_name() == "__join_keys__" {
+            continue;
+        }
+        fs::create_dir_all(path.join(table.file_name())).unwrap();
+        for file in fs::read_dir(table.path()).unwrap() {
+            let file = file.unwrap();
+            fs::copy(
+                file.path(),
+                path.join(table.file_name()).join(file.file_name()),
+            )
+            .unwrap();
+        }
+    }
+    path
+}
+
+#[test]
+fn persist_join_index() {
+    let db_path = copy_db_without_join_keys("persist-join-index");
+    let query = "select table1.a, table2.f from table1 join table2 on table1.id = table2.fid";
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, i32)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(row.0), DBVal::Int(row.1)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(
+        new_group(),
+        new_rows(&[
+            (1, 3),
+            (1, -4),
+            (2, 2),
+            (2, 10),
+            (2, 30),
+            (-3, -20),
+            (6, 0),
+            (8, 7),
+        ]),
+    )]
+    .into_iter()
+    .collect();
+
+    let exec_params = ExecParams {
+        persist_join_index: true,
+        ..Default::default()
+    };
+    {
+        let db = DB::new(&db_path);
+        let mut exec = Executor::new(&db, None, None);
+        let parser = Parser::new(&db);
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result = utils::process_dbresults(exec.run(query, &exec_params, &mut exec_stats));
+        assert_eq!(expected, result);
+    }
+
+    // The written index matches the prebuilt one.
+    for suffix in &[
+        "left",
+        "left.startpts",
+        "left.null_idxs",
+        "right",
+        "right.startpts",
+        "right.null_idxs",
+    ] {
+        let name = format!("table1.id = table2.fid.{}", suffix);
+        assert_eq!(
+            fs::read(Path::new(DB_PATH).join("__join_keys__").join(&name)).unwrap(),
+            fs::read(db_path.join("__join_keys__").join(&name)).unwrap(),
+            "{}",
+            name
+        );
+    }
+    assert!(db_path
+        .join("__join_keys__")
+        .join("table1.id = table2.fid.meta")
+        .exists());
+    assert!(fs::read_dir(db_path.join("__join_keys__"))
+        .unwrap()
+        .all(|file| !file.unwrap().path().to_str().unwrap().ends_with(".tmp")));
+
+    // Once the key column changes, the written index is stale and must not be used.
+    let mut fids = vec![];
+    for _ in 0..8 {
+        fids.extend_from_slice(&0i32.to_ne_bytes());
+    }
+    fs::write(db_path.join("table2").join("fid"), fids).unwrap();
+
+    let expected: ResultSet = vec![(
+        new_group(),
+        new_rows(&[
+            (1, 3),
+            (1, 2),
+            (1, -4),
+            (1, 10),
+            (1, 30),
+            (1, -20),
+            (1, 0),
+            (1, 7),
+        ]),
+    )]
+    .into_iter()
+    .collect();
+
+    let db = DB::new(&db_path);
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+    let query = parser.parse(query, &Default::default()).unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+
+    fs::remove_dir_all(&db_path).unwrap();
+}
+
+#[test]
+fn theta_joins() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<i32>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), int(row.1)]);
+        }
+        records
+    }
+
+    fn some(rows: &[(i32, i32)]) -> Vec<(Option<i32>, Option<i32>)> {
+        rows.iter().map(|(a, b)| (Some(*a), Some(*b))).collect()
+    }
+
+    let greater = some(&[
+        (1, 0),
+        (2, 0),
+        (2, 1),
+        (3, 0),
+        (3, 1),
+        (3, 2),
+        (5, 0),
+        (5, 1),
+        (5, 2),
+        (5, 3),
+        (6, 0),
+        (6, 1),
+        (6, 2),
+        (6, 3),
+        (6, 5),
+        (8, 0),
+        (8, 1),
+        (8, 2),
+        (8, 3),
+        (8, 5),
+    ]);
+    let mut greater_or_none = greater.clone();
+    greater_or_none.push((Some(-3), None));
+
+    let expected: Vec<ResultSet> = vec![
+        some(&[
+            (-3, 0),
+            (-3, 1),
+            (-3, 2),
+            (-3, 3),
+            (-3, 5),
+            (-3, 10),
+            (1, 2),
+            (1, 3),
+            (1, 5),
+            (1, 10),
+            (2, 3),
+            (2, 5),
+            (2, 10),
+            (3, 5),
+            (3, 10),
+            (5, 10),
+            (6, 10),
+            (8, 10),
+        ]),
+        greater.clone(),
+        greater,
+        greater_or_none,
+        some(&[(-3, 0), (-3, 1), (-3, 2), (-3, 3), (-3, 5), (3, 3)]),
+        some(&[
+            (6, 3),
+            (6, 10),
+            (6, 2),
+            (6, 0),
+            (6, 5),
+            (6, 1),
+            (8, 3),
+            (8, 10),
+            (8, 2),
+            (8, 0),
+            (8, 5),
+            (8, 1),
+        ]),
+    ]
+    .into_iter()
+    .map(|rows| vec![(new_group(), new_rows(&rows))].into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select table1.a, table6.fid from table1 join table6 on table1.a < table6.fid",
+        "select table1.a, table6.fid from table1 join table6 on table1.a > table6.fid",
+        "select table1.a, table6.fid from table1 join table6 on table6.fid < table1.a",
+        "select table1.a, table6.fid from table1 left join table6 on table1.a > table6.fid",
+        "select table1.a, table6.fid from table1 join table6 \
+         on table6.fid between table1.a and table1.id",
+        "select table1.a, table6.fid from table1 join table6 on table1.a <> table6.fid \
+         where table1.a > 5",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn composite_keys() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, i32)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(row.0), DBVal::Int(row.1)]);
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(new_group(), new_rows(&[(1, 2)]))],
+        vec![(new_group(), new_rows(&[(1, 2)]))],
+        vec![(new_group(), new_rows(&[(0, 3), (1, 10), (1, 30)]))],
+        vec![(
+            new_group(),
+            new_rows(&[
+                (3, 3),
+                (2, 2),
+                (2, 30),
+                (30, 2),
+                (30, 30),
+                (-4, -4),
+                (-4, 3),
+                (10, 10),
+                (-20, -20),
+                (0, 0),
+                (7, 7),
+            ]),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select table1.id, table2.f from table1 join table2 \
+         on table1.id = table2.fid and table1.a = table2.f",
+        "select table1.id, table2.f from table1 join table2 \
+         on (table2.f = table1.a and (table2.fid = table1.id))",
+        "select table1.id, table2.f from table1 join table2 \
+         on table1.id = table2.fid and table1.a < table2.f",
+        "select table2.f, table4.f from table2 join table4 using (fid, d)",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+// table7 is:
+//  id | day        | flag  | score
+//   1 | 1970-01-01 | true  | 1.5
+//   3 | 1970-01-02 | false | 3.0
+//   5 | 1970-01-03 | true  | 8.0
+//   7 | 1970-01-02 | true  | -3.0
+// 100 | 1970-01-06 | false | 6.0
+// table8 is:
+// day        | flag  | n
+// 1970-01-02 | true  | 10
+// 1970-01-03 | false | 20
+// 1970-01-04 | true  | 30
+#[test]
+fn coerced_join_keys() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(DBVal, DBVal)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![row.0.clone(), row.1.clone()]);
+        }
+        records
+    }
+
+    fn ints_longs(rows: &[(i32, i64)]) -> Vec<(DBVal, DBVal)> {
+        rows.iter()
+            .map(|(a, b)| (DBVal::Int(*a), DBVal::Long(*b)))
+            .collect()
+    }
+
+    fn longs_ints(rows: &[(i64, i32)]) -> Vec<(DBVal, DBVal)> {
+        rows.iter()
+            .map(|(a, b)| (DBVal::Long(*a), DBVal::Int(*b)))
+            .collect()
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        ints_longs(&[(1, 1), (3, 3), (5, 5)]),
+        vec![
+            (DBVal::Int(3), DBVal::Double(3.)),
+            (DBVal::Int(-3), DBVal::Double(-3.)),
+            (DBVal::Int(6), DBVal::Double(6.)),
+            (DBVal::Int(8), DBVal::Double(8.)),
+        ],
+        ints_longs(&[
+            (0, 1),
+            (1, 1),
+            (1, 3),
+            (1, 5),
+            (1, 7),
+            (5, 1),
+            (5, 3),
+            (5, 5),
+            (5, 7),
+            (6, 1),
+            (7, 1),
+        ]),
+        longs_ints(&[(3, 10), (7, 10), (5, 20)]),
+        longs_ints(&[
+            (1, 10),
+            (1, 30),
+            (5, 10),
+            (5, 30),
+            (7, 10),
+            (7, 30),
+            (3, 20),
+            (100, 20),
+        ]),
+        vec![(
+            DBVal::DateTime(Utc.timestamp(86400, 0)),
+            DBVal::DateTime(Utc.timestamp(86400, 0)),
+        )],
+    ]
+    .into_iter()
+    .map(|rows| vec![(new_group(), new_rows(&rows))].into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select table1.a, table7.id from table1 join table7 on table1.a = table7.id",
+        "select table1.a, table7.score from table1 join table7 on table7.score = table1.a",
+        "select table1.id, table7.id from table1 join table7 on table1.c > table7.id",
+        "select table7.id, table8.n from table7 join table8 on table7.day = table8.day",
+        "select table7.id, table8.n from table7 join table8 on table7.flag = table8.flag",
+        "select table7.day, table8.day from table7 join table8 \
+         on table7.day = table8.day and table7.flag = table8.flag",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn incompatible_join_keys() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    let queries = [
+        "select table1.a from table1 join table2 on table1.a = table2.d",
+        "select table7.id from table7 join table8 on table7.day < table8.n",
+        "select table7.id from table7 join table8 using (flag) \
+         join table1 on table8.day = table1.id",
+        "select table1.a from table1 join table2 on table1.a + 1 = table2.d",
+        "select table7.id from table7 join table8 on table7.day = cast(table8.n as bigint)",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(result, Err(ParseError::JoinKeyTypeError(..))),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn pushdown_below_joins() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<i32>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), int(row.1)]);
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(),
+            new_rows(&[(Some(6), Some(0)), (Some(8), Some(7))]),
+        )],
+        vec![(new_group(), new_rows(&[(Some(3), Some(3))]))],
+        vec![(
+            new_group(),
+            new_rows(&[(Some(5), Some(10)), (Some(5), Some(30)), (Some(8), None)]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[(Some(3), Some(3)), (Some(5), Some(10)), (Some(5), Some(30))]),
+        )],
+        vec![(new_group(), new_rows(&[(None, Some(-4))]))],
+        vec![(
+            new_group(),
+            new_rows(&[(Some(10), Some(3)), (Some(30), Some(3))]),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select table1.a, table2.f from table1 join table2 on table1.id = table2.fid \
+         where table1.a > 2 and table2.f < 10",
+        "select table1.a, table2.f from table1 join table2 on table1.a = table2.f \
+         where table1.id < 5 and table2.fid = 0",
+        "select table1.a, table3.f from table1 left join table3 on table1.id = table3.fid \
+         where table1.a > 2 and (table3.f > 5 or table1.a > 7)",
+        "select table1.a, table3.f from table1 right join table3 on table1.id = table3.fid \
+         where table3.f > 2",
+        "select table1.a, table3.f from table1 right join table3 on table1.id = table3.fid \
+         where table1.a is null and table3.f < 5",
+        "select table2.f, table3.f from table1 join table2 on table1.id = table2.fid \
+         join table3 on table1.a = table3.fid where table2.f > 5 and table3.f < 20",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+
+    // Once the join was evaluated without a filter, as the experiments do, nothing is pushed down
+    // and the whole filter is left to evaluate.
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        query
+            .table
+            .table
+            .eval_join(&Default::default(), &mut exec_stats, None);
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+
+    // Nothing is left to join once the filter is pushed down.
+    let query = parser
+        .parse(
+            "select table1.a, table2.f from table1 join table2 on table1.id = table2.fid \
+             where table1.a > 100",
+            &Default::default(),
+        )
+        .unwrap();
+    let mut exec_stats = ExecStats::new();
+    assert!(exec
+        .run(query, &Default::default(), &mut exec_stats)
+        .is_empty());
+}
+
+#[test]
+fn implicit_joins() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    // Each query with a comma-separated FROM list, along with the same query written with joins.
+    let queries = [
+        (
+            "select table1.a, table2.f from table1, table2 \
+             where table1.id = table2.fid and table1.a > 2 and table2.f < 10",
+            "select table1.a, table2.f from table1 join table2 on table1.id = table2.fid \
+             where table1.a > 2 and table2.f < 10",
+        ),
+        (
+            "select table1.a, table2.f from table1, table2 \
+             where table2.fid = table1.id and table1.a < table2.f",
+            "select table1.a, table2.f from table1 join table2 on table1.id = table2.fid \
+             where table1.a < table2.f",
+        ),
+        (
+            "select table3.f, table6.e from table3, table6, table1 \
+             where table1.id = table3.fid and table1.id = table6.fid",
+            "select table3.f, table6.e from table1 join table3 on table1.id = table3.fid \
+             join table6 on table1.id = table6.fid",
+        ),
+        (
+            "select table1.a, table3.f, table2.f from table1 left join table3 \
+             on table1.id = table3.fid, table2 where table2.fid = table1.id",
+            "select table1.a, table3.f, table2.f from table1 left join table3 \
+             on table1.id = table3.fid join table2 on table2.fid = table1.id",
+        ),
+    ];
+    for (implicit, explicit) in &queries {
+        let query = parser.parse(implicit, &Default::default()).unwrap();
+        assert!(
+            query.table.table.to_string().contains(" JOIN "),
+            "{}",
+            query.table.table
+        );
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+
+        let query = parser.parse(explicit, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let expected =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected, result, "{}", implicit);
+    }
+}
+
+#[test]
+fn cross_joins() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    let queries = [
+        "select table1.a, table2.f from table1, table2",
+        "select table1.a, table2.f from table1, table2 where table1.a > table2.f",
+        "select table1.a, table2.f from table1, table2, table3 where table1.id = table2.fid",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(result, Err(ParseError::CrossJoin(..))),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn self_joins() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[Vec<i32>]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(row.iter().map(|val| DBVal::Int(*val)).collect());
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(),
+            new_rows(&[
+                vec![0, 1],
+                vec![1, 2],
+                vec![2, 3],
+                vec![3, 5],
+                vec![4, 3],
+                vec![6, 6],
+            ]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[vec![1, 2], vec![2, 3], vec![4, 3], vec![6, 6]]),
+        )],
+        vec![(new_group(), new_rows(&[vec![3, 3, 5], vec![2, 5, 3]]))],
+        vec![(new_group(), new_rows(&[vec![3, 3, 5], vec![2, 5, 3]]))],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select t1.id, t2.id from table1 t1 join table1 t2 on t1.a = t2.id",
+        "select t1.id, t2.id from table1 t1 join table1 t2 on t1.a = t2.id where t2.a > 2",
+        "select table3.f, x.a, y.a from table3 join table1 x on table3.fid = x.id \
+         join table1 y on table3.f = y.id",
+        "select table3.f, x.a, y.a from table3, table1 x, table1 y \
+         where table3.fid = x.id and y.id = table3.f",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn self_join_without_aliases() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    let queries = [
+        "select table1.id from table1 join table1 on table1.a = table1.id",
+        "select table1.id from table1, table1 where table1.a = table1.id",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(result, Err(ParseError::DuplicateTableName(..))),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn unknown_tables() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    let queries = [
+        "select t.a from table9 t",
+        "select table1.id from table1 join table9 on table1.a = table9.id",
+        "select table1.id from table1, table9 where table1.a = table9.id",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(result, Err(ParseError::TableDoesNotExist(..))),
+            "{}",
+            query
+        );
+    }
+
+    let queries = [
+        "select t.a from public.table1 t",
+        "select t.a from (select table1.a from table1) t",
+        "select table1.id from table1, public.table3 where table1.id = table3.fid",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(result, Err(ParseError::UnsupportedRelation(..))),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn bloom_filter_joins() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<i32>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), int(row.1)]);
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(2), Some(10)),
+                (Some(2), Some(30)),
+                (Some(4), Some(10)),
+                (Some(4), Some(30)),
+            ]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(10), Some(2)),
+                (Some(10), Some(4)),
+                (Some(30), Some(2)),
+                (Some(30), Some(4)),
+            ]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[(Some(0), None), (Some(1), Some(3))]),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    // None of these joins has a prebuilt index, and each has a filter on its smaller side.
+    let queries = [
+        "select table1.id, table3.f from table1 join table3 on table1.a = table3.fid \
+         where table3.f > 5",
+        "select table3.f, table1.id from table3 left join table1 on table3.fid = table1.a \
+         where table3.f > 5",
+        "select table1.id, table3.f from table1 left join table3 on table1.a = table3.fid \
+         where table1.id < 2",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let mut joined_records = vec![];
+        for bloom_filter_joins in [false, true] {
+            let exec_params = ExecParams {
+                bloom_filter_joins,
+                ..Default::default()
+            };
+            let parsed = parser.parse(query, &Default::default()).unwrap();
+            let mut exec_stats = ExecStats::new();
+            let result = utils::process_dbresults(exec.run(parsed, &exec_params, &mut exec_stats));
+            assert_eq!(expected[i], result, "{}", query);
+            assert_eq!(
+                exec_stats.num_bloom_filtered_records > 0,
+                bloom_filter_joins,
+                "{}",
+                query
+            );
+            joined_records.push(exec_stats.num_joined_records);
+        }
+        assert_eq!(joined_records[0], joined_records[1], "{}", query);
+    }
+}
+
+#[test]
+fn sort_merge_joins() {
+    // Without the prebuilt join indexes, every join here is evaluated by a join algorithm.
+    let db_path = copy_db_without_join_keys("sort-merge");
+    let db = DB::new(&db_path);
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    let queries = [
+        "select table1.a, table3.f from table1 join table3 on table1.id = table3.fid",
+        "select table1.a, table3.f from table1 left join table3 on table3.fid = table1.a",
+        "select table1.a, table3.f from table3 right join table1 on table3.fid = table1.a \
+         where table1.id < 5",
+        "select table1.id, table2.f from table1 join table2 \
+         on table1.id = table2.fid and table1.a < table2.f",
+        "select table2.f, table4.f from table2 join table4 using (fid, d)",
+        "select table7.id, table8.n from table7 join table8 \
+         on table7.day = table8.day and table7.flag = table8.flag",
+        "select table1.id, table2.f, table3.f from table1 \
+         join table2 on table1.id = table2.fid join table3 on table2.f = table3.f",
+    ];
+    for query in queries.iter() {
+        let mut results = vec![];
+        let mut join_idxs = vec![];
+        for sort_merge_joins in [false, true] {
+            let exec_params = ExecParams {
+                sort_merge_joins,
+                ..Default::default()
+            };
+            let parsed = parser.parse(query, &Default::default()).unwrap();
+            let table = parsed.table.table.clone();
+            let mut exec_stats = ExecStats::new();
+            results.push(utils::process_dbresults(exec.run(
+                parsed,
+                &exec_params,
+                &mut exec_stats,
+            )));
+            join_idxs.push(table.get_map().clone());
+        }
+        assert!(!results[0].is_empty(), "{}", query);
+        assert_eq!(results[0], results[1], "{}", query);
+        assert_eq!(join_idxs[0], join_idxs[1], "{}", query);
+    }
+
+    fs::remove_dir_all(&db_path).unwrap();
+}
+
+#[test]
+fn bloom_filter_joins_read_fewer_keys() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    // The keys of the second conjunct are only read for the rows of table1 the filter keeps.
+    let query = "select table1.id, table3.f from table1 join table3 \
+                 on table1.a = table3.fid and table1.id < table3.f where table3.f > 5";
+    let mut results = vec![];
+    let mut join_key_records = vec![];
+    for bloom_filter_joins in [false, true] {
+        let exec_params = ExecParams {
+            bloom_filter_joins,
+            ..Default::default()
+        };
+        let parsed = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        results.push(utils::process_dbresults(exec.run(
+            parsed,
+            &exec_params,
+            &mut exec_stats,
+        )));
+        join_key_records.push(exec_stats.num_join_key_records);
+    }
+    assert_eq!(results[0], results[1], "{}", query);
+    assert!(join_key_records[1] < join_key_records[0], "{}", query);
+}
@@ -0,0 +1,123 @@
+mod utils;
+
+use chameleon::{ExecStats, Executor, ParseParams, Parser, DB};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use utils::{DBVal, ResultSet};
+
+const DB_PATH: &str = "data/test-data/mt-test";
+
+// A has 20 rows, C 40, F 70 and G 80. Every column is 0, 1, 2, ... in row order, so each join
+// below matches rows one to one.
+const QUERY: &str = "select G.x from G join F on F.g = G.f join C on C.f = F.c \
+                     join A on A.c = C.a";
+
+#[test]
+fn reorder_joins() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[i32]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(*row)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&(0..20).collect::<Vec<_>>()))]
+        .into_iter()
+        .collect();
+
+    let params = [
+        ParseParams {
+            reorder_joins: true,
+            ..Default::default()
+        },
+        ParseParams::default(),
+    ];
+    // The smallest table goes first unless the joins are built as written.
+    let orders = ["A JOIN C ON", "G JOIN F ON"];
+    for (params, order) in params.iter().zip(&orders) {
+        let query = parser.parse(QUERY, params).unwrap();
+        assert!(
+            query.table.table.to_string().starts_with(order),
+            "{}",
+            query.table.table
+        );
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn reorder_joins_with_selectivities() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[i32]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![DBVal::Int(*row)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[0, 1, 2]))]
+        .into_iter()
+        .collect();
+
+    // A selective predicate on G makes it the smallest input once pushed down.
+    let mut selectivities = HashMap::new();
+    selectivities.insert("G.x < 3".to_string(), 0.01);
+    let params = ParseParams {
+        reorder_joins: true,
+        selectivities,
+        ..Default::default()
+    };
+    let query = parser
+        .parse(&format!("{} where G.x < 3", QUERY), &params)
+        .unwrap();
+    assert!(
+        query.table.table.to_string().starts_with("G JOIN F ON"),
+        "{}",
+        query.table.table
+    );
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn outer_joins_keep_written_order() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    let query = parser
+        .parse(
+            "select G.x from G join F on F.g = G.f left join C on C.f = F.c \
+             join A on A.c = C.a",
+            &ParseParams {
+                reorder_joins: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert!(
+        query.table.table.to_string().starts_with("G JOIN F ON"),
+        "{}",
+        query.table.table
+    );
+}
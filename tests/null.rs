@@ -0,0 +1,397 @@
+mod utils;
+
+use chameleon::{ApproxOptType, ExecParams, ExecStats, Executor, Parser, DB};
+use std::collections::HashSet;
+use std::path::Path;
+use utils::{DBVal, ResultSet};
+
+const DB_PATH: &str = "data/test-data/null-test";
+
+// table1 is:
+//    a | b    | c
+//    1 | x    | 1.5
+// NULL | y    | 2.5
+//    3 | NULL | NULL
+// NULL | x    | 4.5
+//    5 | y    | NULL
+//    6 | NULL | 6.5
+
+fn int(val: Option<i32>) -> DBVal {
+    val.map_or(DBVal::Null, DBVal::Int)
+}
+
+fn string(val: Option<&str>) -> DBVal {
+    val.map_or(DBVal::Null, |s| DBVal::Str(s.to_string()))
+}
+
+#[test]
+fn is_null() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<&str>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), string(row.1)]);
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(),
+            new_rows(&[(None, Some("y")), (None, Some("x"))]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(1), Some("x")),
+                (None, Some("y")),
+                (None, Some("x")),
+                (Some(5), Some("y")),
+            ]),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select a, b from table1 where a is null",
+        "select a, b from table1 where b is not null",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result);
+    }
+}
+
+#[test]
+fn comparisons_skip_nulls() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<&str>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), string(row.1)]);
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(),
+            new_rows(&[(Some(3), None), (Some(5), Some("y")), (Some(6), None)]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[(Some(1), Some("x")), (Some(5), Some("y"))]),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select a, b from table1 where a > 2",
+        "select a, b from table1 where a <> 3 and b <> 'z'",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result);
+    }
+}
+
+#[test]
+fn null_in_disjunction() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<&str>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), string(row.1)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(
+        new_group(),
+        new_rows(&[
+            (Some(1), Some("x")),
+            (Some(3), None),
+            (None, Some("x")),
+            (Some(5), Some("y")),
+            (Some(6), None),
+        ]),
+    )]
+    .into_iter()
+    .collect();
+
+    let query = "select a, b from table1 where a > 2 or b = 'x'";
+    for approx_opt_type in [ApproxOptType::OnePredLookahead, ApproxOptType::Tdacb] {
+        let mut exec = Executor::new(&db, None, None);
+        let exec_params = ExecParams {
+            approx_opt_type,
+            ..Default::default()
+        };
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result = utils::process_dbresults(exec.run(query, &exec_params, &mut exec_stats));
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn null_arithmetic() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[Option<i64>]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![row.map_or(DBVal::Null, DBVal::Long)]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[Some(2), None]))]
+        .into_iter()
+        .collect();
+
+    let query = parser
+        .parse(
+            "select a + 1 from table1 where b = 'x'",
+            &Default::default(),
+        )
+        .unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn in_list_with_nulls() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(Option<i32>, Option<bool>)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(row.0), row.1.map_or(DBVal::Null, DBVal::Bool)]);
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        // Rows which match no item are NULL, not TRUE
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(1), Some(false)),
+                (None, None),
+                (Some(3), None),
+                (Some(5), None),
+                (Some(6), None),
+            ]),
+        )],
+        vec![(
+            new_group(),
+            new_rows(&[
+                (Some(1), Some(true)),
+                (None, None),
+                (Some(3), None),
+                (Some(5), None),
+                (Some(6), None),
+            ]),
+        )],
+        // Only the rows where c is NULL are unknown
+        vec![(
+            new_group(),
+            new_rows(&[(Some(6), Some(true)), (Some(1), Some(false))]),
+        )],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select a, a not in (1, null) from table1",
+        "select a, a in (1, null) from table1",
+        "select a, a not in (1, c) from table1 where a not in (1, c) or a = 1",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let parsed = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(parsed, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", query);
+    }
+}
+
+#[test]
+fn aggregates_skip_nulls() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group() -> Vec<DBVal> {
+        vec![]
+    }
+
+    fn new_rows(rows: &[(i32, i32, i32, f64)]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![
+                DBVal::Int(row.0),
+                DBVal::Int(row.1),
+                DBVal::Int(row.2),
+                DBVal::Double(row.3),
+            ]);
+        }
+        records
+    }
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[(15, 4, 6, 3.75)]))]
+        .into_iter()
+        .collect();
+
+    let query = parser
+        .parse(
+            "select sum(a), count(a), count(*), avg(c) from table1",
+            &Default::default(),
+        )
+        .unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn group_by_with_nulls() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group(s: Option<&str>) -> Vec<DBVal> {
+        vec![string(s)]
+    }
+
+    fn new_rows(rows: &[Option<i32>]) -> HashSet<Vec<DBVal>> {
+        let mut records = HashSet::new();
+        for row in rows {
+            records.insert(vec![int(*row)]);
+        }
+        records
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![
+            (new_group(Some("x")), new_rows(&[Some(1)])),
+            (new_group(Some("y")), new_rows(&[Some(5)])),
+            (new_group(None), new_rows(&[Some(9)])),
+        ],
+        vec![
+            (new_group(Some("x")), new_rows(&[None])),
+            (new_group(Some("y")), new_rows(&[None])),
+        ],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select sum(a) from table1 group by b",
+        "select max(a) from table1 where a is null group by b",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result);
+    }
+}
+
+#[test]
+fn distinct_with_nulls() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    fn new_group(s: Option<&str>) -> Vec<DBVal> {
+        s.map_or(vec![], |s| vec![string(Some(s))])
+    }
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(
+            new_group(None),
+            vec![vec![int(Some(2)), int(Some(4)), int(Some(4))]],
+        )],
+        // NULLs are one value
+        vec![(
+            new_group(None),
+            vec![
+                vec![string(Some("x"))],
+                vec![string(Some("y"))],
+                vec![string(None)],
+            ],
+        )],
+        // Groups of only NULLs still aggregate to NULL
+        vec![
+            (new_group(Some("x")), vec![vec![int(None)]]),
+            (new_group(Some("y")), vec![vec![int(None)]]),
+        ],
+    ]
+    .into_iter()
+    .map(|x| {
+        x.into_iter()
+            .map(|(group, rows)| (group, rows.into_iter().collect::<HashSet<_>>()))
+            .collect()
+    })
+    .collect();
+
+    let queries = [
+        "select count(distinct b), count(b), count(distinct a) from table1",
+        "select distinct b from table1",
+        "select max(distinct a) from table1 where a is null group by b",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
@@ -0,0 +1,240 @@
+mod utils;
+
+use chameleon::{ExecStats, Executor, ParseError, Parser, DB};
+use std::path::Path;
+use utils::DBVal;
+
+const NULL_DB_PATH: &str = "data/test-data/null-test";
+const GROUP_BY_DB_PATH: &str = "data/test-data/group-by-test";
+
+// table1 of null-test is:
+//    a | b    | c
+//    1 | x    | 1.5
+// NULL | y    | 2.5
+//    3 | NULL | NULL
+// NULL | x    | 4.5
+//    5 | y    | NULL
+//    6 | NULL | 6.5
+
+fn int(val: Option<i32>) -> DBVal {
+    val.map_or(DBVal::Null, DBVal::Int)
+}
+
+fn string(val: Option<&str>) -> DBVal {
+    val.map_or(DBVal::Null, |s| DBVal::Str(s.to_string()))
+}
+
+fn double(val: Option<f64>) -> DBVal {
+    val.map_or(DBVal::Null, DBVal::Double)
+}
+
+fn run_queries(db_path: &str, queries: &[&str]) -> Vec<Vec<Vec<DBVal>>> {
+    let db = DB::new(Path::new(db_path));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    queries
+        .iter()
+        .map(|query| {
+            let parsed = parser.parse(query, &Default::default()).unwrap();
+            let mut exec_stats = ExecStats::new();
+            let results = exec.run(parsed, &Default::default(), &mut exec_stats);
+            assert_eq!(exec_stats.num_final_records, results[0].len() as u128);
+            utils::process_ordered_dbresults(results)
+        })
+        .collect()
+}
+
+#[test]
+fn order_by() {
+    let expected: Vec<Vec<Vec<DBVal>>> = vec![
+        // NULLs are last when ascending
+        vec![
+            vec![int(Some(1)), string(Some("x"))],
+            vec![int(Some(3)), string(None)],
+            vec![int(Some(5)), string(Some("y"))],
+            vec![int(Some(6)), string(None)],
+            vec![int(None), string(Some("x"))],
+            vec![int(None), string(Some("y"))],
+        ],
+        // and first when descending
+        vec![
+            vec![int(Some(3))],
+            vec![int(Some(5))],
+            vec![int(Some(6))],
+            vec![int(None)],
+            vec![int(None)],
+            vec![int(Some(1))],
+        ],
+        vec![
+            vec![int(Some(5)), double(None)],
+            vec![int(Some(3)), double(None)],
+            vec![int(Some(1)), double(Some(1.5))],
+        ],
+        // By alias and ordinal
+        vec![
+            vec![string(Some("y")), int(Some(5))],
+            vec![string(Some("y")), int(None)],
+            vec![string(Some("x")), int(Some(1))],
+            vec![string(Some("x")), int(None)],
+            vec![string(None), int(Some(3))],
+            vec![string(None), int(Some(6))],
+        ],
+        // By an expression which is not projected
+        vec![
+            vec![string(None)],
+            vec![string(Some("y"))],
+            vec![string(None)],
+            vec![string(Some("x"))],
+        ],
+        vec![vec![int(Some(5))], vec![int(Some(6))]],
+        vec![vec![int(Some(3))], vec![int(Some(5))]],
+        vec![
+            vec![string(Some("x"))],
+            vec![string(Some("y"))],
+            vec![string(None)],
+        ],
+    ];
+
+    let queries = vec![
+        "select a, b from table1 order by a, b",
+        "select a from table1 order by c desc, a",
+        "select a, c from table1 order by c nulls first, a desc limit 3",
+        "select b as name, a from table1 order by name desc nulls last, 2",
+        "select b from table1 where a is not null order by 0 - a",
+        "select a from table1 where a > 1 order by a offset 1",
+        "select a from table1 order by a limit 2 offset 1 rows",
+        "select distinct b from table1 order by b",
+    ];
+
+    let results = run_queries(NULL_DB_PATH, &queries);
+    for (i, result) in results.into_iter().enumerate() {
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn order_by_groups() {
+    let expected: Vec<Vec<Vec<DBVal>>> = vec![
+        vec![
+            vec![int(Some(17)), int(Some(8))],
+            vec![int(Some(5)), int(Some(6))],
+            vec![int(Some(3)), int(Some(3))],
+        ],
+        vec![vec![int(Some(3))], vec![int(Some(5))]],
+        vec![vec![int(Some(8))], vec![int(Some(6))]],
+        vec![vec![int(Some(3))], vec![int(Some(5))]],
+        // Each group is a single row, even with the GROUP BY keys projected
+        vec![
+            vec![string(Some("a")), int(Some(17))],
+            vec![string(Some("b")), int(Some(5))],
+        ],
+        vec![
+            vec![string(Some("c")), int(Some(1))],
+            vec![string(Some("b")), int(Some(3))],
+            vec![string(Some("a")), int(Some(4))],
+        ],
+        vec![
+            vec![string(Some("b")), int(Some(6))],
+            vec![string(Some("a")), int(Some(8))],
+        ],
+    ];
+
+    let queries = vec![
+        "select sum(a), max(a) from table1 group by b order by 1 desc",
+        "select sum(a) as total from table1 group by b order by total limit 2",
+        "select max(a) from table1 group by b order by sum(a) desc limit 2",
+        "select sum(a) from table1 group by b having sum(a) < 10 order by 1",
+        "select b, sum(a) as s from table1 group by b order by s desc limit 2",
+        "select b, count(a) from table1 group by b order by b desc",
+        "select b, max(a) from table1 group by b having sum(a) > 4 order by 2",
+    ];
+
+    let results = run_queries(GROUP_BY_DB_PATH, &queries);
+    for (i, result) in results.into_iter().enumerate() {
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+
+    let query = "select b, sum(a) as s from table1 group by b order by s desc limit 2";
+    let results = run_queries(NULL_DB_PATH, &[query]);
+    assert_eq!(
+        vec![
+            vec![string(None), int(Some(9))],
+            vec![string(Some("y")), int(Some(5))],
+        ],
+        results[0],
+        "{}",
+        query
+    );
+}
+
+#[test]
+fn order_by_options_keep_other_tokens() {
+    let expected: Vec<Vec<Vec<DBVal>>> = vec![
+        vec![vec![int(None)]],
+        vec![vec![int(None)], vec![int(None)], vec![int(Some(6))]],
+        vec![vec![string(Some("x"))]],
+        // Intervals of months are sort keys too
+        vec![vec![int(Some(6))], vec![int(Some(5))], vec![int(Some(3))]],
+    ];
+
+    let queries = vec![
+        "select a from table1 where b in ('x', 'offset 1') order by a desc nulls last offset 1",
+        "select a as nulls from table1 where c > 2 order by nulls nulls first",
+        "select b from table1 where b < 'x''' order by b nulls last limit 1",
+        "select a from table1 where a > 2 order by interval '1' month, a desc",
+    ];
+
+    let results = run_queries(NULL_DB_PATH, &queries);
+    for (i, result) in results.into_iter().enumerate() {
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+}
+
+#[test]
+fn limit_without_order_by() {
+    let results = run_queries(NULL_DB_PATH, &["select a, b from table1 limit 4"]);
+    assert_eq!(4, results[0].len());
+}
+
+#[test]
+fn order_by_errors() {
+    let db = DB::new(Path::new(NULL_DB_PATH));
+    let parser = Parser::new(&db);
+
+    let queries = [
+        "select a from table1 order by 2",
+        "select a from table1 order by z",
+        "select distinct b from table1 order by a",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(result, Err(ParseError::OrderByError(_))),
+            "{}",
+            query
+        );
+    }
+
+    let queries = [
+        "select a from table1 order by a nulls",
+        "select a from table1 order by a nulls middle",
+        "select a from table1 order by a limit 1 offset 1 rows b",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(result, Err(ParseError::RawParserError(_))),
+            "{}",
+            query
+        );
+    }
+
+    let query = "select a from table1 order by a fetch first 2 rows only";
+    let result = parser.parse(query, &Default::default());
+    assert!(
+        matches!(result, Err(ParseError::LimitError(_))),
+        "{}",
+        query
+    );
+}
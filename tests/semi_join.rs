@@ -0,0 +1,182 @@
+mod utils;
+
+use chameleon::{
+    query_utils, ApproxOptType, ExecParams, ExecStats, Executor, ParseError, Parser, DB,
+};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use utils::{DBVal, ResultSet};
+
+const DB_PATH: &str = "data/test-data/join-test";
+
+// table1.id is 0, ..., 7 and table1.a is 1, 2, 3, 5, 3, -3, 6, 8.
+// table3.fid is 2, 3, 10, 3, 3 and table3.f is 3, 2, -4, 10, 30.
+
+fn new_group() -> Vec<DBVal> {
+    vec![]
+}
+
+fn new_rows(rows: &[i32]) -> HashSet<Vec<DBVal>> {
+    let mut records = HashSet::new();
+    for row in rows {
+        records.insert(vec![DBVal::Int(*row)]);
+    }
+    records
+}
+
+#[test]
+fn in_subquery_and_exists() {
+    let db = DB::new(Path::new(DB_PATH));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    let expected: Vec<ResultSet> = vec![
+        vec![(new_group(), new_rows(&[2, 3]))],
+        vec![(new_group(), new_rows(&[0, 1, 2, 4, 5, 6, 7]))],
+        vec![(new_group(), new_rows(&[1, 2, 4]))],
+        vec![(new_group(), new_rows(&[0, 1, 4, 5, 6, 7]))],
+        vec![(new_group(), new_rows(&[0, 5]))],
+        vec![(new_group(), new_rows(&[2]))],
+    ]
+    .into_iter()
+    .map(|x| x.into_iter().collect())
+    .collect();
+
+    let queries = [
+        "select table1.id from table1 where table1.id in (select table3.fid from table3)",
+        "select table1.id from table1 \
+         where table1.id not in (select table3.fid from table3 where table3.f > 5)",
+        "select table1.id from table1 \
+         where exists (select * from table3 where table3.fid = table1.a and table3.f < 5)",
+        "select table1.id from table1 \
+         where not exists (select * from table3 where table1.id = table3.fid)",
+        "select table1.id from table1 \
+         where table1.a < 2 and exists (select * from table3 where table3.f > 20)",
+        "select t.id from table1 t \
+         where t.a in (select table3.f from table3 where table3.fid = t.id)",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        let query = parser.parse(query, &Default::default()).unwrap();
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+        assert_eq!(expected[i], result, "{}", queries[i]);
+    }
+
+    // Nothing passes if the subquery has no rows.
+    let query = parser
+        .parse(
+            "select table1.id from table1 \
+             where exists (select * from table3 where table3.f > 100)",
+            &Default::default(),
+        )
+        .unwrap();
+    let mut exec_stats = ExecStats::new();
+    assert!(exec
+        .run(query, &Default::default(), &mut exec_stats)
+        .is_empty());
+}
+
+#[test]
+fn semi_joins_in_disjunctions() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[2, 3, 6, 7]))]
+        .into_iter()
+        .collect();
+
+    let query = parser
+        .parse(
+            "select table1.id from table1 \
+             where table1.a > 5 or table1.id in (select table3.fid from table3)",
+            &Default::default(),
+        )
+        .unwrap();
+    let selectivities = query.filter.as_ref().map(|f| {
+        let mut selectivities = HashMap::new();
+        query_utils::estimate_selectivities(f, &mut selectivities);
+        selectivities
+    });
+    assert_eq!(
+        selectivities.as_ref().unwrap()["table1.id IN (SELECT table3.fid FROM table3)"],
+        0.25
+    );
+
+    let mut exec = Executor::new(&db, selectivities, None);
+    for approx_opt_type in [
+        ApproxOptType::NoApproxOpt,
+        ApproxOptType::OnePredLookahead,
+        ApproxOptType::Tdacb,
+        ApproxOptType::BDCWithBestD,
+    ] {
+        let exec_params = ExecParams {
+            approx_opt_type,
+            ..Default::default()
+        };
+        let mut exec_stats = ExecStats::new();
+        let result =
+            utils::process_dbresults(exec.run(query.clone(), &exec_params, &mut exec_stats));
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn not_in_with_nulls() {
+    // table1.a is 1, NULL, 3, NULL, 5, 6.
+    let db = DB::new(Path::new("data/test-data/null-test"));
+    let mut exec = Executor::new(&db, None, None);
+    let parser = Parser::new(&db);
+
+    let expected: ResultSet = vec![(new_group(), new_rows(&[1, 3]))].into_iter().collect();
+
+    let query = parser
+        .parse(
+            "select table1.a from table1 \
+             where table1.a not in (select t.a from table1 t where t.a > 4)",
+            &Default::default(),
+        )
+        .unwrap();
+    let mut exec_stats = ExecStats::new();
+    let result = utils::process_dbresults(exec.run(query, &Default::default(), &mut exec_stats));
+    assert_eq!(expected, result);
+
+    // A NULL among the subquery's keys makes NOT IN unknown for every row.
+    let query = parser
+        .parse(
+            "select table1.a from table1 where table1.a not in (select t.a from table1 t)",
+            &Default::default(),
+        )
+        .unwrap();
+    let mut exec_stats = ExecStats::new();
+    assert!(exec
+        .run(query, &Default::default(), &mut exec_stats)
+        .is_empty());
+}
+
+#[test]
+fn unsupported_subqueries() {
+    let db = DB::new(Path::new(DB_PATH));
+    let parser = Parser::new(&db);
+
+    let queries = [
+        "select table1.id from table1 \
+         where table1.id in (select table3.fid from table3 group by table3.fid)",
+        "select table1.id from table1 \
+         where table1.id in (select table3.fid, table3.f from table3)",
+        "select table1.id from table1 \
+         where exists (select * from table3 where table3.f < table1.a)",
+        "select table1.id from table1 \
+         where table1.a > 5 or exists (select * from table3, table2)",
+        "select table1.id from table1 \
+         where exists (select * from table3 join table3 on table3.f = table3.fid)",
+    ];
+    for query in &queries {
+        let result = parser.parse(query, &Default::default());
+        assert!(
+            matches!(result, Err(ParseError::FilterError(..))),
+            "{}",
+            query
+        );
+    }
+}
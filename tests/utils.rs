@@ -79,6 +79,8 @@ pub enum DBVal {
     Bool(bool),
     DateTime(DateTime<Utc>),
     Duration(Duration),
+    Months(i32),
+    Null,
 }
 
 impl Eq for DBVal {}
@@ -110,6 +112,10 @@ impl Hash for DBVal {
             DBVal::Duration(d) => {
                 d.hash(state);
             }
+            DBVal::Months(m) => {
+                m.hash(state);
+            }
+            DBVal::Null => {}
         }
     }
 }
@@ -131,6 +137,39 @@ pub fn print_result_set(rs: &ResultSet, tag: &str) {
     }
 }
 
+fn result_val(result: &DBResult, group: &[DBCol], i: usize) -> DBVal {
+    let col = result.cols.get(group).expect("Col didn't have group");
+    if result.is_null(group, i) {
+        return DBVal::Null;
+    }
+    match col {
+        DBCol::Int(vals) => DBVal::Int(vals[i]),
+        DBCol::Long(vals) => DBVal::Long(vals[i]),
+        DBCol::Float(vals) => DBVal::Float(vals[i]),
+        DBCol::Double(vals) => DBVal::Double(vals[i]),
+        DBCol::Str(vals) => DBVal::Str(vals[i].clone()),
+        DBCol::Bool(vals) => DBVal::Bool(vals[i]),
+        DBCol::DateTime(vals) => DBVal::DateTime(vals[i]),
+        DBCol::Duration(vals) => DBVal::Duration(vals[i]),
+        DBCol::Months(vals) => DBVal::Months(vals[i]),
+    }
+}
+
+// Returns the rows of results which were ordered, and so are ungrouped, in their order
+pub fn process_ordered_dbresults(results: Vec<DBResult>) -> Vec<Vec<DBVal>> {
+    assert!(!results.is_empty());
+    assert!(results.iter().all(|result| result.is_ungrouped()));
+
+    (0..results[0].len())
+        .map(|i| {
+            results
+                .iter()
+                .map(|result| result_val(result, &[], i))
+                .collect()
+        })
+        .collect()
+}
+
 // Returns as a hash map of rows
 pub fn process_dbresults(results: Vec<DBResult>) -> ResultSet {
     assert!(!results.is_empty());
@@ -144,25 +183,17 @@ pub fn process_dbresults(results: Vec<DBResult>) -> ResultSet {
         let mut rows = HashSet::new();
         let col = results[0].cols.get(group).unwrap();
         for i in 0..col.len() {
-            let mut row = vec![];
-            for result in &results {
-                let col = result.cols.get(group).expect("Col didn't have group");
-                row.push(match col {
-                    DBCol::Int(vals) => DBVal::Int(vals[i]),
-                    DBCol::Long(vals) => DBVal::Long(vals[i]),
-                    DBCol::Float(vals) => DBVal::Float(vals[i]),
-                    DBCol::Double(vals) => DBVal::Double(vals[i]),
-                    DBCol::Str(vals) => DBVal::Str(vals[i].clone()),
-                    DBCol::Bool(vals) => DBVal::Bool(vals[i]),
-                    DBCol::DateTime(vals) => DBVal::DateTime(vals[i]),
-                    DBCol::Duration(vals) => DBVal::Duration(vals[i]),
-                });
-            }
+            let row = results
+                .iter()
+                .map(|result| result_val(result, group, i))
+                .collect();
             rows.insert(row);
         }
         let group = group
             .iter()
             .map(|col| match col {
+                // The group of NULL keys
+                col if col.len() == 0 => DBVal::Null,
                 DBCol::Int(vals) => DBVal::Int(vals[0]),
                 DBCol::Long(vals) => DBVal::Long(vals[0]),
                 DBCol::Float(vals) => DBVal::Float(vals[0]),
@@ -171,6 +202,7 @@ pub fn process_dbresults(results: Vec<DBResult>) -> ResultSet {
                 DBCol::Bool(vals) => DBVal::Bool(vals[0]),
                 DBCol::DateTime(vals) => DBVal::DateTime(vals[0]),
                 DBCol::Duration(vals) => DBVal::Duration(vals[0]),
+                DBCol::Months(vals) => DBVal::Months(vals[0]),
             })
             .collect();
         parsed_results.insert(group, rows);